@@ -0,0 +1,59 @@
+use crate::{Client, ClientError, MapInfo};
+
+impl Client {
+    /// the map that is played after the current one
+    pub fn get_next_map_info(&mut self) -> Result<MapInfo, ClientError> {
+        self.call("GetNextMapInfo", ())
+    }
+
+    /// tells everyone what the next map is, once per map and only if it is a
+    /// different one
+    pub(crate) fn announce_next_map(&mut self) -> Result<(), ClientError> {
+        let Some(template) = self.config().announce_next_map.clone() else {
+            return Ok(());
+        };
+        if self.announced {
+            return Ok(());
+        }
+        self.announced = true;
+
+        let next = self.get_next_map_info()?;
+        // a restart plays the same map again
+        if self.current_map.as_ref().is_some_and(|m| m.UId == next.UId) {
+            return Ok(());
+        }
+        let msg = fill_template(&template, &next);
+        self.call::<bool>("ChatSendServerMessage", msg.as_str())?;
+        Ok(())
+    }
+
+    /// `/nextinfo` tells the player what comes after the current map
+    pub(crate) fn nextinfo_command(&mut self, login: &str) -> Result<(), ClientError> {
+        // a staged map is inserted right after the current one at the podium
+        if let Some(staged) = self.staged.front() {
+            let name = staged
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("tmx map {}", staged.id));
+            let reply = format!("next map: {name}");
+            return self.chat_send_to_login(login, &reply);
+        }
+        let reply = match self.get_next_map_info() {
+            Ok(next) if !next.UId.is_empty() => {
+                fill_template("next map: {name} by {author}, {style}", &next)
+            }
+            Err(err) if err.is_connection() => return Err(err),
+            _ => "next map not chosen yet".to_owned(),
+        };
+        self.chat_send_to_login(login, &reply)
+    }
+}
+
+/// replaces `{name}`, `{author}`, `{style}` and `{env}` with the map's
+pub fn fill_template(template: &str, map: &MapInfo) -> String {
+    template
+        .replace("{name}", &map.Name)
+        .replace("{author}", &map.AuthorNickname)
+        .replace("{style}", &map.MapStyle)
+        .replace("{env}", &map.Environnement)
+}
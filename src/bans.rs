@@ -0,0 +1,88 @@
+use dxr::TryFromValue;
+
+use crate::{guests::LoginEntry, Client, ClientError};
+
+/// how many logins of each list `/bans` shows, the rest only goes to the log
+const CHAT_ENTRIES: usize = 10;
+
+#[allow(non_snake_case)]
+#[derive(TryFromValue, Debug, Clone)]
+pub struct BanEntry {
+    pub Login: String,
+    pub ClientName: String,
+    pub IPAddress: String,
+}
+
+impl Client {
+    /// bans only last until the server restarts, see the black list for more
+    pub fn get_ban_list(&mut self) -> Result<Vec<BanEntry>, ClientError> {
+        self.call_paged("GetBanList")
+    }
+
+    pub fn get_black_list(&mut self) -> Result<Vec<String>, ClientError> {
+        let entries: Vec<LoginEntry> = self.call_paged("GetBlackList")?;
+        Ok(entries.into_iter().map(|e| e.Login).collect())
+    }
+
+    pub fn clean_ban_list(&mut self) -> Result<(), ClientError> {
+        self.checked_call("CleanBanList", ())
+    }
+
+    pub fn clean_black_list(&mut self) -> Result<(), ClientError> {
+        self.checked_call("CleanBlackList", ())
+    }
+
+    /// `filename` is relative to the server's `UserData/Config` directory
+    pub fn save_black_list(&mut self, filename: &str) -> Result<(), ClientError> {
+        self.checked_call("SaveBlackList", filename)
+    }
+
+    /// `filename` is relative to the server's `UserData/Config` directory
+    pub fn load_black_list(&mut self, filename: &str) -> Result<(), ClientError> {
+        self.checked_call("LoadBlackList", filename)
+    }
+
+    fn checked_call(
+        &mut self,
+        f: &'static str,
+        args: impl dxr::TryToParams,
+    ) -> Result<(), ClientError> {
+        let suc: bool = self.call(f, args)?;
+        if !suc {
+            return Err(ClientError::Rejected(f));
+        }
+        Ok(())
+    }
+
+    /// `/bans` shows how many logins are banned and blacklisted and the most
+    /// recent ones, the full lists are printed to the log
+    pub(crate) fn bans_command(&mut self, login: &str) -> Result<(), ClientError> {
+        let bans: Vec<String> = self.get_ban_list()?.into_iter().map(|b| b.Login).collect();
+        let black = self.get_black_list()?;
+        say!("banned: {}", bans.join(", "));
+        say!("blacklisted: {}", black.join(", "));
+
+        let reply = format!(
+            "{} banned{}, {} blacklisted{}",
+            bans.len(),
+            recent(&bans),
+            black.len(),
+            recent(&black)
+        );
+        self.chat_send_to_login(login, &reply)
+    }
+}
+
+/// the last few entries, the server appends new ones at the end
+fn recent(logins: &[String]) -> String {
+    if logins.is_empty() {
+        return String::new();
+    }
+    let start = logins.len().saturating_sub(CHAT_ENTRIES);
+    let more = if start > 0 {
+        ", see the log for the rest"
+    } else {
+        ""
+    };
+    format!(" ({}{more})", logins[start..].join(", "))
+}
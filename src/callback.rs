@@ -0,0 +1,287 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use dxr::{DxrError, TryFromParams, TryFromValue, Value};
+
+use crate::{method_allowed, Client, ClientError, Lifecycle, PlayerInfo};
+
+/// how modes send most of their events
+pub(crate) const SCRIPT_CALLBACK: &str = "ManiaPlanet.ModeScriptCallbackArray";
+/// a player clicked an element of a manialink
+pub(crate) const MANIALINK_ANSWER: &str = "ManiaPlanet.PlayerManialinkPageAnswer";
+/// a player started or stopped spectating, changed teams and so on
+pub(crate) const PLAYER_INFO_CHANGED: &str = "ManiaPlanet.PlayerInfoChanged";
+
+/// a callback from the server
+#[derive(Debug, Clone, PartialEq)]
+pub enum Callback {
+    /// an event of the mode script, like `Trackmania.Event.WayPoint`
+    ///
+    /// the payload is a single json value if the mode sent one string, which
+    /// is what the trackmania modes do, and an array of them otherwise.
+    Script {
+        name: String,
+        json: serde_json::Value,
+    },
+    /// a player clicked an element with an `action`, which is the answer
+    ///
+    /// `entries` has the contents of the text fields on the page, if any.
+    ManialinkAnswer {
+        login: String,
+        answer: String,
+        entries: Vec<ManialinkEntry>,
+    },
+    /// a player started or stopped spectating, or anything else about them
+    /// changed
+    PlayerInfoChanged(PlayerInfo),
+    /// the match started or ended, or the podium did, whether the mode is a
+    /// script or a legacy one
+    Lifecycle(Lifecycle),
+    /// any other callback, with its params untouched
+    Method { name: String, params: Vec<Value> },
+}
+
+impl Callback {
+    pub fn parse(name: &str, params: &[Value]) -> Result<Callback, CallbackError> {
+        if name == MANIALINK_ANSWER {
+            let (_uid, login, answer) =
+                <(i32, String, String)>::try_from_params(params.get(..3).unwrap_or_default())?;
+            let entries = params
+                .get(3)
+                .map(Vec::<ManialinkEntry>::try_from_value)
+                .transpose()?
+                .unwrap_or_default();
+            return Ok(Callback::ManialinkAnswer {
+                login,
+                answer,
+                entries,
+            });
+        }
+        if name == PLAYER_INFO_CHANGED {
+            let info = params
+                .first()
+                .ok_or_else(|| DxrError::parameter_mismatch(0, 1))?;
+            return Ok(Callback::PlayerInfoChanged(PlayerInfo::try_from_value(
+                info,
+            )?));
+        }
+        if let Some(event) = Lifecycle::from_method(name) {
+            return Ok(Callback::Lifecycle(event));
+        }
+        if name != SCRIPT_CALLBACK {
+            return Ok(Callback::Method {
+                name: name.to_owned(),
+                params: params.to_vec(),
+            });
+        }
+        let (name, json) = parse_script(params)?;
+        if let Some(event) = Lifecycle::from_script(&name) {
+            return Ok(Callback::Lifecycle(event));
+        }
+        Ok(Callback::Script { name, json })
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Callback::Script { name, .. } | Callback::Method { name, .. } => name,
+            Callback::ManialinkAnswer { .. } => MANIALINK_ANSWER,
+            Callback::PlayerInfoChanged(_) => PLAYER_INFO_CHANGED,
+            Callback::Lifecycle(Lifecycle::BeginMatch) => "ManiaPlanet.BeginMatch",
+            Callback::Lifecycle(Lifecycle::EndMatch) => "ManiaPlanet.EndMatch",
+            Callback::Lifecycle(Lifecycle::PodiumStart) => "Maniaplanet.Podium_Start",
+            Callback::Lifecycle(Lifecycle::PodiumEnd) => "Maniaplanet.Podium_End",
+        }
+    }
+}
+
+/// the text between the first `open` and the `close` after it
+fn element<'a>(xml: &'a str, open: &str, close: &str) -> Option<&'a str> {
+    let start = xml.find(open)? + open.len();
+    let len = xml[start..].find(close)?;
+    Some(&xml[start..start + len])
+}
+
+/// the method of a callback, and for script callbacks the name of the event,
+/// without parsing all of it
+pub(crate) fn callback_names(msg: &str) -> Option<(&str, Option<&str>)> {
+    let method = element(msg, "<methodName>", "</methodName>")?.trim();
+    if method != SCRIPT_CALLBACK {
+        return Some((method, None));
+    }
+    // the event is the first param, always a string
+    let params = &msg[msg.find("<params>")?..];
+    Some((method, element(params, "<string>", "</string>")))
+}
+
+/// the name and the payload of a script callback
+pub(crate) fn parse_script(params: &[Value]) -> Result<(String, serde_json::Value), CallbackError> {
+    let (name, data) = <(String, Vec<String>)>::try_from_params(params)?;
+    let mut json = data
+        .iter()
+        .map(|d| serde_json::from_str(d))
+        .collect::<Result<Vec<serde_json::Value>, _>>()
+        .map_err(|err| CallbackError::Json {
+            name: name.clone(),
+            err,
+        })?;
+    let json = if json.len() == 1 {
+        json.remove(0)
+    } else {
+        serde_json::Value::Array(json)
+    };
+    Ok((name, json))
+}
+
+/// the contents of a text field on a manialink
+#[allow(non_snake_case)]
+#[derive(TryFromValue, Debug, Clone, PartialEq, Eq)]
+pub struct ManialinkEntry {
+    pub Name: String,
+    pub Value: String,
+}
+
+/// the server sent a callback we could not make sense of
+#[derive(Debug)]
+pub enum CallbackError {
+    Params(DxrError),
+    /// the mode sent a payload that is not json
+    Json {
+        name: String,
+        err: serde_json::Error,
+    },
+}
+
+impl fmt::Display for CallbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallbackError::Params(err) => write!(f, "invalid params: {err}"),
+            CallbackError::Json { name, err } => write!(f, "invalid json in {name}: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for CallbackError {}
+
+impl From<DxrError> for CallbackError {
+    fn from(err: DxrError) -> Self {
+        CallbackError::Params(err)
+    }
+}
+
+/// why [`Client::wait_for_script_callback`] came back empty handed
+#[derive(Debug)]
+pub enum WaitError {
+    Timeout,
+    /// a shutdown was requested while waiting
+    Shutdown,
+    Client(ClientError),
+}
+
+impl fmt::Display for WaitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WaitError::Timeout => write!(f, "timed out waiting for a callback"),
+            WaitError::Shutdown => write!(f, "shutting down"),
+            WaitError::Client(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for WaitError {}
+
+impl From<ClientError> for WaitError {
+    fn from(err: ClientError) -> Self {
+        WaitError::Client(err)
+    }
+}
+
+impl Client {
+    /// whether `ignored_callbacks` drops the callback in `msg`
+    pub(crate) fn is_ignored_callback(&self, msg: &str) -> bool {
+        let ignored = &self.config().ignored_callbacks;
+        if ignored.is_empty() {
+            return false;
+        }
+        match callback_names(msg) {
+            Some((method, event)) => {
+                method_allowed(ignored, method)
+                    || event.is_some_and(|event| method_allowed(ignored, event))
+            }
+            None => false,
+        }
+    }
+
+    /// asks the mode not to send the script callbacks in `ignored_callbacks`
+    ///
+    /// only whole names can be blocked, patterns are dropped when they
+    /// arrive instead. older modes do not know the method, which is fine.
+    pub(crate) fn block_ignored_callbacks(&mut self) -> Result<(), ClientError> {
+        let names: Vec<String> = self
+            .config()
+            .ignored_callbacks
+            .iter()
+            .filter(|name| !name.ends_with('*') && !name.starts_with("ManiaPlanet."))
+            .cloned()
+            .collect();
+        if names.is_empty() {
+            return Ok(());
+        }
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        match self.trigger_mode_script_event_array("XmlRpc.BlockCallbacks", &names) {
+            Err(err) if !err.is_connection() => debug!("while blocking callbacks: {err}"),
+            res => res?,
+        }
+        Ok(())
+    }
+
+    /// reacts to the events of the mode script
+    pub(crate) fn handle_script_callback(
+        &mut self,
+        name: &str,
+        json: &serde_json::Value,
+    ) -> Result<(), ClientError> {
+        self.handle_warm_up_callback(name);
+        self.handle_score_callback(name, json);
+        if self.script_callback.is_none() && self.awaited_script.as_deref() == Some(name) {
+            self.script_callback = Some(json.clone());
+        }
+        Ok(())
+    }
+
+    /// handles messages until the mode sends the script callback `name`, and
+    /// returns its payload
+    ///
+    /// only callbacks that arrive after this is called count, like the answer
+    /// to [`Client::trigger_mode_script_event`].
+    pub fn wait_for_script_callback(
+        &mut self,
+        name: &str,
+        timeout: Duration,
+    ) -> Result<serde_json::Value, WaitError> {
+        // a callback handler might be waiting for another one already
+        let outer = (
+            self.awaited_script.replace(name.to_owned()),
+            self.script_callback.take(),
+        );
+        let res = self.await_script_callback(Instant::now() + timeout);
+        (self.awaited_script, self.script_callback) = outer;
+        res
+    }
+
+    fn await_script_callback(&mut self, deadline: Instant) -> Result<serde_json::Value, WaitError> {
+        loop {
+            if let Some(json) = self.script_callback.take() {
+                return Ok(json);
+            }
+            if self.shutdown_requested() {
+                return Err(WaitError::Shutdown);
+            }
+            if Instant::now() >= deadline {
+                return Err(WaitError::Timeout);
+            }
+            self.await_messages_until(Some(deadline))?;
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// where a map in the maps directory came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "origin", rename_all = "lowercase")]
+pub enum MapOrigin {
+    /// downloaded by us
+    Tmx { id: u64 },
+    /// added with [`crate::Client::add_local_map`]
+    Local,
+    /// found in the map list, see [`crate::Client::reconcile_map_list`]
+    External,
+}
+
+/// what we know about one map file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    #[serde(flatten)]
+    pub origin: MapOrigin,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<String>,
+    /// with its formatting codes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// see `download_thumbnails`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<PathBuf>,
+    /// see `download_replays`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay: Option<PathBuf>,
+    /// the map list did not have it when it was last reconciled
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub orphaned: bool,
+}
+
+impl CatalogEntry {
+    pub fn new(origin: MapOrigin) -> Self {
+        CatalogEntry {
+            origin,
+            uid: None,
+            name: None,
+            thumbnail: None,
+            replay: None,
+            orphaned: false,
+        }
+    }
+
+    pub fn tmx_id(&self) -> Option<u64> {
+        match self.origin {
+            MapOrigin::Tmx { id } => Some(id),
+            MapOrigin::Local | MapOrigin::External => None,
+        }
+    }
+}
+
+/// the maps we know about, by their path relative to the maps directory
+///
+/// both separators mean the same, since the server might run on windows and
+/// list its maps with backslashes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Catalog {
+    entries: BTreeMap<String, CatalogEntry>,
+}
+
+/// `file_name` the way the catalog has it
+pub(crate) fn key(file_name: &str) -> String {
+    file_name.replace('\\', "/")
+}
+
+impl Catalog {
+    pub fn get(&self, file_name: &str) -> Option<&CatalogEntry> {
+        self.entries.get(&key(file_name))
+    }
+
+    pub fn get_mut(&mut self, file_name: &str) -> Option<&mut CatalogEntry> {
+        self.entries.get_mut(&key(file_name))
+    }
+
+    /// replaces what was known about `file_name`
+    pub fn insert(&mut self, file_name: &str, entry: CatalogEntry) {
+        self.entries.insert(key(file_name), entry);
+    }
+
+    pub fn remove(&mut self, file_name: &str) -> Option<CatalogEntry> {
+        self.entries.remove(&key(file_name))
+    }
+
+    /// the file name of the entry with `uid`, for maps the server lists
+    /// under another name
+    pub fn file_of_uid(&self, uid: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(_, entry)| entry.uid.as_deref() == Some(uid))
+            .map(|(file_name, _)| file_name.as_str())
+    }
+
+    /// the entries with `/` as their separator
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &CatalogEntry)> {
+        self.entries
+            .iter()
+            .map(|(file_name, entry)| (file_name.as_str(), entry))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_separators_find_the_same_entry() {
+        let mut catalog = Catalog::default();
+        catalog.insert(
+            "Downloaded\\1.Map.Gbx",
+            CatalogEntry::new(MapOrigin::Tmx { id: 1 }),
+        );
+        assert_eq!(
+            catalog.get("Downloaded/1.Map.Gbx").unwrap().tmx_id(),
+            Some(1)
+        );
+        assert!(catalog.remove("Downloaded/1.Map.Gbx").is_some());
+        assert!(catalog.is_empty());
+    }
+
+    #[test]
+    fn entries_keep_their_origin_in_json() {
+        let mut catalog = Catalog::default();
+        let mut entry = CatalogEntry::new(MapOrigin::Tmx { id: 7 });
+        entry.uid = Some("uid".to_owned());
+        catalog.insert("7.Map.Gbx", entry);
+        catalog.insert("mine.Map.Gbx", CatalogEntry::new(MapOrigin::Local));
+        let json = serde_json::to_value(&catalog).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "7.Map.Gbx": { "origin": "tmx", "id": 7, "uid": "uid" },
+                "mine.Map.Gbx": { "origin": "local" },
+            })
+        );
+        assert_eq!(serde_json::from_value::<Catalog>(json).unwrap(), catalog);
+    }
+}
@@ -0,0 +1,78 @@
+use std::fmt;
+
+use crate::{Client, ConnectionState};
+
+/// one line of the report of [`Client::check_setup`]
+#[derive(Debug, Clone)]
+pub struct CheckItem {
+    pub name: &'static str,
+    /// what we found, or what is wrong
+    pub result: Result<String, String>,
+}
+
+impl CheckItem {
+    fn new<T: fmt::Display, E: fmt::Display>(name: &'static str, result: Result<T, E>) -> Self {
+        CheckItem {
+            name,
+            result: result
+                .map(|ok| ok.to_string())
+                .map_err(|err| err.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for CheckItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.result {
+            Ok(found) => write!(f, "PASS {}: {found}", self.name),
+            Err(err) => write!(f, "FAIL {}: {err}", self.name),
+        }
+    }
+}
+
+impl Client {
+    /// checks the config, the login, the maps directory and the map source,
+    /// without changing anything on the server
+    ///
+    /// nothing is downloaded. what needs the login is skipped if it fails.
+    pub fn check_setup(&mut self) -> Vec<CheckItem> {
+        let problems = self.config().problems();
+        let mut items = vec![CheckItem::new(
+            "config",
+            if problems.is_empty() {
+                Ok("looks fine".to_owned())
+            } else {
+                Err(problems.join(", "))
+            },
+        )];
+
+        let login = self
+            .log_in()
+            .map(|()| format!("logged in as {}", self.config().login));
+        let logged_in = login.is_ok();
+        items.push(CheckItem::new("login", login));
+        if logged_in {
+            let maps_dir = self
+                .check_maps_directory()
+                .and_then(|()| self.check_fallback_maps())
+                .and_then(|()| self.maps_dir_path(""))
+                .map(|dir| format!("{} is writable", dir.display()));
+            items.push(CheckItem::new("maps directory", maps_dir));
+        } else {
+            items.push(CheckItem {
+                name: "maps directory",
+                result: Err("skipped, we are not logged in".to_owned()),
+            });
+        }
+        self.disconnect();
+        self.state = ConnectionState::Disconnected;
+
+        let search = self.decayed_search();
+        let source = self
+            .source
+            .next_candidate(&search, &self.history)
+            .map(|candidate| format!("the search found map {}", candidate.id));
+        items.push(CheckItem::new("map source", source));
+        items
+    }
+}
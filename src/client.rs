@@ -0,0 +1,2091 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
+    fmt, fs,
+    io::{self, Read, Write},
+    net::{Shutdown, TcpStream},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::eyre;
+use dxr::{
+    Fault, FaultResponse, MethodCall, MethodResponse, TryFromParams, TryFromValue, TryToValue,
+    Value,
+};
+
+use crate::{
+    callback::{parse_script, MANIALINK_ANSWER, PLAYER_INFO_CHANGED, SCRIPT_CALLBACK},
+    control::map_file_name,
+    countdown::PendingSwitch,
+    debounce::CommandDebounce,
+    download::{download_file, DownloadError},
+    echo::PendingEcho,
+    frame::FrameDecoder,
+    identity::PlayerIdentities,
+    info::SessionStats,
+    lifecycle::LifecycleHandler,
+    manialink::ManialinkHandler,
+    output::trace,
+    password::redact_chat,
+    prefetch::Prefetch,
+    queue::Pending,
+    rankings::Standings,
+    reconnect::ConnectHandler,
+    skipped::ExpectedMap,
+    vote::MapVote,
+    Blocklist, CacheDry, CacheMapSource, Callback, CallbackForwarder, Catalog, CatalogEntry,
+    ClientError, Config, ConfigError, FileStorage, GbxMapHeader, History, HttpResponse, InsertMode,
+    Ladder, Lifecycle, MapCandidate, MapFilter, MapOrigin, MapSearch, MapSource, MetadataCache,
+    RateLimiter, Storage, TimeBudget, TmxMapSource,
+};
+
+/// how many candidates we look at before giving up on finding a new map
+const PICK_ATTEMPTS: usize = 10;
+/// what we send to `SetApiVersion`, newest first
+const API_VERSIONS: &[&str] = &[
+    "2023-04-24",
+    "2023-03-25",
+    "2022-03-21",
+    "2019-03-02",
+    "2013-04-16",
+    "2012-06-19",
+];
+/// how many maps we try to download when tmx fails us, and how often we try
+/// to get a thumbnail
+const DOWNLOAD_ATTEMPTS: usize = 3;
+/// how many entries we ask for at once from paged methods
+const PAGE_SIZE: i32 = 100;
+/// list methods sometimes fault while the server loads a map
+const PAGE_ATTEMPTS: usize = 3;
+/// how long the server has to say hello after we connect
+const HELLO_TIMEOUT: Duration = Duration::from_secs(10);
+/// the real hello is "GBXRemote 2", anything much longer is not one
+const MAX_HELLO_LEN: u32 = 64;
+
+/// a call that waits for its response
+#[derive(Debug)]
+pub(crate) struct SentCall {
+    pub method: String,
+    pub sent: Instant,
+}
+
+/// everything the message loop reacts to
+pub(crate) enum Event {
+    /// a response or callback read from connection number `conn`
+    ///
+    /// `received` is when the reader got it, the time it waited in the queue
+    /// does not count towards the latency of a call.
+    Frame {
+        conn: u64,
+        handle: u32,
+        msg: String,
+        received: Instant,
+    },
+    /// connection number `conn` was closed
+    Closed { conn: u64, err: io::Error },
+    /// a line from the control channel
+    Control(String),
+    /// a request to the status server, answered through `reply`
+    Http {
+        path: String,
+        reply: Sender<HttpResponse>,
+    },
+    /// we got a signal and should stop, see [`crate::ShutdownHandle`]
+    Shutdown,
+    /// time to pick another map for the prefetch pool
+    RefillPrefetch,
+    /// a prefetched map was downloaded and checked, or not
+    Prefetched {
+        candidate: Box<MapCandidate>,
+        rel_path: String,
+        result: Result<(), DownloadError>,
+    },
+}
+
+/// how far along the connection to the server is, see [`Client::state`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// trying to connect for the first time
+    Connecting,
+    /// connected and logged in
+    Authenticated,
+    /// the connection was lost, we keep trying to get it back
+    Reconnecting,
+    /// not connected yet, or closed on purpose
+    Disconnected,
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            ConnectionState::Connecting => "connecting",
+            ConnectionState::Authenticated => "authenticated",
+            ConnectionState::Reconnecting => "reconnecting",
+            ConnectionState::Disconnected => "disconnected",
+        };
+        f.write_str(state)
+    }
+}
+
+impl ConnectionState {
+    /// whether the controller can talk to the server right now
+    pub fn is_healthy(self) -> bool {
+        self == ConnectionState::Authenticated
+    }
+}
+
+pub struct Client {
+    /// `None` while disconnected, everything else survives a reconnect
+    client: Option<TcpStream>,
+    pub(crate) state: ConnectionState,
+    /// counts connections, so events from an old connection can be ignored
+    conn: u64,
+    api_version: Option<&'static str>,
+    /// see [`Client::server_name`]
+    pub(crate) server_name: Option<String>,
+    events: Receiver<Event>,
+    events_tx: Sender<Event>,
+    exchange: reqwest::blocking::Client,
+    handle: u32,
+
+    pub(crate) calls: HashMap<u32, SentCall>,
+    responses: HashMap<u32, String>,
+    /// the innermost call that waits for its response, only that response
+    /// wakes the message loop
+    awaited_call: Option<u32>,
+    /// requests that arrived while a call was waiting for its response
+    pub(crate) deferred: VecDeque<Event>,
+    /// control commands that arrived while the server was away
+    pub(crate) pending: VecDeque<Pending>,
+    /// where the replies to control commands go, stdout by default
+    pub(crate) control_replies: Box<dyn Write + Send>,
+
+    config: Config,
+    /// where the config was loaded from, for `/reload`
+    pub(crate) config_path: Option<PathBuf>,
+    pub(crate) source: Box<dyn MapSource>,
+    filter: Option<MapFilter>,
+    pub(crate) history: History,
+    /// how much of its weight each disliked tag has for now, see [`crate::TagDecay`]
+    pub(crate) tag_factors: HashMap<u32, f64>,
+    /// the authors of the last picked maps, oldest first, for `author_cooldown`
+    recent_authors: VecDeque<String>,
+    forwarder: Option<CallbackForwarder>,
+
+    pub(crate) current_map: Option<MapInfo>,
+    /// the map that should start next, see [`Client::check_expected_map`]
+    pub(crate) expected_next: Option<ExpectedMap>,
+    /// logins that disliked the current map
+    pub(crate) dislikes: HashSet<String>,
+    /// when the first of them came in
+    pub(crate) dislikes_since: Option<Instant>,
+    /// downloaded maps waiting for the end of the map to be inserted
+    pub(crate) staged: VecDeque<MapCandidate>,
+    /// when the server last sent us anything, for the keep-alive
+    pub(crate) last_frame: Instant,
+    /// the handle of the keep-alive call we are waiting for
+    pub(crate) ping: Option<(u32, Instant)>,
+    /// the keep-alive echo whose callback we are waiting for
+    pub(crate) echo: Option<PendingEcho>,
+    /// whether the server took `EnableCallbacks`, so echoes come back
+    pub(crate) callbacks_enabled: bool,
+    /// when the session summary was sent, or would have been on an empty server
+    pub(crate) last_summary: Instant,
+    pub(crate) stats: SessionStats,
+    pub(crate) metadata: MetadataCache,
+    /// whether the next map was announced during the current one
+    pub(crate) announced: bool,
+    limiter: RateLimiter,
+    /// whether the calls made now are on behalf of an operator, see
+    /// [`Client::as_untrusted`]
+    pub(crate) untrusted: bool,
+    /// safe mode was armed for this session, see [`Client::arm`]
+    pub(crate) armed: bool,
+    /// the command running now checked the role of whoever sent it, see
+    /// [`Client::as_vetted`]
+    pub(crate) vetted: bool,
+    /// set by [`Client::close`], callbacks are ignored from then on
+    pub(crate) closing: bool,
+    /// lines from stdin are calls and callbacks are only printed, see
+    /// [`Client::run_repl`]
+    pub(crate) repl: bool,
+    /// a call waits for another one to be answered, see
+    /// [`Client::wait_for_call_slot`]
+    awaiting_slot: bool,
+    /// the ladder limits as of the last check, see [`Client::fits_ladder`]
+    pub(crate) ladder: Ladder,
+    /// what the server can load, see [`Client::fits_environment`]
+    pub(crate) environments: Option<Vec<String>>,
+    pub(crate) debounce: CommandDebounce,
+    /// nicknames by login, see [`Client::nickname_of`]
+    pub(crate) identities: PlayerIdentities,
+    pub(crate) vote: Option<MapVote>,
+    /// whether there was a vote during the current map already
+    pub(crate) vote_held: bool,
+    /// see [`Client::next_map`]
+    pub(crate) pending_switch: Option<PendingSwitch>,
+    /// see [`Client::pause_rotation`]
+    pub(crate) rotation_paused: bool,
+    /// see [`Client::in_warmup`]
+    pub(crate) in_warmup: bool,
+    /// the script we asked for, until the map it applies to starts
+    pub(crate) pending_mode: Option<String>,
+    /// how many more times tmx may be asked again during the current map, so a
+    /// search that never finds anything does not hammer it
+    pub(crate) retries_left: usize,
+    /// the script callback [`Client::wait_for_script_callback`] waits for,
+    /// and its payload once it arrived
+    pub(crate) awaited_script: Option<String>,
+    pub(crate) script_callback: Option<serde_json::Value>,
+    pub(crate) players: HashSet<String>,
+    /// the players above that are spectating
+    pub(crate) spectators: HashSet<String>,
+    pub(crate) standings: Standings,
+    pub(crate) prefetch: Prefetch,
+    /// how many players [`Client::wait_for_players`] waits for
+    pub(crate) awaited_players: Option<usize>,
+    pub(crate) manialink_handlers: HashMap<String, ManialinkHandler>,
+    pub(crate) lifecycle_handlers: HashMap<Lifecycle, Vec<LifecycleHandler>>,
+    /// with whether they also run on the first connection
+    pub(crate) connect_handlers: Vec<(bool, ConnectHandler)>,
+    pub(crate) blocklist: Blocklist,
+    /// where the maps in the maps directory came from
+    pub(crate) catalog: Catalog,
+    pub(crate) storage: Box<dyn Storage>,
+}
+
+impl Client {
+    pub fn new(config: Config) -> Self {
+        // trackmania.exchange does not like it if we don't give a user_agent
+        let exchange = reqwest::blocking::Client::builder()
+            .user_agent("hytak-server-util")
+            .build()
+            .unwrap();
+        let source: Box<dyn MapSource> = if config.offline {
+            Box::new(CacheMapSource::new(config.download_cache.clone()))
+        } else {
+            Box::new(TmxMapSource::new(exchange.clone()))
+        };
+        Self::with_source(config, exchange, source)
+    }
+
+    /// like [`Client::new`], but picks maps from `source` instead of tmx
+    pub fn with_source(
+        config: Config,
+        exchange: reqwest::blocking::Client,
+        source: Box<dyn MapSource>,
+    ) -> Self {
+        let (events_tx, events) = mpsc::channel();
+        let mut client = Client {
+            client: None,
+            state: ConnectionState::Disconnected,
+            conn: 0,
+            api_version: None,
+            server_name: None,
+            events,
+            events_tx,
+            exchange,
+            handle: 0x80000000,
+            calls: HashMap::new(),
+            responses: HashMap::new(),
+            awaited_call: None,
+            deferred: VecDeque::new(),
+            pending: VecDeque::new(),
+            control_replies: Box::new(io::stdout()),
+            history: History::new(config.history_size).with_window(config.no_repeat_window),
+            tag_factors: HashMap::new(),
+            recent_authors: VecDeque::new(),
+            forwarder: config.forward_callbacks.clone().map(CallbackForwarder::new),
+            current_map: None,
+            expected_next: None,
+            dislikes: HashSet::new(),
+            dislikes_since: None,
+            staged: VecDeque::new(),
+            last_frame: Instant::now(),
+            ping: None,
+            echo: None,
+            callbacks_enabled: false,
+            last_summary: Instant::now(),
+            stats: SessionStats::default(),
+            metadata: MetadataCache::new(config.metadata_cache_size, config.metadata_ttl),
+            announced: false,
+            ladder: Ladder::NotLadder,
+            environments: None,
+            debounce: CommandDebounce::default(),
+            identities: PlayerIdentities::default(),
+            vote: None,
+            vote_held: false,
+            pending_switch: None,
+            rotation_paused: false,
+            in_warmup: false,
+            pending_mode: None,
+            retries_left: config.max_retries_per_map,
+            awaited_script: None,
+            script_callback: None,
+            players: HashSet::new(),
+            spectators: HashSet::new(),
+            standings: Standings::default(),
+            prefetch: Prefetch::default(),
+            awaited_players: None,
+            manialink_handlers: HashMap::new(),
+            lifecycle_handlers: HashMap::new(),
+            connect_handlers: Vec::new(),
+            limiter: RateLimiter::new(config.max_calls_per_sec),
+            untrusted: false,
+            armed: false,
+            vetted: false,
+            closing: false,
+            repl: false,
+            awaiting_slot: false,
+            blocklist: Blocklist::default(),
+            catalog: Catalog::default(),
+            storage: Box::new(FileStorage::new(&config)),
+            config,
+            config_path: None,
+            source,
+            filter: None,
+        };
+        client.load_state();
+        client
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub(crate) fn exchange(&self) -> &reqwest::blocking::Client {
+        &self.exchange
+    }
+
+    /// remembers `path` so `/reload` knows where to read the config from
+    pub fn set_config_path(&mut self, path: impl Into<PathBuf>) {
+        self.config_path = Some(path.into());
+    }
+
+    /// re-reads the config and applies everything that does not need a reconnect
+    ///
+    /// returns the fields that changed but were not applied because they need a restart.
+    pub fn reload_config(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<Vec<&'static str>, ConfigError> {
+        let mut config = Config::load_server(path, self.config.id.as_deref())?;
+        // `--offline` is not in the file, and only a restart turns it off
+        config.offline |= self.config.offline;
+        let restart_required = self.config.restart_required(&config);
+
+        // keep using the old connection settings until the next restart
+        config.address = self.config.address.clone();
+        config.login = self.config.login.clone();
+        config.password = self.config.password.clone();
+        // and the ones the map source and the log prefix were made with
+        config.download_cache = self.config.download_cache.clone();
+        config.id = self.config.id.clone();
+
+        self.history.set_capacity(config.history_size);
+        self.history.set_window(config.no_repeat_window);
+        self.metadata
+            .set_limits(config.metadata_cache_size, config.metadata_ttl);
+        if self.limiter.rate() != config.max_calls_per_sec {
+            self.limiter = RateLimiter::new(config.max_calls_per_sec);
+        }
+        if self.forwarder.as_ref().map(|f| f.addr()) != config.forward_callbacks.as_deref() {
+            self.forwarder = config.forward_callbacks.clone().map(CallbackForwarder::new);
+        }
+        self.config = config;
+        Ok(restart_required)
+    }
+
+    /// (re)connects to the server, dropping any previous connection
+    pub fn connect(&mut self) -> Result<(), ClientError> {
+        // only a connection that was up before can have lost something
+        let reconnect = self.state == ConnectionState::Reconnecting;
+        self.log_in()?;
+        self.log_server_info()?;
+
+        // some servers refuse, which only matters for what reacts to callbacks
+        let enabled = match self.call("EnableCallbacks", [true]) {
+            Err(ClientError::Fault(fault)) => {
+                say!("could not enable callbacks: {}", fault.string());
+                false
+            }
+            res => res?,
+        };
+        self.callbacks_enabled = enabled;
+        if !enabled {
+            let features = self.config.callback_features();
+            if !features.is_empty() {
+                say!("these settings need callbacks: {}", features.join(", "));
+                return Err(ClientError::Rejected("EnableCallbacks"));
+            }
+            say!("callbacks are not enabled, maps are not rotated and chat commands do nothing");
+        }
+        // legacy modes have no script callbacks to enable
+        match self.enable_script_callbacks() {
+            Err(err) if !err.is_connection() => say!("while enabling script callbacks: {err}"),
+            res => res?,
+        }
+        self.block_ignored_callbacks()?;
+
+        match self.refresh_players() {
+            Err(err) if !err.is_connection() => say!("while listing the players: {err}"),
+            res => res?,
+        }
+        self.apply_config()?;
+        self.check_maps_directory()?;
+        self.check_fallback_maps()?;
+        if !reconnect {
+            self.reconcile_on_start()?;
+        }
+        self.stats.connected_since = Some(Instant::now());
+        self.handle_connected(reconnect)?;
+        self.schedule_prefetch();
+        self.flush_pending()
+    }
+
+    /// the handshake and the login, dropping any previous connection
+    pub(crate) fn log_in(&mut self) -> Result<(), ClientError> {
+        self.disconnect();
+        if self.state != ConnectionState::Reconnecting {
+            self.state = ConnectionState::Connecting;
+        }
+
+        let mut stream = TcpStream::connect(&self.config.address)?;
+        // a call is written in three parts, without this the later ones wait
+        // for the server to acknowledge the first
+        stream.set_nodelay(self.config.tcp_nodelay)?;
+
+        let reconnect = self.state == ConnectionState::Reconnecting;
+        let hello = read_hello(&mut stream, reconnect)?;
+        if hello != "GBXRemote 2" {
+            return Err(ClientError::Handshake(hello));
+        }
+
+        self.conn += 1;
+        spawn_reader(
+            stream.try_clone()?,
+            self.conn,
+            self.config.max_frame_len,
+            self.events_tx.clone(),
+        );
+        self.client = Some(stream);
+
+        self.negotiate_api_version()?;
+        let credentials = [self.config.login.clone(), self.config.password.clone()];
+        // older servers say false, newer ones fault
+        match self.call("Authenticate", credentials) {
+            Ok(true) => {}
+            Ok(false) => return Err(ClientError::Auth("wrong login or password".to_owned())),
+            Err(ClientError::Fault(fault)) => {
+                return Err(ClientError::Auth(fault.string().to_owned()))
+            }
+            Err(err) => return Err(err),
+        }
+        if self.state == ConnectionState::Reconnecting {
+            self.stats.reconnects += 1;
+        }
+        self.state = ConnectionState::Authenticated;
+        Ok(())
+    }
+
+    /// connects and logs in, then disconnects again without changing anything
+    /// on the server
+    ///
+    /// meant for health checks, it fails unless the state got to be
+    /// [`ConnectionState::is_healthy`].
+    pub fn check_health(&mut self) -> Result<(), ClientError> {
+        let res = self.log_in().and_then(|()| self.check_callbacks());
+        let healthy = self.state.is_healthy();
+        self.disconnect();
+        self.state = ConnectionState::Disconnected;
+        res?;
+        if !healthy {
+            return Err(ClientError::Rejected("Authenticate"));
+        }
+        Ok(())
+    }
+
+    /// connects, runs `f` and disconnects again, for one-off commands
+    ///
+    /// unlike [`Client::connect`], this leaves callbacks off and applies none
+    /// of the config, so nothing reacts to the server while `f` runs.
+    pub fn oneshot<T>(
+        config: Config,
+        f: impl FnOnce(&mut Client) -> Result<T, ClientError>,
+    ) -> Result<T, ClientError> {
+        let mut client = Client::new(config);
+        let res = client.log_in().and_then(|()| f(&mut client));
+        client.disconnect();
+        client.state = ConnectionState::Disconnected;
+        res
+    }
+
+    /// tries the versions we know, newest first, until the server takes one
+    fn negotiate_api_version(&mut self) -> Result<(), ClientError> {
+        for &version in API_VERSIONS {
+            match self.call::<bool>("SetApiVersion", version) {
+                Ok(true) => {
+                    if self.api_version != Some(version) {
+                        say!("using api version {version}");
+                    }
+                    self.api_version = Some(version);
+                    return Ok(());
+                }
+                Ok(false) | Err(ClientError::Fault(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Err(ClientError::Rejected("SetApiVersion"))
+    }
+
+    /// the api version the server agreed to on the last connect
+    pub fn api_version(&self) -> Option<&'static str> {
+        self.api_version
+    }
+
+    /// pushes the server settings from the config
+    ///
+    /// this runs on every connect, since a restarted server forgets them.
+    pub fn apply_config(&mut self) -> Result<(), ClientError> {
+        if let Some(mods) = self.config.forced_mods.clone() {
+            match self.set_forced_mods(&mods) {
+                Err(err) if !err.is_connection() => say!("while forcing mods: {err}"),
+                res => res?,
+            }
+        }
+        if let Some(file) = self.config.black_list_file.clone() {
+            match self.load_black_list(&file) {
+                Err(err) if !err.is_connection() => say!("while loading the black list: {err}"),
+                res => res?,
+            }
+        }
+        if let Some(timeout) = self.config.call_vote_timeout {
+            match self.set_call_vote_timeout(timeout) {
+                Err(err) if !err.is_connection() => {
+                    say!("while setting the call vote timeout: {err}")
+                }
+                res => res?,
+            }
+        }
+        let display = self.config.display.clone();
+        match self.apply_display_settings(&display) {
+            Err(err) if !err.is_connection() => say!("while applying the display settings: {err}"),
+            res => res?,
+        }
+        match self.refresh_environments() {
+            Err(err) if !err.is_connection() => say!("while reading the server title: {err}"),
+            res => res?,
+        }
+        self.check_ladder()
+    }
+
+    /// only asks for the limits if we have difficulties to check them against
+    fn check_ladder(&mut self) -> Result<(), ClientError> {
+        if self.config.ladder_difficulties.is_empty() {
+            return Ok(());
+        }
+        match self.refresh_ladder() {
+            Err(err) if !err.is_connection() => say!("while reading the ladder limits: {err}"),
+            res => {
+                res?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.client.is_some()
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    /// drops the connection after it failed, [`Client::connect`] makes a new one
+    pub fn disconnect(&mut self) {
+        if self.state == ConnectionState::Authenticated {
+            self.state = ConnectionState::Reconnecting;
+        }
+        if let Some(old) = self.client.take() {
+            // this stops the reader thread of the old connection
+            let _ = old.shutdown(Shutdown::Both);
+        }
+        // responses for calls on the old connection will never arrive
+        self.calls.clear();
+        self.responses.clear();
+        // we will not hear about the end of it
+        self.in_warmup = false;
+        self.drop_map_switch();
+        self.ping = None;
+        self.echo = None;
+        self.callbacks_enabled = false;
+        self.last_frame = Instant::now();
+        self.stats.connected_since = None;
+    }
+
+    /// sleeps for `duration`, unless a shutdown is requested in the meantime
+    ///
+    /// returns whether it was.
+    pub fn sleep_unless_shutdown(&mut self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+            match self.events.recv_timeout(left) {
+                Ok(Event::Shutdown) => return true,
+                // monitoring wants to know that we are reconnecting right now
+                Ok(Event::Http { path, reply }) => {
+                    let _ = reply.send(self.http_response(&path));
+                }
+                // nothing is sent while we are away, so this only queues it
+                Ok(Event::Control(line)) => {
+                    if let Err(err) = self.handle_control(&line) {
+                        say!("while queueing a control command: {err}");
+                    }
+                }
+                // the download went on without the server
+                Ok(Event::Prefetched {
+                    candidate,
+                    rel_path,
+                    result,
+                }) => self.prefetch_done(candidate, rel_path, result),
+                // can only be from the connection that just failed, refills
+                // are only ever deferred
+                Ok(Event::Frame { .. } | Event::Closed { .. } | Event::RefillPrefetch) => {}
+                Err(_) => break,
+            }
+        }
+        self.shutdown_requested()
+    }
+
+    /// whether a shutdown is waiting for the outermost loop
+    pub(crate) fn shutdown_requested(&self) -> bool {
+        self.deferred.iter().any(|e| matches!(e, Event::Shutdown))
+    }
+
+    fn stream(&mut self) -> io::Result<&mut TcpStream> {
+        self.client
+            .as_mut()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))
+    }
+
+    /// a sender for feeding events into the message loop from other threads
+    pub(crate) fn events_tx(&self) -> Sender<Event> {
+        self.events_tx.clone()
+    }
+
+    pub fn write_u32(&mut self, val: u32) -> io::Result<()> {
+        self.stream()?.write_all(&val.to_le_bytes())
+    }
+
+    pub fn new_handle(&mut self) -> u32 {
+        self.handle += 1;
+        if self.handle >= 0xffffff00 {
+            self.handle = 0x80000000;
+        }
+        self.handle
+    }
+
+    pub fn call<R>(
+        &mut self,
+        f: &'static str,
+        args: impl dxr::TryToParams,
+    ) -> Result<R, ClientError>
+    where
+        R: TryFromValue,
+    {
+        let params = args
+            .try_to_params()
+            .map_err(|err| ClientError::Encode(err.to_string()))?;
+        let res = self.call_raw(f, params)?;
+        R::try_from_value(&res).map_err(|err| ClientError::Type {
+            method: f.to_owned(),
+            err,
+        })
+    }
+
+    /// like [`Client::call`], but with params and result left as xml-rpc values
+    ///
+    /// useful for methods that are only known at runtime, or whose result
+    /// does not always have the same shape.
+    pub fn call_raw(&mut self, f: &str, params: Vec<Value>) -> Result<Value, ClientError> {
+        self.wait_for_call_slot()?;
+        let handle = self.send_call(f, params)?;
+        let call = SentCall {
+            method: f.to_owned(),
+            sent: Instant::now(),
+        };
+        self.calls.insert(handle, call);
+        self.stats.max_in_flight = self.stats.max_in_flight.max(self.calls.len());
+        let msg = self.await_response(handle)?;
+        if msg.is_empty() {
+            return Err(ClientError::Decode(format!("empty response to {f}")));
+        }
+
+        if let Ok(res) = dxr::deserialize_xml::<FaultResponse>(&msg) {
+            let fault = Fault::try_from(res).map_err(|err| ClientError::Decode(err.to_string()))?;
+            self.stats.rpc_faults += 1;
+            return Err(fault.into());
+        }
+        let res: MethodResponse =
+            dxr::deserialize_xml(&msg).map_err(|err| ClientError::Decode(err.to_string()))?;
+        Ok(res.inner())
+    }
+
+    /// waits until the response for `handle` is in
+    ///
+    /// responses to the calls further up the stack are kept for them, without
+    /// waking us.
+    fn await_response(&mut self, handle: u32) -> Result<String, ClientError> {
+        let outer = self.awaited_call.replace(handle);
+        let res = loop {
+            // a call made while handling a callback might have read it for us
+            if let Some(msg) = self.responses.remove(&handle) {
+                break Ok(msg);
+            }
+            if let Err(err) = self.await_messages() {
+                break Err(err);
+            }
+        };
+        self.awaited_call = outer;
+        res
+    }
+
+    /// whether the message loop should return to [`Client::await_response`]
+    fn response_ready(&self) -> bool {
+        match self.awaited_call {
+            Some(handle) => self.responses.contains_key(&handle),
+            None => !self.responses.is_empty(),
+        }
+    }
+
+    /// waits until fewer than `max_in_flight_calls` calls wait for a response
+    ///
+    /// calls made by the handlers of callbacks that arrive while waiting for
+    /// a response pile up on top of it, this keeps them from flooding the
+    /// server.
+    fn wait_for_call_slot(&mut self) -> Result<(), ClientError> {
+        let max = self.config.max_in_flight_calls;
+        if max == 0 || self.calls.len() < max {
+            return Ok(());
+        }
+        self.stats.in_flight_waits += 1;
+        let outer = std::mem::replace(&mut self.awaiting_slot, true);
+        let mut res = Ok(());
+        while res.is_ok() && self.calls.len() >= max {
+            res = self.await_messages();
+        }
+        self.awaiting_slot = outer;
+        res
+    }
+
+    /// writes a call without waiting for the response, returns its handle
+    pub(crate) fn send_call(&mut self, f: &str, params: Vec<Value>) -> Result<u32, ClientError> {
+        self.check_allowed(f)?;
+        self.check_destructive(f)?;
+        self.limiter.acquire();
+        let method = MethodCall::new(f.to_owned(), params);
+        let msg =
+            dxr::serialize_xml(&method).map_err(|err| ClientError::Encode(err.to_string()))?;
+        self.write_u32(msg.len() as u32)?;
+        let handle = self.new_handle();
+        self.write_u32(handle)?;
+        self.stream()?.write_all(msg.as_bytes())?;
+        trace(true, handle, &msg);
+        Ok(handle)
+    }
+
+    /// calls a `GetXList(max, start)` style method until all pages are fetched
+    ///
+    /// pages that fault for a moment are asked for again, see
+    /// [`Client::call_with_retry`].
+    pub fn call_paged<T>(&mut self, f: &'static str) -> Result<Vec<T>, ClientError>
+    where
+        T: TryFromValue,
+    {
+        let mut all = Vec::new();
+        loop {
+            let page: Vec<T> =
+                self.call_with_retry(f, (PAGE_SIZE, all.len() as i32), PAGE_ATTEMPTS)?;
+            let done = page.len() < PAGE_SIZE as usize;
+            all.extend(page);
+            if done {
+                return Ok(all);
+            }
+        }
+    }
+
+    /// [`Client::await_messages`] until the connection fails or we are asked
+    /// to stop
+    ///
+    /// other errors, like a fault on a call made for a control command, are
+    /// reported and the loop goes on.
+    pub fn run_messages(&mut self) -> Result<(), ClientError> {
+        loop {
+            match self.await_messages() {
+                Err(err) if !err.is_connection() => {
+                    self.report_error("handling the server's messages", err)
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// this will wait for callbacks, or the response to the innermost call
+    ///
+    /// control commands and status requests that arrive in the meantime are
+    /// handled as well. when nothing is waiting for a response, this returns
+    /// once a [`crate::ShutdownHandle`] was used.
+    pub fn await_messages(&mut self) -> Result<(), ClientError> {
+        self.await_messages_until(None)
+    }
+
+    /// like [`Client::await_messages`], but also returns at the deadline
+    ///
+    /// a shutdown is left in the queue for the outermost loop to see.
+    pub(crate) fn await_messages_until(
+        &mut self,
+        deadline: Option<Instant>,
+    ) -> Result<(), ClientError> {
+        loop {
+            // someone further up might be waiting for a response that is in
+            // already, requests have to wait until it is taken
+            if self.calls.is_empty() && self.responses.is_empty() {
+                match self.deferred.pop_front() {
+                    Some(Event::Control(line)) => {
+                        self.handle_control(&line)?;
+                        continue;
+                    }
+                    Some(Event::Http { path, reply }) => {
+                        // the http thread might have given up waiting already
+                        let _ = reply.send(self.http_response(&path));
+                        continue;
+                    }
+                    Some(Event::Shutdown) => {
+                        if deadline.is_some() {
+                            self.deferred.push_front(Event::Shutdown);
+                        }
+                        return Ok(());
+                    }
+                    Some(Event::RefillPrefetch) => {
+                        self.refill_prefetch()?;
+                        continue;
+                    }
+                    Some(Event::Prefetched {
+                        candidate,
+                        rel_path,
+                        result,
+                    }) => {
+                        self.prefetch_done(candidate, rel_path, result);
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+
+            let left = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            if left == Some(Duration::ZERO) {
+                return Ok(());
+            }
+            // we hold a sender ourselves, so this can only time out
+            let vote = self.vote_timeout();
+            let dislikes = self.dislike_timeout();
+            let summary = self.summary_timeout();
+            let switch = self.switch_timeout();
+            let event = match (
+                self.keepalive_timeout(),
+                left,
+                vote,
+                dislikes,
+                summary,
+                switch,
+            ) {
+                (None, None, None, None, None, None) => self.events.recv().unwrap(),
+                (keepalive, left, vote, dislikes, summary, switch) => {
+                    let timeout = keepalive
+                        .into_iter()
+                        .chain(left)
+                        .chain(vote)
+                        .chain(dislikes)
+                        .chain(summary)
+                        .chain(switch)
+                        .min()
+                        .unwrap();
+                    match self.events.recv_timeout(timeout) {
+                        Ok(event) => event,
+                        Err(_) if vote == Some(timeout) => {
+                            match self.end_map_vote() {
+                                Err(err) if !err.is_connection() => {
+                                    self.report_error("ending the map vote", err)
+                                }
+                                res => res?,
+                            }
+                            continue;
+                        }
+                        Err(_) if keepalive == Some(timeout) => {
+                            self.keepalive()?;
+                            continue;
+                        }
+                        Err(_) if dislikes == Some(timeout) => {
+                            match self.expire_dislikes() {
+                                Err(err) if !err.is_connection() => {
+                                    self.report_error("ending the dislike vote", err)
+                                }
+                                res => res?,
+                            }
+                            continue;
+                        }
+                        Err(_) if summary == Some(timeout) => {
+                            match self.send_session_summary() {
+                                Err(err) if !err.is_connection() => {
+                                    self.report_error("sending the session summary", err)
+                                }
+                                res => res?,
+                            }
+                            continue;
+                        }
+                        Err(_) if switch == Some(timeout) => {
+                            match self.tick_switch() {
+                                Err(err) if !err.is_connection() => {
+                                    self.report_error("switching the map", err)
+                                }
+                                res => res?,
+                            }
+                            continue;
+                        }
+                        Err(_) => continue,
+                    }
+                }
+            };
+            match event {
+                Event::Frame {
+                    conn,
+                    handle,
+                    msg,
+                    received,
+                } if conn == self.conn => {
+                    trace(false, handle, &msg);
+                    self.last_frame = Instant::now();
+                    if let Some((_, sent)) = self.ping.filter(|&(ping, _)| ping == handle) {
+                        self.stats.ping_rtt = Some(received.saturating_duration_since(sent));
+                        self.ping = None;
+                        continue;
+                    }
+                    if self.echo_frame(handle, &msg, received) {
+                        continue;
+                    }
+                    // were we expecting a response for this handle?
+                    if let Some(call) = self.calls.remove(&handle) {
+                        self.observe_call(&call, received);
+                        self.responses.insert(handle, msg);
+                        if self.response_ready() || self.awaiting_slot {
+                            return Ok(());
+                        }
+                        continue;
+                    }
+
+                    self.as_trusted(|c| c.handle_callback(&msg, handle))?;
+                    // the calls it made might have read the response we wait for
+                    if self.response_ready()
+                        || self.script_callback.is_some()
+                        || self.enough_players()
+                    {
+                        return Ok(());
+                    }
+                }
+                Event::Closed { conn, err } if conn == self.conn => {
+                    self.client = None;
+                    return Err(err.into());
+                }
+                // left over from a previous connection
+                Event::Frame { .. } | Event::Closed { .. } => {}
+                // requests are handled one at a time, so their replies stay in order
+                event @ (Event::Control(_)
+                | Event::Http { .. }
+                | Event::Shutdown
+                | Event::RefillPrefetch
+                | Event::Prefetched { .. }) => self.deferred.push_back(event),
+            }
+        }
+    }
+
+    /// reacts to a callback, only the connection failing is an error
+    ///
+    /// anything else that goes wrong is reported, so a fault on one callback
+    /// does not stop the controller, or fail a call that waits meanwhile.
+    pub fn handle_callback(&mut self, msg: &str, handle: u32) -> Result<(), ClientError> {
+        match self.dispatch_callback(msg, handle) {
+            Err(err) if !err.is_connection() => {
+                self.report_error("handling a callback", err);
+                Ok(())
+            }
+            res => res,
+        }
+    }
+
+    fn dispatch_callback(&mut self, msg: &str, _handle: u32) -> Result<(), ClientError> {
+        if self.closing {
+            return Ok(());
+        }
+        if msg.is_empty() {
+            debug!("ignoring an empty callback");
+            return Ok(());
+        }
+        // before parsing it, busy servers send lots of these
+        if self.is_ignored_callback(msg) {
+            return Ok(());
+        }
+        let call: MethodCall = match dxr::deserialize_xml(msg) {
+            Ok(call) => call,
+            Err(err) => {
+                say!("ignoring invalid callback: {err}");
+                return Ok(());
+            }
+        };
+        let name = call.name().to_owned();
+        let params = call.params();
+        if self.repl {
+            self.print_repl_callback(&name, &params);
+            return Ok(());
+        }
+
+        if let Some(forwarder) = &mut self.forwarder {
+            let params = if name == "ManiaPlanet.PlayerChat" {
+                redact_chat(&params)
+            } else {
+                Cow::Borrowed(&params[..])
+            };
+            forwarder.forward(&name, &params, self.config.server_timezone);
+        }
+
+        if name == "ManiaPlanet.PlayerChat" {
+            match <(i32, String, String, bool)>::try_from_params(&params) {
+                // the server itself has uid 0
+                Ok((0, ..)) => {}
+                Ok((_, login, text, _registered)) => {
+                    self.run_player_command(&login, |c| c.handle_chat(&login, &text))?
+                }
+                Err(err) => say!("invalid {name}: {err}"),
+            }
+        } else if name == MANIALINK_ANSWER {
+            match Callback::parse(&name, &params) {
+                Ok(Callback::ManialinkAnswer {
+                    login,
+                    answer,
+                    entries,
+                }) => self.run_player_command(&login, |c| {
+                    c.handle_manialink_answer(&login, &answer, &entries)
+                })?,
+                Ok(_) => {}
+                Err(err) => say!("ignoring {name}: {err}"),
+            }
+        } else if name == PLAYER_INFO_CHANGED {
+            match Callback::parse(&name, &params) {
+                Ok(Callback::PlayerInfoChanged(info)) => self.player_info_changed(info),
+                Ok(_) => {}
+                Err(err) => say!("ignoring {name}: {err}"),
+            }
+        } else if name == "ManiaPlanet.BeginMap" {
+            self.current_map = params.first().and_then(|p| MapInfo::try_from_value(p).ok());
+            self.dislikes.clear();
+            self.dislikes_since = None;
+            self.drop_map_switch();
+            self.standings.clear_scores();
+            self.announced = false;
+            self.retries_left = self.config.max_retries_per_map;
+            self.stats.maps_played += 1;
+            self.recover_tags();
+            match self.check_expected_map() {
+                Err(err) if !err.is_connection() => {
+                    self.report_error("checking the inserted map", err)
+                }
+                res => res?,
+            }
+
+            // the podium was too short for the vote, so it decides the map after this one
+            match self.end_map_vote() {
+                Err(err) if !err.is_connection() => self.report_error("ending the map vote", err),
+                res => res?,
+            }
+            self.vote_held = false;
+
+            if let Some(limit) = self.config.time_limit {
+                match self.reassert_time_limit(limit) {
+                    Err(err) if !err.is_connection() => {
+                        self.report_error("setting time limit", err)
+                    }
+                    res => res?,
+                }
+            }
+
+            // a new ladder mode applies from this map on
+            self.check_ladder()?;
+            match self.check_mode_change() {
+                Err(err) if !err.is_connection() => self.report_error("changing the mode", err),
+                res => res?,
+            }
+
+            if self.rotation_paused {
+                say!("the rotation is paused, not downloading a map");
+                match self.hold_current_map() {
+                    Err(err) if !err.is_connection() => self.report_error("keeping the map", err),
+                    res => res?,
+                }
+            } else {
+                // whatever was staged last map did not get its chance
+                self.insert_staged()?;
+                match self.grace_left() {
+                    Some(left) => self.preview_random_map(left),
+                    None if self.in_warmup && self.config.skip_warmup_downloads => {
+                        say!("not downloading a map during the warm-up")
+                    }
+                    None => {
+                        if !self.add_prefetched_map()? && !self.add_random_map()? {
+                            self.keep_fallback_map()?;
+                        }
+                    }
+                }
+            }
+            match self.save_match_settings() {
+                Err(err) if !err.is_connection() => {
+                    self.report_error("saving the match settings", err)
+                }
+                res => res?,
+            }
+        } else if (name == "ManiaPlanet.EndMatch" || name == "ManiaPlanet.EndMap")
+            && !self.rotation_paused
+        {
+            // the podium is the least disruptive moment to insert
+            self.insert_staged()?;
+            match self.announce_next_map() {
+                Err(err) if !err.is_connection() => {
+                    self.report_error("announcing the next map", err)
+                }
+                res => res?,
+            }
+            match self.start_map_vote() {
+                Err(err) if !err.is_connection() => self.report_error("starting the map vote", err),
+                res => res?,
+            }
+        } else if name == SCRIPT_CALLBACK {
+            match parse_script(&params) {
+                Ok((name, json)) => {
+                    self.handle_script_callback(&name, &json)?;
+                    if let Some(event) = Lifecycle::from_script(&name) {
+                        self.handle_lifecycle(event)?;
+                    }
+                }
+                Err(err) => say!("ignoring {name}: {err}"),
+            }
+        } else if name == "ManiaPlanet.PlayerConnect" {
+            if let Some(Ok(login)) = params.first().map(String::try_from_value) {
+                self.remember_player(&login)?;
+                self.players.insert(login);
+            }
+        } else if name == "ManiaPlanet.PlayerDisconnect" {
+            if let Some(Ok(login)) = params.first().map(String::try_from_value) {
+                self.debounce.forget(&login);
+                self.identities.left(&login);
+                self.spectators.remove(&login);
+                self.standings.forget(&login);
+                self.players.remove(&login);
+            }
+            // an empty server might not finish the map any time soon
+            if !self.staged.is_empty() && !self.rotation_paused && self.player_count()? == 0 {
+                self.insert_staged()?;
+            }
+        }
+        if let Some(event) = Lifecycle::from_method(&name) {
+            self.handle_lifecycle(event)?;
+        }
+
+        Ok(())
+    }
+
+    /// picks a new map and inserts or stages it, trying another one if the
+    /// download fails
+    ///
+    /// returns whether a map was inserted or staged.
+    fn add_random_map(&mut self) -> Result<bool, ClientError> {
+        for attempt in 0..DOWNLOAD_ATTEMPTS {
+            if attempt > 0 && !self.spend_retry() {
+                say!("could not find a suitable map, trying again next map");
+                return Ok(false);
+            }
+            let candidate = match self.random_map() {
+                Ok(candidate) => candidate,
+                // the source warned about it already
+                Err(err) if err.is::<CacheDry>() => return Ok(false),
+                Err(err) => {
+                    self.report_error("picking a map", err);
+                    return Ok(false);
+                }
+            };
+            say!("downloading map {}", candidate.id);
+            let res = match self.fetch_map(candidate.id) {
+                Ok(_) if self.config.deferred_insert => {
+                    self.staged.push_back(candidate);
+                    Ok(true)
+                }
+                Ok(rel_path) if self.add_map(&rel_path, self.config.insert_mode)? => {
+                    self.apply_time_budget(&candidate).map(|()| true)
+                }
+                Ok(_) => Ok(false),
+                Err(err) => Err(err),
+            };
+            match res {
+                // another map would end up in the same place
+                Err(ClientError::Download(err @ DownloadError::Filesystem(_))) => {
+                    self.report_error("saving a map", err);
+                    return Ok(false);
+                }
+                Err(ClientError::Download(err)) => self.report_error("downloading a map", err),
+                // skipped, which was logged already
+                Err(ClientError::MapInRotation(_)) => {}
+                res => return res,
+            }
+        }
+        Ok(false)
+    }
+
+    /// a failed command should not take the controller down, the player is
+    /// told what went wrong instead
+    fn run_player_command(
+        &mut self,
+        login: &str,
+        command: impl FnOnce(&mut Self) -> Result<(), ClientError>,
+    ) -> Result<(), ClientError> {
+        match self.as_untrusted(command) {
+            Err(err) if !err.is_connection() => {
+                self.report_error("running a chat command", &err);
+                self.chat_send_to_login(login, &err.to_string())
+            }
+            res => res,
+        }
+    }
+
+    /// how much of the startup grace period is left, `None` once it is over
+    pub(crate) fn grace_left(&self) -> Option<Duration> {
+        let grace = self.config.startup_grace?;
+        // callbacks can arrive while we are still connecting
+        let connected = self
+            .stats
+            .connected_since
+            .map_or(Duration::ZERO, |since| since.elapsed());
+        grace.checked_sub(connected).filter(|left| !left.is_zero())
+    }
+
+    /// shows what [`Client::add_random_map`] would do, without touching the
+    /// history or the server
+    fn preview_random_map(&mut self, left: Duration) {
+        self.stats.tmx_requests += 1;
+        let search = self.decayed_search();
+        match self.source.next_candidate(&search, &self.history) {
+            Ok(candidate) => say!(
+                "would download map {} (in grace period, {}s left)",
+                candidate.id,
+                left.as_secs_f32().ceil()
+            ),
+            Err(err) => {
+                self.stats.tmx_failures += 1;
+                self.report_error("picking a map", err)
+            }
+        }
+    }
+
+    /// picks a map from the source that was not played recently
+    pub fn random_map(&mut self) -> color_eyre::Result<MapCandidate> {
+        let candidate = self.pick_map(true, &[])?;
+        self.record_pick(&candidate);
+        Ok(candidate)
+    }
+
+    /// picks a map like [`Client::random_map`], but leaves the history, the
+    /// author cooldown and the retry budget alone
+    ///
+    /// for maps that might not be played, like the candidates of a vote.
+    /// `exclude` are ids that were picked already.
+    pub(crate) fn peek_random_map(&mut self, exclude: &[u64]) -> color_eyre::Result<MapCandidate> {
+        self.pick_map(false, exclude)
+    }
+
+    fn pick_map(
+        &mut self,
+        spend_retries: bool,
+        exclude: &[u64],
+    ) -> color_eyre::Result<MapCandidate> {
+        let mut filtered = 0;
+        for attempt in 0..PICK_ATTEMPTS {
+            // the first try is free, unless the budget ran out already
+            let exhausted = if !spend_retries {
+                false
+            } else if attempt == 0 {
+                self.retries_left == 0
+            } else {
+                !self.spend_retry()
+            };
+            if exhausted {
+                if filtered > 0 {
+                    say!(
+                        "the map filter rejected {filtered} maps before the retry budget ran out, it might be too strict"
+                    );
+                }
+                return Err(eyre!(
+                    "could not find a suitable map, trying again next map"
+                ));
+            }
+            self.stats.tmx_requests += 1;
+            let search = self.decayed_search();
+            let candidate = match self.source.next_candidate(&search, &self.history) {
+                Ok(candidate) => candidate,
+                Err(err) => {
+                    // running out of cached maps is not tmx's fault
+                    if !err.is::<CacheDry>() {
+                        self.stats.tmx_failures += 1;
+                    }
+                    return Err(err);
+                }
+            };
+            if self.history.contains(candidate.id)
+                || self.blocklist.contains(candidate.id)
+                || exclude.contains(&candidate.id)
+            {
+                continue;
+            }
+            if !self.fits_ladder(&candidate) {
+                say!(
+                    "skipping map {}, {} is outside the ladder limits",
+                    candidate.id,
+                    candidate.difficulty.as_deref().unwrap_or_default()
+                );
+                continue;
+            }
+            // sources that know nothing but the id have nothing to share
+            let bare = candidate == MapCandidate::from_id(candidate.id);
+            if !bare {
+                self.metadata.insert(candidate.clone());
+            }
+            // the filter, the cooldown and the environment need everything tmx knows about the map
+            let cooldown = self.config.author_cooldown;
+            let enrich = self.filter.is_some() || cooldown > 0 || self.limits_environment();
+            let candidate = if bare && enrich {
+                self.tmx_metadata(candidate.id).unwrap_or_else(|err| {
+                    debug!("no metadata of map {}: {err}", candidate.id);
+                    candidate
+                })
+            } else {
+                candidate
+            };
+            if let Some(author) = self.recent_author(&candidate) {
+                say!(
+                    "skipping map {}, {author} made one of the last {cooldown} maps",
+                    candidate.id
+                );
+                continue;
+            }
+            if !self.fits_environment(&candidate) {
+                say!(
+                    "skipping map {}, the server cannot load {} maps",
+                    candidate.id,
+                    candidate.environment.as_deref().unwrap_or_default()
+                );
+                continue;
+            }
+            if let Some(filter) = &self.filter {
+                if !filter(&candidate) {
+                    debug!("skipping map {}, the map filter rejected it", candidate.id);
+                    filtered += 1;
+                    continue;
+                }
+            }
+            return Ok(candidate);
+        }
+        if filtered > 0 {
+            say!("the map filter rejected {filtered} of {PICK_ATTEMPTS} candidates");
+        }
+        Err(eyre!("no new map after {PICK_ATTEMPTS} candidates"))
+    }
+
+    /// remembers that `candidate` is going to be played, for the history and
+    /// the author cooldown
+    pub(crate) fn record_pick(&mut self, candidate: &MapCandidate) {
+        self.history.push(candidate.id);
+        self.save_history();
+        if let Some(author) = &candidate.author {
+            self.recent_authors.push_back(author.clone());
+            while self.recent_authors.len() > self.config.author_cooldown {
+                self.recent_authors.pop_front();
+            }
+        }
+    }
+
+    /// the author of `candidate` if they made one of the last
+    /// `author_cooldown` maps, maps of unknown authors always pass
+    fn recent_author<'a>(&self, candidate: &'a MapCandidate) -> Option<&'a str> {
+        let author = candidate.author.as_deref()?;
+        self.recent_authors
+            .iter()
+            .rev()
+            .take(self.config.author_cooldown)
+            .any(|other| other.eq_ignore_ascii_case(author))
+            .then_some(author)
+    }
+
+    /// whether the budget of the current map allows another attempt
+    fn spend_retry(&mut self) -> bool {
+        match self.retries_left.checked_sub(1) {
+            Some(left) => {
+                self.retries_left = left;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn set_time_budget(&mut self, budget: Option<TimeBudget>) {
+        self.config.time_budget = budget;
+    }
+
+    /// only candidates `filter` returns true for are added to the rotation
+    ///
+    /// it runs after the other checks, like the history and the ladder. every
+    /// rejected candidate spends one of `max_retries_per_map`, so a filter
+    /// that rejects almost everything may leave a map without a successor.
+    pub fn set_map_filter(&mut self, filter: MapFilter) {
+        self.filter = Some(filter);
+    }
+
+    pub fn clear_map_filter(&mut self) {
+        self.filter = None;
+    }
+
+    /// used from the next pick on, until the config is reloaded
+    pub fn set_search(&mut self, search: MapSearch) {
+        self.config.search = search;
+    }
+
+    /// the limit only applies from the next map on, which is the one we just inserted
+    pub fn set_time_attack_limit(&mut self, limit: Duration) -> Result<(), ClientError> {
+        let ms = limit.as_millis().min(i32::MAX as u128) as i32;
+        let suc: bool = self.call("SetTimeAttackLimit", ms)?;
+        if !suc {
+            return Err(ClientError::Rejected("SetTimeAttackLimit"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn apply_time_budget(
+        &mut self,
+        candidate: &MapCandidate,
+    ) -> Result<(), ClientError> {
+        let (Some(budget), Some(author_time)) = (&self.config.time_budget, candidate.author_time)
+        else {
+            return Ok(());
+        };
+        let limit = budget.limit_for(author_time);
+        say!(
+            "setting time limit to {}s ({} / {})",
+            limit.as_secs(),
+            candidate.length.as_deref().unwrap_or("unknown length"),
+            candidate
+                .difficulty
+                .as_deref()
+                .unwrap_or("unknown difficulty"),
+        );
+        match self.set_time_attack_limit(limit) {
+            Err(err) if !err.is_connection() => say!("while setting time limit: {err}"),
+            res => res?,
+        }
+        Ok(())
+    }
+
+    /// returns whether the map was added to the rotation, to be played next
+    pub fn download_map(&mut self, id: u64) -> Result<bool, ClientError> {
+        self.download_map_to(id, InsertMode::Next)
+    }
+
+    /// like [`Client::download_map`], but the map can go into the pool instead
+    pub fn download_map_to(&mut self, id: u64, mode: InsertMode) -> Result<bool, ClientError> {
+        // so we know what we got when it is announced or voted on later
+        if !self.config.offline {
+            if let Err(err) = self.tmx_metadata(id) {
+                debug!("no tmx metadata of map {id}: {err}");
+            }
+        }
+        let rel_path = self.fetch_map(id)?;
+        self.add_map(&rel_path, mode)
+    }
+
+    /// downloads the map into the maps directory, unless it is already there
+    ///
+    /// returns the path relative to the maps directory. a map that is in the
+    /// rotation already is handled as `on_duplicate` says.
+    pub fn fetch_map(&mut self, id: u64) -> Result<String, ClientError> {
+        let rel_path = self.map_path(id);
+        let dest = self.maps_dir_path(&rel_path)?;
+        self.fetch_map_to(id, &dest)?;
+        self.resolve_duplicate(&rel_path)?;
+        self.catalog_download(id, &rel_path, &dest);
+        if self.config.download_thumbnails {
+            self.fetch_thumbnail(id, &dest.with_file_name(format!("{id}.jpg")));
+        }
+        if self.config.download_replays {
+            let path = self.replays_dir_path(&format!("TMX/{id}.Replay.Gbx"))?;
+            self.fetch_replay(id, &path);
+        }
+        if let Some(dir) = dest.parent() {
+            match self.evict_maps(dir, id) {
+                Err(err) if !err.is_connection() => {
+                    self.report_error("making room in the maps directory", err)
+                }
+                res => res?,
+            }
+        }
+        Ok(rel_path)
+    }
+
+    /// remembers that `rel_path` is map `id` from tmx, with what its header says
+    fn catalog_download(&mut self, id: u64, rel_path: &str, dest: &Path) {
+        let mut entry = match self.catalog.remove(rel_path) {
+            Some(entry) if entry.tmx_id() == Some(id) => entry,
+            _ => CatalogEntry::new(MapOrigin::Tmx { id }),
+        };
+        if let Some(header) = fs::read(dest)
+            .ok()
+            .and_then(|file| GbxMapHeader::parse(&file))
+        {
+            entry.uid = Some(header.uid);
+            entry.name = Some(header.name);
+        }
+        entry.orphaned = false;
+        self.catalog.insert(rel_path, entry);
+        self.save_catalog();
+    }
+
+    /// notes something that came with map `id` in its catalog entry
+    fn catalog_extra(&mut self, id: u64, update: impl FnOnce(&mut CatalogEntry)) {
+        let rel_path = self.map_path(id);
+        let Some(entry) = self.catalog.get_mut(&rel_path) else {
+            return;
+        };
+        if entry.tmx_id() != Some(id) {
+            return;
+        }
+        let old = entry.clone();
+        update(entry);
+        let changed = *entry != old;
+        if changed {
+            self.save_catalog();
+        }
+    }
+
+    fn fetch_map_to(&mut self, id: u64, dest: &Path) -> Result<(), DownloadError> {
+        if dest.exists() {
+            say!("map is already downloaded");
+            return Ok(());
+        }
+        if let Some(cache) = self.config.download_cache.clone() {
+            match self.fetch_cached(id, &cache.join(map_file_name(id)), dest) {
+                Err(DownloadError::Filesystem(err)) => say!("not using the download cache: {err}"),
+                res => return res,
+            }
+        }
+        self.download_to(id, dest)
+    }
+
+    /// the map is fine without one, so failures are only logged
+    ///
+    /// connection problems are retried from the budget of the map.
+    pub(crate) fn fetch_thumbnail(&mut self, id: u64, path: &Path) {
+        if !path.exists() && !self.config.offline {
+            let url = format!("https://trackmania.exchange/maps/thumbnail/{id}");
+            self.fetch_extra(id, "thumbnail", &url, path);
+        }
+        if path.exists() {
+            self.catalog_extra(id, |entry| entry.thumbnail = Some(path.to_owned()));
+        }
+    }
+
+    /// the best replay tmx has of the map, which may well be none
+    fn fetch_replay(&mut self, id: u64, path: &Path) {
+        if !path.exists() && !self.config.offline {
+            self.download_replay(id, path);
+        }
+        if path.exists() {
+            self.catalog_extra(id, |entry| entry.replay = Some(path.to_owned()));
+        }
+    }
+
+    fn download_replay(&mut self, id: u64, path: &Path) {
+        let Some(replay_id) = self.best_replay_id(id) else {
+            return;
+        };
+        if let Some(dir) = path.parent() {
+            if let Err(err) = fs::create_dir_all(dir) {
+                self.report_error("creating the replay directory", err);
+                return;
+            }
+        }
+        let url = format!("https://trackmania.exchange/recordgbx/{replay_id}");
+        self.fetch_extra(id, "replay", &url, path);
+    }
+
+    fn best_replay_id(&mut self, id: u64) -> Option<u64> {
+        self.stats.tmx_requests += 1;
+        let url = format!("https://trackmania.exchange/api/replays/get_replays/{id}");
+        let res = self
+            .exchange
+            .get(url)
+            .send()
+            .and_then(|res| res.error_for_status())
+            .and_then(|res| res.text());
+        let replays = match res.map(|text| serde_json::from_str::<serde_json::Value>(&text)) {
+            Ok(Ok(replays)) => replays,
+            Ok(Err(err)) => {
+                debug!("tmx sent no replay list for map {id}: {err}");
+                return None;
+            }
+            Err(err) => {
+                self.stats.tmx_failures += 1;
+                debug!("could not list the replays of map {id}: {err}");
+                return None;
+            }
+        };
+        // the best one comes first
+        let replay_id = replays
+            .as_array()
+            .and_then(|replays| replays.first())
+            .and_then(|replay| replay.get("ReplayID"))
+            .and_then(|id| id.as_u64());
+        if replay_id.is_none() {
+            debug!("map {id} has no replay on tmx");
+        }
+        replay_id
+    }
+
+    /// downloads something that comes with the map, failures are only logged
+    ///
+    /// connection problems are retried from the budget of the map.
+    fn fetch_extra(&mut self, id: u64, what: &str, url: &str, path: &Path) {
+        for attempt in 1..=DOWNLOAD_ATTEMPTS {
+            self.stats.tmx_requests += 1;
+            match download_file(&self.exchange, url, path) {
+                Ok(()) => return,
+                Err(DownloadError::Status(reqwest::StatusCode::NOT_FOUND)) => {
+                    debug!("map {id} has no {what}");
+                    return;
+                }
+                Err(err @ DownloadError::Http(_))
+                    if attempt < DOWNLOAD_ATTEMPTS && self.spend_retry() =>
+                {
+                    self.stats.tmx_failures += 1;
+                    debug!("retrying the {what} of map {id}: {err}");
+                }
+                Err(err) => {
+                    self.stats.tmx_failures += 1;
+                    self.report_error(&format!("downloading a {what}"), err);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn fetch_cached(&mut self, id: u64, cached: &Path, dest: &Path) -> Result<(), DownloadError> {
+        if cached.exists() {
+            say!("map is in the download cache");
+        } else {
+            // other servers might be copying from the cache right now, so they
+            // should only ever see complete maps
+            self.download_to(id, cached)?;
+        }
+        if let Err(err) = fs::copy(cached, dest) {
+            let _ = fs::remove_file(dest);
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    fn download_to(&mut self, id: u64, path: &Path) -> Result<(), DownloadError> {
+        if self.config.offline {
+            return Err(DownloadError::Offline);
+        }
+        self.stats.tmx_requests += 1;
+        let url = format!("https://trackmania.exchange/maps/download/{id}");
+        if let Err(err) = download_file(&self.exchange, &url, path) {
+            self.stats.tmx_failures += 1;
+            return Err(err);
+        }
+        self.stats.maps_downloaded += 1;
+        Ok(())
+    }
+
+    /// inserts a downloaded map so it is played next
+    pub fn insert_map(&mut self, rel_path: &str) -> Result<bool, ClientError> {
+        self.add_map(rel_path, InsertMode::Next)
+    }
+
+    /// adds a downloaded map to the rotation, to be played next or whenever
+    /// its turn comes
+    pub fn add_map(&mut self, rel_path: &str, mode: InsertMode) -> Result<bool, ClientError> {
+        let method = match mode {
+            InsertMode::Next => "InsertMap",
+            InsertMode::Pool => "AddMap",
+        };
+        let inserted = match self.call::<bool>(method, rel_path) {
+            Err(ClientError::Fault(err)) => {
+                self.report_error("inserting map", err.string());
+                false
+            }
+            Err(err) => return Err(err),
+            Ok(suc) => suc,
+        };
+        if inserted {
+            // shows whether the download is the map we meant to get
+            match self.get_map_info(rel_path) {
+                Ok(info) if mode == InsertMode::Next => {
+                    say!("inserted {} by {}", info.Name, info.AuthorNickname);
+                    self.expected_next = Some(ExpectedMap::new(&info));
+                }
+                // we do not know when it is played, so there is nothing to check
+                Ok(info) => say!("added {} by {}", info.Name, info.AuthorNickname),
+                Err(err) if !err.is_connection() => say!("while checking the inserted map: {err}"),
+                res => {
+                    res?;
+                }
+            }
+        }
+        Ok(inserted)
+    }
+}
+
+/// the hello is not framed like everything after it, it is just a length
+/// and the text
+///
+/// tls and http ports wait for us to speak first, others send something that
+/// is not text, both end up as [`ClientError::NotGbxRemote`]. when we
+/// `reconnect`, the port was the right one before, and a server that is
+/// starting up again may just be slow, so waiting too long stays an io error.
+fn read_hello(stream: &mut TcpStream, reconnect: bool) -> Result<String, ClientError> {
+    stream.set_read_timeout(Some(HELLO_TIMEOUT))?;
+    let mut len = [0; 4];
+    match stream.read_exact(&mut len) {
+        Err(err) if is_timeout(&err) && !reconnect => {
+            return Err(ClientError::NotGbxRemote(format!(
+                "it sent nothing for {}s",
+                HELLO_TIMEOUT.as_secs()
+            )))
+        }
+        res => res?,
+    }
+    let not_text = |bytes: &[u8]| {
+        ClientError::NotGbxRemote(format!(
+            "it started with {:?}",
+            String::from_utf8_lossy(bytes)
+        ))
+    };
+    let n = u32::from_le_bytes(len);
+    if n == 0 || n > MAX_HELLO_LEN {
+        return Err(not_text(&len));
+    }
+    let mut hello = vec![0; n as usize];
+    stream.read_exact(&mut hello)?;
+    if !hello.iter().all(|&b| b.is_ascii_graphic() || b == b' ') {
+        return Err(not_text(&hello));
+    }
+    stream.set_read_timeout(None)?;
+    Ok(String::from_utf8_lossy(&hello).into_owned())
+}
+
+/// reads frames on a thread of its own, so the message loop can wait for
+/// the server and the control channel at the same time
+fn spawn_reader(mut stream: TcpStream, conn: u64, max_frame_len: u32, events: Sender<Event>) {
+    thread::spawn(move || {
+        let mut decoder = FrameDecoder::with_max_len(max_frame_len);
+        let mut buf = vec![0; 64 * 1024];
+        let err = loop {
+            let n = match stream.read(&mut buf) {
+                Ok(0) => break io::Error::from(io::ErrorKind::UnexpectedEof),
+                Ok(n) => n,
+                // the decoder keeps partial frames, so we can just read again
+                Err(err) if is_timeout(&err) => continue,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => break err,
+            };
+            decoder.push(&buf[..n]);
+
+            loop {
+                let frame = match decoder.next_frame() {
+                    Ok(Some(frame)) => frame,
+                    Ok(None) => break,
+                    // whatever follows is not where a frame starts either
+                    Err(err) => {
+                        let err = io::Error::new(io::ErrorKind::InvalidData, err);
+                        let _ = events.send(Event::Closed { conn, err });
+                        return;
+                    }
+                };
+                let msg = match String::from_utf8(frame.body) {
+                    Ok(msg) => msg,
+                    Err(err) => {
+                        let err = io::Error::new(io::ErrorKind::InvalidData, err);
+                        let _ = events.send(Event::Closed { conn, err });
+                        return;
+                    }
+                };
+                let event = Event::Frame {
+                    conn,
+                    handle: frame.handle,
+                    msg,
+                    received: Instant::now(),
+                };
+                if events.send(event).is_err() {
+                    return;
+                }
+            }
+        };
+        let _ = events.send(Event::Closed { conn, err });
+    });
+}
+
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+#[allow(non_snake_case)]
+#[derive(TryFromValue, TryToValue, Debug, Clone)]
+pub struct MapInfo {
+    pub Name: String,
+    pub UId: String,
+    pub FileName: String,
+    pub Environnement: String,
+    pub Author: String,
+    pub AuthorNickname: String,
+    pub GoldTime: i32,
+    pub CopperPrice: i32,
+    pub MapType: String,
+    pub MapStyle: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use dxr::Value;
+
+    use crate::{
+        identity::PlayerEntry,
+        testing::{
+            callback_xml, gbx_map, map_info, temp_dir, test_client, test_config, MockServer, Reply,
+        },
+        Client, ClientError, MapCandidate, MapOrigin, MemoryStorage, StubMapSource,
+    };
+
+    #[test]
+    fn faults_while_handling_a_callback_are_reported() {
+        let server = MockServer::start(|method, _| match method {
+            "GetPlayerList" => Some(Reply::Fault(-1000, "not now")),
+            "InsertMap" => Some(Reply::Fault(-1000, "no such map")),
+            _ => None,
+        });
+        let mut client = test_client(test_config(&server));
+        client.staged.push_back(MapCandidate::from_id(1));
+        let msg = callback_xml(
+            "ManiaPlanet.PlayerDisconnect",
+            vec![
+                Value::string("alice".to_owned()),
+                Value::string(String::new()),
+            ],
+        );
+        client.handle_callback(&msg, 0).unwrap();
+        let last_error = client.stats.last_error.clone().unwrap();
+        assert!(last_error.contains("not now"), "{last_error}");
+
+        // inserting the staged map faults this time
+        let msg = callback_xml("ManiaPlanet.EndMap", vec![]);
+        client.handle_callback(&msg, 0).unwrap();
+        let last_error = client.stats.last_error.clone().unwrap();
+        assert!(last_error.contains("no such map"), "{last_error}");
+        assert!(client.is_connected());
+    }
+
+    fn log_in_error(authenticate: Reply) -> ClientError {
+        let server = MockServer::start(move |method, _| match method {
+            "Authenticate" => Some(authenticate.clone()),
+            _ => None,
+        });
+        let mut client = Client::new(test_config(&server));
+        client.set_storage(Box::new(MemoryStorage::default()));
+        client.log_in().unwrap_err()
+    }
+
+    #[test]
+    fn a_refused_login_is_an_auth_error() {
+        let err = log_in_error(Reply::of(false));
+        assert!(
+            matches!(&err, ClientError::Auth(reason) if reason == "wrong login or password"),
+            "{err}"
+        );
+        let err = log_in_error(Reply::Fault(-1000, "Login unknown."));
+        assert!(
+            matches!(&err, ClientError::Auth(reason) if reason == "Login unknown."),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn recent_and_disliked_maps_are_not_picked() {
+        let server = MockServer::start(|_, _| None);
+        let mut client = test_client(test_config(&server));
+        client.source = Box::new(StubMapSource::new([1, 2, 1, 3]));
+        client.blocklist.insert(2);
+
+        assert_eq!(client.random_map().unwrap().id, 1);
+        assert_eq!(client.random_map().unwrap().id, 3);
+        assert!(client.history().contains(1) && client.history().contains(3));
+        // the stub has nothing left
+        assert!(client.random_map().is_err());
+    }
+
+    #[test]
+    fn skipped_candidates_spend_the_retry_budget() {
+        let server = MockServer::start(|_, _| None);
+        let mut config = test_config(&server);
+        config.max_retries_per_map = 1;
+        let mut client = test_client(config);
+        client.source = Box::new(StubMapSource::new([1, 1, 1, 2]));
+
+        assert_eq!(client.random_map().unwrap().id, 1);
+        let err = client.random_map().unwrap_err();
+        assert!(err.to_string().contains("could not find"), "{err}");
+        assert_eq!(client.retries_left, 0);
+    }
+
+    #[test]
+    fn nested_calls_get_their_own_response() {
+        let server = MockServer::start(|method, _| match method {
+            // the response to the outer call arrives before the one the
+            // callback waits for
+            "Outer" => {
+                let joined = callback_xml(
+                    "ManiaPlanet.PlayerConnect",
+                    vec![Value::string("alice".to_owned()), Value::boolean(false)],
+                );
+                Some(Reply::Callback(joined, Box::new(Reply::of(41))))
+            }
+            "GetPlayerInfo" => Some(Reply::of(PlayerEntry {
+                Login: "alice".to_owned(),
+                NickName: "Alice".to_owned(),
+                PlayerId: 2,
+            })),
+            _ => None,
+        });
+        let mut client = test_client(test_config(&server));
+        assert_eq!(client.call::<i32>("Outer", ()).unwrap(), 41);
+        assert_eq!(client.nickname_of("alice").as_deref(), Some("Alice"));
+        assert!(client.responses.is_empty());
+    }
+
+    #[test]
+    fn empty_callbacks_are_ignored() {
+        let server = MockServer::start(|_, _| None);
+        let mut client = test_client(test_config(&server));
+        client.handle_callback("", 0).unwrap();
+        assert_eq!(client.stats.last_error, None);
+    }
+
+    #[test]
+    fn downloads_are_recorded_in_the_catalog() {
+        let dir = temp_dir("catalog");
+        std::fs::write(dir.join("5.Map.Gbx"), gbx_map("uid5", "five")).unwrap();
+        let maps_dir = dir.display().to_string();
+        let server = MockServer::start(move |method, _| match method {
+            "GetMapsDirectory" => Some(Reply::of(maps_dir.as_str())),
+            "GetMapList" => Some(Reply::of(Vec::<Value>::new())),
+            _ => None,
+        });
+        let mut client = test_client(test_config(&server));
+        let storage = MemoryStorage::default();
+        client.set_storage(Box::new(storage.clone()));
+
+        assert_eq!(client.fetch_map(5).unwrap(), "5.Map.Gbx");
+        let catalog = storage.catalog().unwrap();
+        let entry = catalog.get("5.Map.Gbx").unwrap();
+        assert_eq!(entry.origin, MapOrigin::Tmx { id: 5 });
+        assert_eq!(entry.uid.as_deref(), Some("uid5"));
+        assert_eq!(entry.name.as_deref(), Some("five"));
+        assert_eq!(entry.thumbnail, None);
+    }
+
+    #[test]
+    fn thumbnails_are_recorded_in_the_catalog() {
+        let dir = temp_dir("thumbnail");
+        std::fs::write(dir.join("5.Map.Gbx"), gbx_map("uid5", "five")).unwrap();
+        std::fs::write(dir.join("5.jpg"), b"jpeg").unwrap();
+        let maps_dir = dir.display().to_string();
+        let server = MockServer::start(move |method, _| match method {
+            "GetMapsDirectory" => Some(Reply::of(maps_dir.as_str())),
+            "GetMapList" => Some(Reply::of(Vec::<Value>::new())),
+            _ => None,
+        });
+        let mut config = test_config(&server);
+        config.download_thumbnails = true;
+        config.offline = true;
+        let mut client = test_client(config);
+
+        client.fetch_map(5).unwrap();
+        let entry = client.catalog.get("5.Map.Gbx").unwrap();
+        assert_eq!(entry.thumbnail, Some(dir.join("5.jpg")));
+    }
+
+    #[test]
+    fn replays_are_recorded_in_the_catalog() {
+        let user_data = temp_dir("replay");
+        let dir = user_data.join("Maps");
+        let replay = user_data.join("Replays/TMX/5.Replay.Gbx");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::create_dir_all(replay.parent().unwrap()).unwrap();
+        std::fs::write(dir.join("5.Map.Gbx"), gbx_map("uid5", "five")).unwrap();
+        std::fs::write(&replay, b"GBX").unwrap();
+        let maps_dir = dir.display().to_string();
+        let server = MockServer::start(move |method, _| match method {
+            "GetMapsDirectory" => Some(Reply::of(maps_dir.as_str())),
+            "GetMapList" => Some(Reply::of(Vec::<Value>::new())),
+            _ => None,
+        });
+        let mut config = test_config(&server);
+        config.download_replays = true;
+        config.offline = true;
+        let mut client = test_client(config);
+
+        client.fetch_map(5).unwrap();
+        let entry = client.catalog.get("5.Map.Gbx").unwrap();
+        assert_eq!(entry.replay, Some(replay));
+    }
+
+    #[test]
+    fn the_map_list_is_saved_as_match_settings() {
+        let server = MockServer::start(|method, _| match method {
+            "GetMapList" => Some(Reply::of(vec![
+                map_info("1.Map.Gbx", "a"),
+                map_info("M&M.Map.Gbx", "b"),
+            ])),
+            _ => None,
+        });
+        let mut config = test_config(&server);
+        config.match_settings_path = Some("unused".into());
+        let mut client = test_client(config);
+        let storage = MemoryStorage::default();
+        client.set_storage(Box::new(storage.clone()));
+
+        client.save_match_settings().unwrap();
+        let xml = storage.match_settings().unwrap();
+        assert!(xml.contains("<map><file>1.Map.Gbx</file></map>"), "{xml}");
+        assert!(xml.contains("<file>M&amp;M.Map.Gbx</file>"), "{xml}");
+    }
+}
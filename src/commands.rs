@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use crate::{Client, ClientError, ForcedMod, ForcedMods, MapSearch, Role};
+
+impl Client {
+    /// whether `login` may use the commands for mods
+    pub fn is_admin(&self, login: &str) -> bool {
+        self.role_of(login) >= Role::Mod
+    }
+
+    pub fn chat_send_to_login(&mut self, login: &str, msg: &str) -> Result<(), ClientError> {
+        self.call::<bool>("ChatSendServerMessageToLogin", (msg, login))?;
+        Ok(())
+    }
+
+    /// runs the chat command in `text`, if there is one
+    pub(crate) fn handle_chat(&mut self, login: &str, text: &str) -> Result<(), ClientError> {
+        let Some(command) = text.strip_prefix('/') else {
+            return Ok(());
+        };
+        let mut args = command.split_whitespace();
+        let Some(name) = args.next() else {
+            return Ok(());
+        };
+        let args: Vec<&str> = args.collect();
+        // for arguments that may have spaces of their own, like file names
+        let rest = command.trim_start()[name.len()..].trim();
+
+        // spamming a command does nothing but the first time
+        let window = Duration::from_millis(self.config().command_debounce_ms);
+        let key = [&[name][..], &args].concat().join(" ");
+        if !self.debounce.allow(login, &key, window) {
+            return Ok(());
+        }
+        if self.vote_command(login, name)? {
+            return Ok(());
+        }
+
+        match name {
+            "reload" => self.command_for(login, Role::Owner, |c| c.reload_command(login)),
+            "mod" => self.command_for(login, Role::Owner, |c| c.mod_command(login, &args)),
+            "dislike" => self.dislike_command(login),
+            "next" => self.command_for(login, Role::Mod, |c| c.next_command(login)),
+            "cancel" => self.command_for(login, Role::Mod, |c| c.cancel_command(login)),
+            "pause" => self.command_for(login, Role::Mod, |c| c.pause_command(login)),
+            "resume" => self.command_for(login, Role::Mod, |c| c.resume_command(login)),
+            "nextinfo" => self.nextinfo_command(login),
+            "guest" | "guests" => {
+                self.command_for(login, Role::Mod, |c| c.guest_command(login, &args))
+            }
+            "lock" => self.command_for(login, Role::Mod, |c| c.lock_command(login, &args)),
+            "unlock" => self.command_for(login, Role::Mod, |c| c.unlock_command(login)),
+            "search" => self.command_for(login, Role::Owner, |c| c.search_command(login, &args)),
+            "bans" => self.command_for(login, Role::Mod, |c| c.bans_command(login)),
+            "settings" => self.command_for(login, Role::Mod, |c| c.settings_command(login)),
+            "info" => self.command_for(login, Role::Mod, |c| c.info_command(login)),
+            "mapinfo" => self.command_for(login, Role::Mod, |c| c.mapinfo_command(login, &args)),
+            "netstats" => self.command_for(login, Role::Mod, |c| c.netstats_command(login)),
+            "ladder" => self.command_for(login, Role::Owner, |c| c.ladder_command(login, &args)),
+            "seed" => self.command_for(login, Role::Owner, |c| c.seed_command(login, &args)),
+            "force" => self.command_for(login, Role::Mod, |c| c.force_command(login, &args)),
+            "cast" => self.command_for(login, Role::Mod, |c| c.cast_command(login, &args)),
+            "mode" => self.command_for(login, Role::Owner, |c| c.mode_command(login, &args)),
+            "loadmode" => {
+                self.command_for(login, Role::Owner, |c| c.loadmode_command(login, &args))
+            }
+            "gameinfo" => self.command_for(login, Role::Mod, |c| c.gameinfo_command(login)),
+            "skipwarmup" => self.command_for(login, Role::Mod, |c| c.skipwarmup_command(login)),
+            "replay" => self.command_for(login, Role::Mod, |c| c.replay_command(login, &args)),
+            "addlocal" => self.command_for(login, Role::Owner, |c| c.addlocal_command(login, rest)),
+            "export-rotation" => self.command_for(login, Role::Owner, |c| {
+                c.export_rotation_command(login, &args)
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// `/search` shows the tmx search, `/search <url>` replaces it with the
+    /// filters of a search url from the browser
+    fn search_command(&mut self, login: &str, args: &[&str]) -> Result<(), ClientError> {
+        let reply = match args {
+            [] => format!("searching {}", self.config().search.to_query()),
+            [url] => match MapSearch::from_tmx_url(url) {
+                Ok(mut search) => {
+                    // the url has no weights, keep ours
+                    search.tag_weights = self.config().search.tag_weights.clone();
+                    let reply = format!("now searching {}", search.to_query());
+                    self.set_search(search);
+                    reply
+                }
+                Err(err) => err.to_string(),
+            },
+            _ => "usage: /search [tmx search url]".to_owned(),
+        };
+        self.chat_send_to_login(login, &reply)
+    }
+
+    fn reload_command(&mut self, login: &str) -> Result<(), ClientError> {
+        let Some(path) = self.config_path.clone() else {
+            return self.chat_send_to_login(login, "the config was not loaded from a file");
+        };
+        let reply = match self.reload_config(&path) {
+            Ok(restart_required) if restart_required.is_empty() => "config reloaded".to_owned(),
+            Ok(restart_required) => format!(
+                "config reloaded, restart required for: {}",
+                restart_required.join(", ")
+            ),
+            Err(err) => err.to_string(),
+        };
+        say!("{} reloaded the config: {reply}", self.display_name(login));
+        self.chat_send_to_login(login, &reply)
+    }
+
+    /// `/mod` shows the forced mods, `/mod <env> <url>` forces one and `/mod off` clears them
+    fn mod_command(&mut self, login: &str, args: &[&str]) -> Result<(), ClientError> {
+        let mods = match args {
+            [] => {
+                let current = self.get_forced_mods()?;
+                let reply = if current.Mods.is_empty() {
+                    "no mods are forced".to_owned()
+                } else {
+                    let mods: Vec<String> = current
+                        .Mods
+                        .iter()
+                        .map(|m| format!("{}: {}", m.EnvName, m.Url))
+                        .collect();
+                    mods.join(", ")
+                };
+                return self.chat_send_to_login(login, &reply);
+            }
+            ["off"] => ForcedMods::default(),
+            [env, url] => ForcedMods {
+                Override: true,
+                Mods: vec![ForcedMod {
+                    EnvName: env.to_string(),
+                    Url: url.to_string(),
+                }],
+            },
+            _ => return self.chat_send_to_login(login, "usage: /mod [<env> <url> | off]"),
+        };
+
+        let reply = match self.set_forced_mods(&mods) {
+            Ok(()) => "forced mods updated, they apply from the next map".to_owned(),
+            Err(err) if !err.is_connection() => err.to_string(),
+            Err(err) => return Err(err),
+        };
+        self.chat_send_to_login(login, &reply)
+    }
+}
@@ -0,0 +1,598 @@
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    DisplaySettings, DuplicatePolicy, ForcedMods, InsertMode, MapSearch, MapVoteSettings, Role,
+    ServerTimezone, SessionSummary, SwitchCountdown, TagDecay, TimeBudget, DEFAULT_ALLOWED_METHODS,
+    DEFAULT_MAX_FRAME_LEN, DEFAULT_RETRYABLE_FAULTS,
+};
+
+/// the contents of `controller.toml`, every field is optional
+///
+/// a file can also list several servers as `[[servers]]` tables, see
+/// [`Config::load_all`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// names this server in the logs, defaults to its address
+    pub id: Option<String>,
+    pub address: String,
+    pub login: String,
+    pub password: String,
+
+    /// logins that may use every chat command, like the owners in `roles`
+    pub admins: Vec<String>,
+    /// the role of each login, like `{ alice = "owner", bob = "mod" }`,
+    /// everyone else is a player
+    pub roles: HashMap<String, Role>,
+    /// a player repeating the same chat command within this many milliseconds
+    /// is ignored, 0 turns it off
+    pub command_debounce_ms: u64,
+    /// a table or a tmx search url, see [`MapSearch::from_tmx_url`]
+    #[serde(deserialize_with = "crate::tmx::deserialize_search")]
+    pub search: MapSearch,
+    /// how many recently picked maps are not picked again
+    pub history_size: usize,
+    /// maps picked within this long are not picked again either, however
+    /// many maps came since
+    #[serde(rename = "no_repeat_window_secs", with = "secs::option")]
+    pub no_repeat_window: Option<Duration>,
+    /// a map is not picked if its author made one of this many maps picked
+    /// before it, 0 turns it off
+    pub author_cooldown: usize,
+    /// how often tmx may be asked again for a map that was already played,
+    /// failed to download and so on, in total per map
+    pub max_retries_per_map: usize,
+    /// how many maps we remember the tmx metadata of
+    pub metadata_cache_size: usize,
+    /// how long the tmx metadata of a map is reused before we ask again
+    #[serde(rename = "metadata_ttl_secs", with = "secs")]
+    pub metadata_ttl: Duration,
+    /// when set, the time limit is adjusted to every map we insert
+    pub time_budget: Option<TimeBudget>,
+    /// when set, this time limit is reapplied whenever a map starts
+    ///
+    /// use either this or `time_budget`, since this one overrides the limit
+    /// the budget picked for a map.
+    #[serde(rename = "time_limit_secs", with = "secs::option")]
+    pub time_limit: Option<Duration>,
+    /// address of a tcp listener that gets every callback as a line of json
+    pub forward_callbacks: Option<String>,
+    /// read json commands from stdin, see [`crate::ControlCommand`]
+    ///
+    /// the replies go to stdout, so the log goes to stderr.
+    pub stdin_control: bool,
+    /// applied every time we connect
+    pub forced_mods: Option<ForcedMods>,
+    /// how many players need to `/dislike` a map to remove it for good, 0 disables it
+    pub dislike_threshold: usize,
+    /// where the ids of disliked maps are kept
+    pub blocklist_path: PathBuf,
+    /// when set, the recently picked maps are kept here across restarts
+    pub history_path: Option<PathBuf>,
+    /// lowers the weight of disliked tags for a while
+    pub tag_decay: TagDecay,
+    /// when set, the decayed tags are kept here across restarts
+    pub tag_decay_path: Option<PathBuf>,
+    /// where the origin of the maps in the maps directory is kept, see
+    /// [`crate::Catalog`]
+    pub catalog_path: PathBuf,
+    /// when set, the map list is written here as match settings at the start
+    /// of every map, so the server can load it again with `LoadMatchSettings`
+    pub match_settings_path: Option<PathBuf>,
+    /// downloaded maps go to this directory inside the server's maps
+    /// directory, like `"TMX"`, it is created if needed
+    pub maps_subdir: Option<String>,
+    /// maps relative to the maps directory that one of is kept in the
+    /// rotation while no new map can be downloaded, they are never deleted
+    pub fallback_maps: Vec<String>,
+    /// whether maps picked for the rotation are played next or go to the end
+    /// of the map list, maps queued by hand are always played next
+    pub insert_mode: InsertMode,
+    /// download maps on `BeginMap`, but only insert them once the map ends
+    pub deferred_insert: bool,
+    /// what to do with a download that is in the map list already under
+    /// another file name, by its uid
+    pub on_duplicate: DuplicatePolicy,
+    /// on the first connect, compare the map list to the downloads and log
+    /// how many match, without changing either
+    pub reconcile_on_start: bool,
+    /// a `BeginMap` during the warm-up of a script mode downloads nothing
+    pub skip_warmup_downloads: bool,
+    /// keeps downloaded maps here, point several servers at the same directory
+    /// to download each map only once
+    pub download_cache: Option<PathBuf>,
+    /// also save the tmx thumbnail of every map as `<id>.jpg` next to it
+    pub download_thumbnails: bool,
+    /// also save the best tmx replay of every map, as
+    /// `Replays/TMX/<id>.Replay.Gbx` next to the maps directory
+    pub download_replays: bool,
+    /// once the maps we downloaded take more than this, the least recently
+    /// played ones that are not in the rotation are deleted
+    pub max_maps_dir_bytes: Option<u64>,
+    /// never contact tmx, maps are only picked from `download_cache`
+    ///
+    /// also set by passing `--offline`.
+    pub offline: bool,
+    /// how to read the dates the server sends, `"utc"`, `"local"` or an offset
+    /// like `"+02:00"`
+    pub server_timezone: ServerTimezone,
+    /// how long we wait for a clean shutdown after a signal before we just exit
+    #[serde(rename = "shutdown_timeout_secs", with = "secs")]
+    pub shutdown_timeout: Duration,
+    /// how long queued chat messages and skips stay useful while the server
+    /// is away, other control commands wait until it is back
+    #[serde(rename = "queue_ttl_secs", with = "secs")]
+    pub queue_ttl: Duration,
+    /// when set, an idle connection is checked this often, so nat and firewalls
+    /// keep it open and drops are noticed before the next real call
+    #[serde(rename = "keepalive_secs", with = "secs::option")]
+    pub keepalive: Option<Duration>,
+    /// longer frames from the server are taken for a broken stream, and the
+    /// connection is dropped instead of buffering them
+    pub max_frame_len: u32,
+    /// sends small frames right away instead of waiting for the server to
+    /// acknowledge the previous ones, which can add 40ms to every call
+    pub tcp_nodelay: bool,
+    /// callbacks that are dropped as soon as they arrive, by method or by
+    /// script event, like `"Trackmania.Event.WayPoint"`
+    ///
+    /// entries ending in `*` drop every callback starting with the rest.
+    /// features that need a dropped callback do not see it either.
+    pub ignored_callbacks: Vec<String>,
+    /// for this long after connecting, new maps are only picked and logged,
+    /// not downloaded, so the config can be checked first
+    #[serde(rename = "startup_grace_secs", with = "secs::option")]
+    pub startup_grace: Option<Duration>,
+    /// new calls to the server are limited to this many per second, 0 turns
+    /// the limit off
+    pub max_calls_per_sec: f64,
+    /// a call waits while this many others wait for a response, 0 turns
+    /// the limit off
+    pub max_in_flight_calls: usize,
+    /// calls that take longer to be answered are logged, 0 turns that off
+    pub slow_call_ms: u64,
+    /// fault codes, or parts of fault messages, after which read-only calls
+    /// like `GetMapList` are tried again, defaults to
+    /// [`crate::DEFAULT_RETRYABLE_FAULTS`]
+    pub retryable_faults: Vec<String>,
+    /// the range of the random pause between tmx requests when many maps are
+    /// added at once, like `[500, 2000]`
+    pub bulk_jitter_ms: (u64, u64),
+    /// when the rotation has fewer than `min_maps` maps on the first connect,
+    /// maps are added until it has that many, before the first `NextMap`
+    pub seed_on_start: bool,
+    pub min_maps: usize,
+    /// this many maps are picked and downloaded in the background, so a new
+    /// map starting only needs to insert one, 0 turns it off
+    pub prefetch_pool_size: usize,
+    /// the pool is filled up again once fewer maps than this are left in it,
+    /// defaults to as soon as one is missing
+    pub prefetch_refill_below: Option<usize>,
+    /// loaded into the server on connect and saved on shutdown, relative to
+    /// the server's `UserData/Config` directory
+    pub black_list_file: Option<String>,
+    /// when set, this is sent to chat at the end of each map, with `{name}`,
+    /// `{author}`, `{style}` and `{env}` replaced by the next map's
+    pub announce_next_map: Option<String>,
+    /// when set, players vote on the next map during the podium
+    pub map_vote: Option<MapVoteSettings>,
+    /// how long the server's own votes stay open, and how long dislikes have
+    /// to reach `dislike_threshold` once the first one came in
+    #[serde(rename = "call_vote_timeout_secs", with = "secs::option")]
+    pub call_vote_timeout: Option<Duration>,
+    /// below this many players, nobody gets to vote or dislike
+    pub min_vote_players: usize,
+    /// off unless set
+    pub session_summary: Option<SessionSummary>,
+    /// when set, `/next` and the `next` control command warn in chat and
+    /// wait before they switch the map
+    pub switch_countdown: Option<SwitchCountdown>,
+    /// applied on connect, toggles that are not listed stay as they are
+    pub display: DisplaySettings,
+    /// checks the calls of chat and control commands against `allowed_methods`
+    pub restricted: bool,
+    /// chat and control commands and the repl may not kick, ban, remove maps,
+    /// set passwords or delete files, see [`crate::DESTRUCTIVE_METHODS`]
+    ///
+    /// chat commands that need a role are not blocked. `arm` in the repl
+    /// lifts it for the session, setting this to false lifts it for good.
+    pub safe_mode: bool,
+    /// method names, or prefixes ending in `*`, defaults to
+    /// [`crate::DEFAULT_ALLOWED_METHODS`]
+    ///
+    /// prefixes never allow the [`crate::SECRET_METHODS`], they have to be
+    /// listed by name.
+    pub allowed_methods: Vec<String>,
+    /// the ladder points each tmx difficulty stands for, like `Expert = 40000`
+    ///
+    /// on a ladder server, maps whose difficulty is outside the server's
+    /// limits are not picked. difficulties that are not listed always pass.
+    pub ladder_difficulties: HashMap<String, f64>,
+    /// the tmx environments the server can load, like `["Stadium"]`
+    ///
+    /// unset, they follow from the title the server runs. empty, maps of any
+    /// environment are picked.
+    pub environments: Option<Vec<String>>,
+    /// a copy of everything logged goes here, with timestamps
+    pub log_file: Option<PathBuf>,
+    /// every message to and from the server goes here
+    pub protocol_trace: Option<PathBuf>,
+    /// the log and trace files are rotated once they are this large
+    pub log_max_bytes: u64,
+    /// how many rotated files are kept, as `file.1` to `file.N`
+    pub log_keep: usize,
+    /// also log details that help tuning, like the pauses between tmx requests
+    pub debug_log: bool,
+    /// serve the controller's status over http
+    pub enable_http_status: bool,
+    pub http_status_addr: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            id: None,
+            address: "localhost:5000".to_owned(),
+            login: "SuperAdmin".to_owned(),
+            password: "SuperAdmin".to_owned(),
+            admins: Vec::new(),
+            roles: HashMap::new(),
+            command_debounce_ms: 2000,
+            search: MapSearch::default(),
+            history_size: 50,
+            no_repeat_window: None,
+            author_cooldown: 0,
+            max_retries_per_map: 20,
+            metadata_cache_size: 500,
+            metadata_ttl: Duration::from_secs(60 * 60),
+            time_budget: None,
+            time_limit: None,
+            forward_callbacks: None,
+            stdin_control: false,
+            forced_mods: None,
+            dislike_threshold: 3,
+            blocklist_path: "blocklist.json".into(),
+            history_path: None,
+            tag_decay: TagDecay::default(),
+            tag_decay_path: None,
+            catalog_path: "catalog.json".into(),
+            match_settings_path: None,
+            maps_subdir: None,
+            fallback_maps: Vec::new(),
+            insert_mode: InsertMode::Next,
+            on_duplicate: DuplicatePolicy::Skip,
+            reconcile_on_start: false,
+            deferred_insert: false,
+            skip_warmup_downloads: false,
+            download_cache: None,
+            download_thumbnails: false,
+            download_replays: false,
+            max_maps_dir_bytes: None,
+            offline: false,
+            server_timezone: ServerTimezone::default(),
+            shutdown_timeout: Duration::from_secs(5),
+            queue_ttl: Duration::from_secs(30),
+            keepalive: None,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+            tcp_nodelay: true,
+            ignored_callbacks: Vec::new(),
+            startup_grace: None,
+            max_calls_per_sec: 50.0,
+            max_in_flight_calls: 8,
+            slow_call_ms: 500,
+            retryable_faults: DEFAULT_RETRYABLE_FAULTS
+                .iter()
+                .map(|&m| m.to_owned())
+                .collect(),
+            bulk_jitter_ms: (500, 2000),
+            seed_on_start: false,
+            min_maps: 10,
+            prefetch_pool_size: 0,
+            prefetch_refill_below: None,
+            black_list_file: None,
+            announce_next_map: None,
+            map_vote: None,
+            call_vote_timeout: None,
+            min_vote_players: 0,
+            session_summary: None,
+            switch_countdown: None,
+            display: DisplaySettings::default(),
+            restricted: false,
+            safe_mode: true,
+            allowed_methods: DEFAULT_ALLOWED_METHODS
+                .iter()
+                .map(|&m| m.to_owned())
+                .collect(),
+            ladder_difficulties: HashMap::new(),
+            environments: None,
+            log_file: None,
+            protocol_trace: None,
+            log_max_bytes: 10_000_000,
+            log_keep: 5,
+            debug_log: false,
+            enable_http_status: false,
+            http_status_addr: "127.0.0.1:8080".to_owned(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Servers {
+    servers: Vec<Config>,
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> Result<Config, ConfigError> {
+        let text = fs::read_to_string(path)?;
+        Ok(toml::from_str(&text)?)
+    }
+
+    /// every server in the file, which is either a single config or a list of
+    /// them under `[[servers]]`
+    pub fn load_all(path: impl AsRef<Path>) -> Result<Vec<Config>, ConfigError> {
+        let text = fs::read_to_string(path)?;
+        let table: toml::Table = toml::from_str(&text)?;
+        if table.contains_key("servers") {
+            Ok(toml::from_str::<Servers>(&text)?.servers)
+        } else {
+            Ok(vec![toml::from_str(&text)?])
+        }
+    }
+
+    /// the server with this id from [`Config::load_all`]
+    pub fn load_server(path: impl AsRef<Path>, id: Option<&str>) -> Result<Config, ConfigError> {
+        let mut all = Config::load_all(path)?;
+        if all.len() == 1 {
+            return Ok(all.remove(0));
+        }
+        all.into_iter()
+            .find(|c| c.id.as_deref() == id)
+            .ok_or_else(|| ConfigError::MissingServer(id.unwrap_or_default().to_owned()))
+    }
+
+    /// settings that parse but cannot work, as a sentence each
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !self.address.contains(':') {
+            problems.push(format!("address {:?} has no port", self.address));
+        }
+        if self.search.map_type.is_empty() {
+            problems.push("search.map_type is empty".to_owned());
+        }
+        if self.max_calls_per_sec < 0.0 {
+            problems.push("max_calls_per_sec is negative".to_owned());
+        }
+        if !(0.0..=1.0).contains(&self.tag_decay.rate) {
+            problems.push("tag_decay.rate is not between 0 and 1".to_owned());
+        }
+        if !(0.0..=1.0).contains(&self.tag_decay.floor) {
+            problems.push("tag_decay.floor is not between 0 and 1".to_owned());
+        }
+        if self.bulk_jitter_ms.0 > self.bulk_jitter_ms.1 {
+            problems.push("bulk_jitter_ms starts above where it ends".to_owned());
+        }
+        if self.max_frame_len == 0 {
+            problems.push("max_frame_len is 0, no response would fit".to_owned());
+        }
+        if self.offline && self.download_cache.is_none() {
+            problems.push("offline needs a download_cache".to_owned());
+        }
+        if self.enable_http_status && !self.http_status_addr.contains(':') {
+            problems.push(format!(
+                "http_status_addr {:?} has no port",
+                self.http_status_addr
+            ));
+        }
+        problems
+    }
+
+    /// the configured features that do nothing without callbacks, besides
+    /// the rotation and chat commands
+    pub fn callback_features(&self) -> Vec<&'static str> {
+        let mut features = Vec::new();
+        if self.forward_callbacks.is_some() {
+            features.push("forward_callbacks");
+        }
+        if self.time_limit.is_some() {
+            features.push("time_limit_secs");
+        }
+        if self.deferred_insert {
+            features.push("deferred_insert");
+        }
+        if self.announce_next_map.is_some() {
+            features.push("announce_next_map");
+        }
+        if self.map_vote.is_some() {
+            features.push("map_vote");
+        }
+        if !self.ladder_difficulties.is_empty() {
+            features.push("ladder_difficulties");
+        }
+        features
+    }
+
+    pub fn server_id(&self) -> &str {
+        self.id.as_deref().unwrap_or(&self.address)
+    }
+
+    /// names of the fields that differ and can only be applied by reconnecting
+    /// or restarting the controller
+    pub fn restart_required(&self, other: &Config) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.address != other.address {
+            fields.push("address");
+        }
+        if self.login != other.login {
+            fields.push("login");
+        }
+        if self.password != other.password {
+            fields.push("password");
+        }
+        // these only apply when connecting
+        if self.max_frame_len != other.max_frame_len {
+            fields.push("max_frame_len");
+        }
+        if self.tcp_nodelay != other.tcp_nodelay {
+            fields.push("tcp_nodelay");
+        }
+        // the storage is only set up when the client is created
+        if self.blocklist_path != other.blocklist_path {
+            fields.push("blocklist_path");
+        }
+        if self.history_path != other.history_path {
+            fields.push("history_path");
+        }
+        if self.tag_decay_path != other.tag_decay_path {
+            fields.push("tag_decay_path");
+        }
+        if self.catalog_path != other.catalog_path {
+            fields.push("catalog_path");
+        }
+        if self.match_settings_path != other.match_settings_path {
+            fields.push("match_settings_path");
+        }
+        // so is the map source
+        if self.offline != other.offline {
+            fields.push("offline");
+        }
+        if self.download_cache != other.download_cache {
+            fields.push("download_cache");
+        }
+        // these are read once when the controller starts
+        if self.id != other.id {
+            fields.push("id");
+        }
+        if self.stdin_control != other.stdin_control {
+            fields.push("stdin_control");
+        }
+        if self.enable_http_status != other.enable_http_status {
+            fields.push("enable_http_status");
+        }
+        if self.http_status_addr != other.http_status_addr {
+            fields.push("http_status_addr");
+        }
+        if self.log_file != other.log_file {
+            fields.push("log_file");
+        }
+        if self.protocol_trace != other.protocol_trace {
+            fields.push("protocol_trace");
+        }
+        if self.log_max_bytes != other.log_max_bytes {
+            fields.push("log_max_bytes");
+        }
+        if self.log_keep != other.log_keep {
+            fields.push("log_keep");
+        }
+        if self.debug_log != other.debug_log {
+            fields.push("debug_log");
+        }
+        if self.shutdown_timeout != other.shutdown_timeout {
+            fields.push("shutdown_timeout");
+        }
+        fields
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    /// the file lists several servers, but not this one
+    MissingServer(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "could not read config: {err}"),
+            ConfigError::Parse(err) => write!(f, "invalid config: {err}"),
+            ConfigError::MissingServer(id) => write!(f, "no server with id {id:?} in the config"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> Self {
+        ConfigError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(err: toml::de::Error) -> Self {
+        ConfigError::Parse(err)
+    }
+}
+
+/// deserializes a [`std::time::Duration`] as a number of seconds
+pub(crate) mod secs {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer};
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<Duration, D::Error> {
+        Ok(Duration::from_secs(u64::deserialize(de)?))
+    }
+
+    pub mod option {
+        use std::time::Duration;
+
+        use serde::{Deserialize, Deserializer};
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<Option<Duration>, D::Error> {
+            Ok(Option::<u64>::deserialize(de)?.map(Duration::from_secs))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    #[test]
+    fn settings_read_at_startup_need_a_restart() {
+        let config = Config::default();
+        let other = Config {
+            protocol_trace: Some("trace.log".into()),
+            stdin_control: true,
+            history_size: config.history_size + 1,
+            ..Config::default()
+        };
+        assert_eq!(
+            config.restart_required(&other),
+            ["stdin_control", "protocol_trace"]
+        );
+        assert!(config.restart_required(&config.clone()).is_empty());
+    }
+
+    #[test]
+    fn settings_that_cannot_work_are_problems() {
+        assert!(Config::default().problems().is_empty());
+        let config = Config {
+            address: "localhost".to_owned(),
+            max_frame_len: 0,
+            bulk_jitter_ms: (10, 5),
+            offline: true,
+            download_cache: None,
+            ..Config::default()
+        };
+        assert_eq!(
+            config.problems(),
+            [
+                "address \"localhost\" has no port",
+                "bulk_jitter_ms starts above where it ends",
+                "max_frame_len is 0, no response would fit",
+                "offline needs a download_cache",
+            ]
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    io::{BufRead, Write},
+    path::PathBuf,
+    thread,
+};
+
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{client::Event, Client, ClientError};
+
+/// a command read from the control channel, e.g. `{"cmd":"queue","id":12345}`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase", deny_unknown_fields)]
+pub enum ControlCommand {
+    /// skip to the next map, see [`crate::Config::switch_countdown`]
+    Next,
+    /// call off a skip that is still counting down
+    Cancel,
+    /// keep the current map until `resume`, see [`Client::pause_rotation`]
+    Pause,
+    Resume,
+    /// download a map from tmx and play it next
+    Queue {
+        id: u64,
+    },
+    /// send a chat message to everyone
+    Chat {
+        text: String,
+    },
+    /// write the tmx ids of the rotation to `path`, see [`crate::RotationExport`]
+    #[serde(rename = "export-rotation")]
+    ExportRotation {
+        path: PathBuf,
+        match_settings: Option<PathBuf>,
+    },
+}
+
+impl Client {
+    /// reads newline-delimited json commands from stdin
+    ///
+    /// every command gets a json line as reply, either `{"ok":true}` or
+    /// `{"ok":false,"error":"..."}`, see [`Client::set_control_replies`].
+    /// commands that come in while the server is away are queued, see
+    /// [`ControlCommand::is_time_sensitive`].
+    pub fn spawn_stdin_control(&self) {
+        let events = self.events_tx();
+        thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else { return };
+                if events.send(Event::Control(line)).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+
+    pub(crate) fn handle_control(&mut self, line: &str) -> Result<(), ClientError> {
+        if self.repl {
+            return self.repl_line(line);
+        }
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        match serde_json::from_str::<ControlCommand>(line) {
+            Ok(command) => self.dispatch_control(command),
+            Err(err) => {
+                self.control_reply(json!({ "ok": false, "error": err.to_string() }));
+                Ok(())
+            }
+        }
+    }
+
+    /// where the replies to control commands are written, one json value per
+    /// line
+    ///
+    /// stdout by default, which the log shares unless
+    /// [`crate::set_log_to_stderr`] moves it away.
+    pub fn set_control_replies(&mut self, out: Box<dyn Write + Send>) {
+        self.control_replies = out;
+    }
+
+    pub(crate) fn control_reply(&mut self, reply: serde_json::Value) {
+        let res =
+            writeln!(self.control_replies, "{reply}").and_then(|()| self.control_replies.flush());
+        if let Err(err) = res {
+            say!("could not reply to a control command: {err}");
+        }
+    }
+
+    pub fn run_control(&mut self, command: ControlCommand) -> Result<(), ClientError> {
+        match command {
+            ControlCommand::Next => self.next_map()?,
+            ControlCommand::Cancel => {
+                self.cancel_map_switch()?;
+            }
+            ControlCommand::Pause => {
+                self.pause_rotation()?;
+            }
+            ControlCommand::Resume => {
+                self.resume_rotation()?;
+            }
+            ControlCommand::Queue { id } => {
+                // the map might already be in the list, in which case choosing it still works
+                self.download_map(id)?;
+                let suc: bool = self.call("ChooseNextMap", self.map_path(id).as_str())?;
+                if !suc {
+                    return Err(ClientError::Rejected("ChooseNextMap"));
+                }
+                // not necessarily one we inserted
+                self.expected_next = None;
+            }
+            ControlCommand::Chat { text } => {
+                self.call::<bool>("ChatSendServerMessage", text.as_str())?;
+            }
+            ControlCommand::ExportRotation {
+                path,
+                match_settings,
+            } => {
+                let export = self.export_rotation()?;
+                if let Err(err) = export.save(&path, match_settings.as_deref()) {
+                    say!("could not write {}: {err}", path.display());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// where a downloaded map ends up, relative to the maps directory
+pub(crate) fn map_file_name(id: u64) -> String {
+    format!("{id}.Map.Gbx")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::testing::{test_client, test_config, MockServer, Reply, SharedBuffer};
+
+    fn replies(buffer: &SharedBuffer) -> Vec<Value> {
+        buffer
+            .lines()
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn replies_go_to_their_own_writer() {
+        let server = MockServer::start(|method, _| match method {
+            "NextMap" => Some(Reply::Fault(-1000, "no next map")),
+            _ => None,
+        });
+        let mut client = test_client(test_config(&server));
+        let buffer = SharedBuffer::default();
+        client.set_control_replies(Box::new(buffer.clone()));
+
+        client
+            .handle_control(r#"{"cmd":"chat","text":"hi"}"#)
+            .unwrap();
+        client.handle_control(r#"{"cmd":"next"}"#).unwrap();
+        client.handle_control(r#"{"cmd":"dance"}"#).unwrap();
+        let replies = replies(&buffer);
+        assert_eq!(replies.len(), 3);
+        assert_eq!(replies[0], json!({ "ok": true }));
+        assert_eq!(replies[1]["ok"], json!(false));
+        assert!(replies[1]["error"]
+            .as_str()
+            .unwrap()
+            .contains("no next map"));
+        assert_eq!(replies[2]["ok"], json!(false));
+        assert_eq!(server.params_of("ChatSendServerMessage").len(), 1);
+    }
+}
@@ -0,0 +1,129 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::{Client, ClientError};
+
+/// a warning in chat before the map is switched by hand
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SwitchCountdown {
+    #[serde(rename = "duration_secs", with = "crate::config::secs")]
+    pub duration: Duration,
+    /// sent every second, with `{secs}` replaced by the seconds left
+    pub message: String,
+    /// sent when the switch is called off
+    pub cancelled_message: String,
+}
+
+impl Default for SwitchCountdown {
+    fn default() -> Self {
+        SwitchCountdown {
+            duration: Duration::from_secs(5),
+            message: "changing map in {secs}...".to_owned(),
+            cancelled_message: "the map change was called off".to_owned(),
+        }
+    }
+}
+
+/// a switch that is counting down
+#[derive(Debug, Clone)]
+pub(crate) struct PendingSwitch {
+    ends: Instant,
+    next_tick: Instant,
+}
+
+impl Client {
+    /// skips to the next map, after the countdown if there is one
+    ///
+    /// the countdown runs in the message loop, this returns right away.
+    pub fn next_map(&mut self) -> Result<(), ClientError> {
+        let Some(countdown) = self.config().switch_countdown.clone() else {
+            return self.call::<bool>("NextMap", ()).map(drop);
+        };
+        // the call is made from the message loop later, where it is trusted
+        self.check_allowed("NextMap")?;
+        if self.pending_switch.is_some() {
+            return Ok(());
+        }
+        let now = Instant::now();
+        self.pending_switch = Some(PendingSwitch {
+            ends: now + countdown.duration,
+            next_tick: now,
+        });
+        self.tick_switch()
+    }
+
+    /// stops the countdown, returns whether there was one
+    pub fn cancel_map_switch(&mut self) -> Result<bool, ClientError> {
+        if self.pending_switch.take().is_none() {
+            return Ok(false);
+        }
+        if let Some(countdown) = &self.config().switch_countdown {
+            let msg = countdown.cancelled_message.clone();
+            self.call::<bool>("ChatSendServerMessage", msg.as_str())?;
+        }
+        Ok(true)
+    }
+
+    /// the map changed or the server went away, so there is nothing to switch
+    pub(crate) fn drop_map_switch(&mut self) {
+        self.pending_switch = None;
+    }
+
+    /// how long the message loop may wait before the next second is up
+    pub(crate) fn switch_timeout(&self) -> Option<Duration> {
+        let pending = self.pending_switch.as_ref()?;
+        // like the vote, only the outermost loop counts down
+        if !self.calls.is_empty() {
+            return None;
+        }
+        Some(
+            pending
+                .next_tick
+                .min(pending.ends)
+                .saturating_duration_since(Instant::now()),
+        )
+    }
+
+    /// announces the seconds left, or switches once they are up
+    pub(crate) fn tick_switch(&mut self) -> Result<(), ClientError> {
+        let Some(pending) = &mut self.pending_switch else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        if now >= pending.ends {
+            self.pending_switch = None;
+            self.call::<bool>("NextMap", ())?;
+            return Ok(());
+        }
+        if now < pending.next_tick {
+            return Ok(());
+        }
+        let left = pending.ends - now;
+        // rounded, so a 5s countdown starts at 5 and not 4
+        let secs = (left + Duration::from_millis(500)).as_secs();
+        pending.next_tick = now + Duration::from_secs(1).min(left);
+        let Some(countdown) = &self.config().switch_countdown else {
+            return Ok(());
+        };
+        let msg = countdown.message.replace("{secs}", &secs.to_string());
+        self.call::<bool>("ChatSendServerMessage", msg.as_str())?;
+        Ok(())
+    }
+
+    /// `/next` skips to the next map, `/cancel` calls that off
+    pub(crate) fn next_command(&mut self, login: &str) -> Result<(), ClientError> {
+        if self.pending_switch.is_some() {
+            return self.chat_send_to_login(login, "the map is changing already");
+        }
+        self.next_map()
+    }
+
+    pub(crate) fn cancel_command(&mut self, login: &str) -> Result<(), ClientError> {
+        if !self.cancel_map_switch()? {
+            return self.chat_send_to_login(login, "the map is not changing");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
+use dxr::{TryFromValue, Value};
+use serde::{de, Deserialize, Deserializer};
+
+/// what timezone the dates from the server are in
+///
+/// xml-rpc's `dateTime.iso8601` has no timezone, so the server sends its own
+/// local time without saying which one that is. dedicated servers usually run
+/// in utc, if yours does not, set `server_timezone` to `"local"` (when the
+/// controller runs on the same machine) or to a fixed offset like `"+02:00"`.
+/// a fixed offset does not follow daylight saving time.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ServerTimezone {
+    #[default]
+    Utc,
+    Local,
+    Offset(FixedOffset),
+}
+
+impl ServerTimezone {
+    /// `None` for local times that do not exist, like the hour skipped when
+    /// daylight saving time starts. the hour that repeats when it ends is taken
+    /// to be the first one.
+    pub fn to_utc(self, naive: NaiveDateTime) -> Option<DateTime<Utc>> {
+        let utc = match self {
+            ServerTimezone::Utc => return Some(naive.and_utc()),
+            ServerTimezone::Local => Local.from_local_datetime(&naive).earliest()?.to_utc(),
+            ServerTimezone::Offset(offset) => {
+                offset.from_local_datetime(&naive).earliest()?.to_utc()
+            }
+        };
+        Some(utc)
+    }
+}
+
+impl FromStr for ServerTimezone {
+    type Err = InvalidTimezone;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "utc" | "UTC" => Ok(ServerTimezone::Utc),
+            "local" => Ok(ServerTimezone::Local),
+            _ => s
+                .parse()
+                .map(ServerTimezone::Offset)
+                .map_err(|_| InvalidTimezone(s.to_owned())),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ServerTimezone {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        String::deserialize(de)?.parse().map_err(de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidTimezone(String);
+
+impl fmt::Display for InvalidTimezone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} is not \"utc\", \"local\" or an offset like \"+02:00\"",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidTimezone {}
+
+/// the formats the server and mode scripts use for dates, the first one is
+/// what `dateTime.iso8601` looks like on the wire
+const FORMATS: [&str; 2] = ["%Y%m%dT%H:%M:%S", "%Y-%m-%dT%H:%M:%S"];
+
+/// reads a date from a `dateTime.iso8601` value, or from a string in the same
+/// format as some scripts send them
+///
+/// strings that carry their own offset (rfc 3339) ignore `zone`.
+pub fn tm_datetime(value: &Value, zone: ServerTimezone) -> Option<DateTime<Utc>> {
+    if let Ok(naive) = NaiveDateTime::try_from_value(value) {
+        return zone.to_utc(naive);
+    }
+    let string = String::try_from_value(value).ok()?;
+    if let Ok(date) = DateTime::parse_from_rfc3339(&string) {
+        return Some(date.to_utc());
+    }
+    FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(&string, format).ok())
+        .and_then(|naive| zone.to_utc(naive))
+}
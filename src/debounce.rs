@@ -0,0 +1,26 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// when each player last sent each chat command
+#[derive(Debug, Clone, Default)]
+pub struct CommandDebounce {
+    last: HashMap<String, HashMap<String, Instant>>,
+}
+
+impl CommandDebounce {
+    /// whether `login` did not send this exact command within `window`,
+    /// counting this one as sent either way
+    pub fn allow(&mut self, login: &str, command: &str, window: Duration) -> bool {
+        let now = Instant::now();
+        let commands = self.last.entry(login.to_owned()).or_default();
+        commands.retain(|_, sent| now.duration_since(*sent) < window);
+        commands.insert(command.to_owned(), now).is_none()
+    }
+
+    /// drops what we remember of a player that left
+    pub fn forget(&mut self, login: &str) {
+        self.last.remove(login);
+    }
+}
@@ -0,0 +1,79 @@
+use serde::Deserialize;
+
+use crate::{Client, MapSearch};
+
+/// how the tags of disliked maps are picked less for a while
+///
+/// every dislike lowers the weight of the map's tags, and every map played
+/// brings them back a bit. decayed tags make the search weight by tags, see
+/// [`MapSearch::tag_weights`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TagDecay {
+    /// the share of its weight a tag loses with every dislike of a map with
+    /// it, 0 disables it
+    pub rate: f64,
+    /// tags never weigh less than this share of their weight
+    pub floor: f64,
+    /// maps until a tag at the floor weighs as much as before
+    pub recovery_maps: u32,
+}
+
+impl Default for TagDecay {
+    fn default() -> Self {
+        TagDecay {
+            rate: 0.0,
+            floor: 0.25,
+            recovery_maps: 10,
+        }
+    }
+}
+
+impl Client {
+    /// weighs the tags of map `id` down after a dislike
+    pub(crate) fn decay_tags(&mut self, id: u64) {
+        let decay = self.config().tag_decay.clone();
+        if decay.rate <= 0.0 {
+            return;
+        }
+        let tags = match self.metadata.get(id) {
+            Some(candidate) => candidate.tags.clone(),
+            None => match self.tmx_metadata(id) {
+                Ok(candidate) => candidate.tags,
+                Err(err) => {
+                    debug!("not decaying the tags of map {id}: {err}");
+                    return;
+                }
+            },
+        };
+        for tag in tags {
+            let factor = self.tag_factors.entry(tag).or_insert(1.0);
+            *factor = (*factor * (1.0 - decay.rate)).max(decay.floor);
+        }
+        self.save_tag_factors();
+    }
+
+    /// one map closer to every tag weighing as much as before
+    pub(crate) fn recover_tags(&mut self) {
+        if self.tag_factors.is_empty() {
+            return;
+        }
+        let decay = &self.config().tag_decay;
+        let step = (1.0 - decay.floor) / decay.recovery_maps.max(1) as f64;
+        self.tag_factors.retain(|_, factor| {
+            *factor += step;
+            *factor < 1.0
+        });
+        self.save_tag_factors();
+    }
+
+    /// the search from the config, with the decayed tags weighing less
+    pub(crate) fn decayed_search(&self) -> MapSearch {
+        let mut search = self.config().search.clone();
+        for (&tag, factor) in &self.tag_factors {
+            let weight = search.tag_weights.get(&tag).copied().unwrap_or(1.0);
+            search.tag_weights.insert(tag, weight * factor);
+        }
+        search
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    storage::{read_versioned, write_versioned, StateFile},
+    Client, ClientError,
+};
+
+/// tmx ids that players disliked enough to never pick them again
+#[derive(Debug, Clone, Default)]
+pub struct Blocklist {
+    ids: HashSet<u64>,
+}
+
+impl Blocklist {
+    /// `None` if there is no file, or it had to be set aside
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Option<Blocklist>> {
+        let ids = read_versioned(path.as_ref(), StateFile::Blocklist)?;
+        Ok(ids.map(|ids| Blocklist { ids }))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut ids: Vec<u64> = self.ids.iter().copied().collect();
+        ids.sort_unstable();
+        write_versioned(path.as_ref(), &ids)
+    }
+
+    pub fn contains(&self, id: u64) -> bool {
+        self.ids.contains(&id)
+    }
+
+    /// returns whether the id was new
+    pub fn insert(&mut self, id: u64) -> bool {
+        self.ids.insert(id)
+    }
+}
+
+/// the tmx id of a map we downloaded, from its file name
+///
+/// both separators count, since the server might run on windows.
+pub fn tmx_id_of(file_name: &str) -> Option<u64> {
+    let name = file_name.rsplit(['/', '\\']).next()?;
+    name.strip_suffix(".Map.Gbx")?.parse().ok()
+}
+
+impl Client {
+    pub(crate) fn dislike_command(&mut self, login: &str) -> Result<(), ClientError> {
+        let threshold = self.config().dislike_threshold;
+        if threshold == 0 {
+            return self.chat_send_to_login(login, "dislikes are disabled");
+        }
+        let Some(map) = self.current_map.clone() else {
+            return self.chat_send_to_login(login, "no map is being played");
+        };
+        let Some(id) = tmx_id_of(&map.FileName) else {
+            return self.chat_send_to_login(login, "this map is not from tmx");
+        };
+        if !self.enough_voters() {
+            return self.chat_send_to_login(login, "not enough players to vote");
+        }
+
+        if !self.dislikes.insert(login.to_owned()) {
+            return self.chat_send_to_login(login, "you already disliked this map");
+        }
+        self.decay_tags(id);
+        if self.dislikes.len() == 1 {
+            self.dislikes_since = Some(Instant::now());
+        }
+        if self.dislikes.len() < threshold {
+            let msg = format!(
+                "{} of {threshold} dislikes for this map",
+                self.dislikes.len()
+            );
+            self.call::<bool>("ChatSendServerMessage", msg.as_str())?;
+            return Ok(());
+        }
+
+        // the players decided, not whoever typed the last dislike, so
+        // neither restricted nor safe mode stands in the way
+        self.as_trusted(|c| c.blocklist_map(id, &map.FileName))?;
+        let msg = format!(
+            "{} was disliked too much and will not be played again",
+            map.Name
+        );
+        self.call::<bool>("ChatSendServerMessage", msg.as_str())?;
+        Ok(())
+    }
+
+    /// how long the message loop may wait before the dislikes run out of time
+    pub(crate) fn dislike_timeout(&self) -> Option<Duration> {
+        let timeout = self.config().call_vote_timeout?;
+        let since = self.dislikes_since?;
+        // like the map vote, only the outermost loop ends it
+        if !self.calls.is_empty() {
+            return None;
+        }
+        Some(timeout.saturating_sub(since.elapsed()))
+    }
+
+    /// the map stays if not enough players disliked it in time
+    pub(crate) fn expire_dislikes(&mut self) -> Result<(), ClientError> {
+        self.dislikes_since = None;
+        if self.dislikes.is_empty() {
+            return Ok(());
+        }
+        self.dislikes.clear();
+        self.call::<bool>(
+            "ChatSendServerMessage",
+            "not enough dislikes in time, the map stays",
+        )?;
+        Ok(())
+    }
+
+    /// removes the map from the rotation and disk, and makes sure it is never picked again
+    pub fn blocklist_map(&mut self, id: u64, file_name: &str) -> Result<(), ClientError> {
+        if self.blocklist.insert(id) {
+            self.save_blocklist();
+        }
+
+        match self.call::<bool>("RemoveMap", file_name) {
+            Err(err) if !err.is_connection() => say!("while removing map: {err}"),
+            res => {
+                res?;
+            }
+        }
+
+        if self.is_fallback_map(file_name) {
+            say!("{file_name} is a fallback map, it stays on disk");
+            return Ok(());
+        }
+        // blocked attempts are logged already
+        if self
+            .check_safe_mode(&format!("deleting {file_name}"))
+            .is_err()
+        {
+            return Ok(());
+        }
+        let path = self.maps_dir_path(file_name)?;
+        if let Err(err) = fs::remove_file(path) {
+            say!("could not delete {file_name}: {err}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dxr::Value;
+
+    use super::tmx_id_of;
+    use crate::testing::{
+        chat_xml, map_info, temp_dir, test_client, test_config, MockServer, Reply,
+    };
+
+    #[test]
+    fn tmx_ids_are_found_behind_either_separator() {
+        assert_eq!(tmx_id_of("123.Map.Gbx"), Some(123));
+        assert_eq!(tmx_id_of("Downloaded/tmx/123.Map.Gbx"), Some(123));
+        assert_eq!(tmx_id_of("Downloaded\\tmx\\123.Map.Gbx"), Some(123));
+        assert_eq!(tmx_id_of("Downloaded\\mine.Map.Gbx"), None);
+    }
+
+    /// a map from tmx being played, with its file in the maps directory
+    fn disliked_map(restricted: bool) -> (MockServer, crate::Client, std::path::PathBuf) {
+        let dir = temp_dir("dislike");
+        std::fs::write(dir.join("123.Map.Gbx"), b"GBX").unwrap();
+        let maps_dir = format!("{}/", dir.display());
+        let server = MockServer::start(move |method, _| match method {
+            "GetMapsDirectory" => Some(Reply::of(maps_dir.as_str())),
+            _ => None,
+        });
+        let mut config = test_config(&server);
+        config.dislike_threshold = 1;
+        config.restricted = restricted;
+        assert!(config.safe_mode);
+        let mut client = test_client(config);
+        client.current_map = Some(map_info("123.Map.Gbx", "uid123"));
+        (server, client, dir)
+    }
+
+    #[test]
+    fn dislike_removes_the_map_in_safe_mode() {
+        let (server, mut client, dir) = disliked_map(false);
+        client
+            .handle_callback(&chat_xml("alice", "/dislike"), 0)
+            .unwrap();
+        assert_eq!(
+            server.params_of("RemoveMap"),
+            vec![vec![Value::string("123.Map.Gbx".to_owned())]]
+        );
+        assert!(client.blocklist.contains(123));
+        assert!(!dir.join("123.Map.Gbx").exists());
+    }
+
+    #[test]
+    fn dislike_removes_the_map_in_restricted_mode() {
+        let (server, mut client, dir) = disliked_map(true);
+        client
+            .handle_callback(&chat_xml("alice", "/dislike"), 0)
+            .unwrap();
+        assert_eq!(server.params_of("RemoveMap").len(), 1);
+        assert!(!dir.join("123.Map.Gbx").exists());
+    }
+
+    #[test]
+    fn chat_commands_still_can_not_remove_maps() {
+        let (_server, mut client, _dir) = disliked_map(false);
+        let res = client.as_untrusted(|c| c.call::<bool>("RemoveMap", "123.Map.Gbx"));
+        assert!(matches!(res, Err(crate::ClientError::SafeModeBlocked(_))));
+    }
+}
@@ -0,0 +1,75 @@
+use serde::Deserialize;
+
+use crate::{bool_setting::*, Client, ClientError, CurrentNext};
+
+/// server toggles that are set together, each one is left alone unless set
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplaySettings {
+    /// 0 leaves it to the players, 1 shows everyone, more shows only that
+    /// many of the best opponents
+    pub force_show_all_opponents: Option<i32>,
+    /// a new validation seed every map, which makes copying replays harder
+    pub use_changing_validation_seed: Option<bool>,
+    /// 0 lists the server, 1 hides it, 2 hides it from other nations
+    pub hide_server: Option<i32>,
+    pub disable_horns: Option<bool>,
+    /// the messages about incoming services, like wins on the ladder
+    pub disable_service_announces: Option<bool>,
+    /// players that go spectating keep their slot
+    pub keep_player_slots: Option<bool>,
+    pub disable_respawn: Option<bool>,
+}
+
+impl Client {
+    /// makes one call for every setting that is set
+    ///
+    /// opponents, the validation seed and respawns apply from the next map on.
+    pub fn apply_display_settings(
+        &mut self,
+        settings: &DisplaySettings,
+    ) -> Result<(), ClientError> {
+        if let Some(show) = settings.force_show_all_opponents {
+            self.set_setting("SetForceShowAllOpponents", show)?;
+        }
+        if let Some(changing) = settings.use_changing_validation_seed {
+            self.set_bool_setting(SET_USE_CHANGING_VALIDATION_SEED, changing)?;
+        }
+        if let Some(hide) = settings.hide_server {
+            self.set_setting("SetHideServer", hide)?;
+        }
+        if let Some(disable) = settings.disable_horns {
+            self.set_bool_setting(DISABLE_HORNS, disable)?;
+        }
+        if let Some(disable) = settings.disable_service_announces {
+            self.set_bool_setting(DISABLE_SERVICE_ANNOUNCES, disable)?;
+        }
+        if let Some(keep) = settings.keep_player_slots {
+            self.set_bool_setting(KEEP_PLAYER_SLOTS, keep)?;
+        }
+        if let Some(disable) = settings.disable_respawn {
+            self.set_bool_setting(SET_DISABLE_RESPAWN, disable)?;
+        }
+        Ok(())
+    }
+
+    /// every setting as the server has it, for the next map where that differs
+    pub fn get_display_settings(&mut self) -> Result<DisplaySettings, ClientError> {
+        let opponents: CurrentNext<i32> = self.call("GetForceShowAllOpponents", ())?;
+        let seed = self.get_bool_setting(GET_USE_CHANGING_VALIDATION_SEED)?;
+        let hide: i32 = self.call("GetHideServer", ())?;
+        let horns = self.get_bool_setting(GET_DISABLE_HORNS)?;
+        let announces = self.get_bool_setting(ARE_SERVICE_ANNOUNCES_DISABLED)?;
+        let slots = self.get_bool_setting(IS_KEEPING_PLAYER_SLOTS)?;
+        let respawn = self.get_bool_setting(GET_DISABLE_RESPAWN)?;
+        Ok(DisplaySettings {
+            force_show_all_opponents: Some(opponents.NextValue),
+            use_changing_validation_seed: Some(seed.NextValue),
+            hide_server: Some(hide),
+            disable_horns: Some(horns.NextValue),
+            disable_service_announces: Some(announces.NextValue),
+            keep_player_slots: Some(slots.NextValue),
+            disable_respawn: Some(respawn.NextValue),
+        })
+    }
+}
@@ -0,0 +1,136 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io,
+    path::Path,
+};
+
+/// why a map could not be downloaded, none of these break the connection
+#[derive(Debug)]
+pub enum DownloadError {
+    Http(reqwest::Error),
+    /// tmx answered, but not with a map
+    Status(reqwest::StatusCode),
+    /// the map could not be written, the maps directory might be full or
+    /// read only
+    Filesystem(io::Error),
+    /// the map is not cached and we may not ask tmx for it
+    Offline,
+    /// what tmx sent does not look like a map
+    NotAMap,
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloadError::Http(err) => write!(f, "download failed: {err}"),
+            DownloadError::Status(status) => write!(f, "download failed with {status}"),
+            DownloadError::Filesystem(err) => write!(f, "could not write map: {err}"),
+            DownloadError::Offline => write!(f, "the map is not cached, not downloading offline"),
+            DownloadError::NotAMap => write!(f, "the download is not a gbx map"),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {}
+
+impl From<io::Error> for DownloadError {
+    fn from(err: io::Error) -> Self {
+        DownloadError::Filesystem(err)
+    }
+}
+
+impl From<reqwest::Error> for DownloadError {
+    fn from(err: reqwest::Error) -> Self {
+        DownloadError::Http(err)
+    }
+}
+
+/// downloads `url` to `path`, which only ever appears complete
+///
+/// the body goes to a temporary file next to `path` first, which is removed
+/// again if anything fails on the way.
+pub fn download_file(
+    exchange: &reqwest::blocking::Client,
+    url: &str,
+    path: &Path,
+) -> Result<(), DownloadError> {
+    let part = path.with_extension(format!("{}.part", rand::random::<u32>()));
+    let res = write_body(exchange, url, &part).and_then(|()| Ok(fs::rename(&part, path)?));
+    if res.is_err() {
+        // it might not even have been created
+        let _ = fs::remove_file(&part);
+    }
+    res
+}
+
+fn write_body(
+    exchange: &reqwest::blocking::Client,
+    url: &str,
+    part: &Path,
+) -> Result<(), DownloadError> {
+    let mut res = exchange.get(url).send()?;
+    if !res.status().is_success() {
+        return Err(DownloadError::Status(res.status()));
+    }
+    let mut file = File::create(part)?;
+    res.copy_to(&mut file)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+    use crate::testing::temp_dir;
+
+    /// answers one request with `response` as it is, then hangs up
+    fn serve_once(response: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 4096]);
+            let _ = stream.write_all(response);
+        });
+        format!("http://{addr}/map")
+    }
+
+    fn leftovers(dir: &Path) -> Vec<String> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn a_cut_off_download_leaves_nothing_behind() {
+        let dir = temp_dir("download");
+        let path = dir.join("1.Map.Gbx");
+        let url = serve_once(b"HTTP/1.1 200 OK\r\ncontent-length: 100\r\n\r\nGBX");
+        let exchange = reqwest::blocking::Client::new();
+        let err = download_file(&exchange, &url, &path).unwrap_err();
+        assert!(matches!(err, DownloadError::Http(_)), "{err}");
+        assert_eq!(leftovers(&dir), Vec::<String>::new());
+    }
+
+    #[test]
+    fn an_error_status_is_not_written() {
+        let dir = temp_dir("download-status");
+        let path = dir.join("1.Map.Gbx");
+        let url = serve_once(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n");
+        let exchange = reqwest::blocking::Client::new();
+        let err = download_file(&exchange, &url, &path).unwrap_err();
+        assert!(
+            matches!(err, DownloadError::Status(reqwest::StatusCode::NOT_FOUND)),
+            "{err}"
+        );
+        assert_eq!(leftovers(&dir), Vec::<String>::new());
+    }
+}
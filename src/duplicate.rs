@@ -0,0 +1,133 @@
+use std::fs;
+
+use serde::Deserialize;
+
+use crate::{Client, ClientError, MapInfo};
+
+/// what happens when a map we downloaded is in the rotation already, under
+/// another file name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicatePolicy {
+    /// delete the new file, so another map is picked
+    #[default]
+    Skip,
+    /// take the old entry out of the rotation, the new file takes its place
+    Replace,
+    /// add it anyway, it is up to the server whether it takes it twice
+    KeepBoth,
+}
+
+impl Client {
+    /// the entry in the rotation with the same uid as `info`, but another file
+    pub(crate) fn find_in_rotation(
+        &mut self,
+        info: &MapInfo,
+    ) -> Result<Option<MapInfo>, ClientError> {
+        Ok(self
+            .get_map_list()?
+            .into_iter()
+            .find(|map| map.UId == info.UId && map.FileName != info.FileName))
+    }
+
+    /// applies `on_duplicate` to the map just downloaded to `rel_path`
+    ///
+    /// fails with [`ClientError::MapInRotation`] when the map is skipped.
+    pub(crate) fn resolve_duplicate(&mut self, rel_path: &str) -> Result<(), ClientError> {
+        let policy = self.config().on_duplicate;
+        if policy == DuplicatePolicy::KeepBoth {
+            return Ok(());
+        }
+        let info = match self.local_map_info(rel_path) {
+            Ok(info) => info,
+            Err(err) if !err.is_connection() => {
+                debug!("not checking {rel_path} for duplicates: {err}");
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
+        let Some(old) = self.find_in_rotation(&info)? else {
+            return Ok(());
+        };
+        match policy {
+            DuplicatePolicy::Skip => {
+                say!("{rel_path} is {} again, deleting it", old.FileName);
+                // fallback maps are only ever added by hand
+                if !self.is_fallback_map(rel_path) {
+                    let path = self.maps_dir_path(rel_path)?;
+                    if let Err(err) = fs::remove_file(path) {
+                        say!("could not delete {rel_path}: {err}");
+                    }
+                }
+                Err(ClientError::MapInRotation(old.FileName))
+            }
+            DuplicatePolicy::Replace => {
+                say!("{rel_path} is {} again, replacing it", old.FileName);
+                if !self.call::<bool>("RemoveMap", old.FileName.as_str())? {
+                    return Err(ClientError::Rejected("RemoveMap"));
+                }
+                Ok(())
+            }
+            DuplicatePolicy::KeepBoth => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dxr::Value;
+
+    use super::DuplicatePolicy;
+    use crate::{
+        testing::{gbx_map, map_info, temp_dir, test_client, test_config, MockServer, Reply},
+        Client, ClientError,
+    };
+
+    /// a download of the map that is in the rotation as old.Map.Gbx
+    fn downloaded_twice(policy: DuplicatePolicy) -> (MockServer, Client, std::path::PathBuf) {
+        let dir = temp_dir("duplicate");
+        std::fs::write(dir.join("7.Map.Gbx"), gbx_map("same", "again")).unwrap();
+        std::fs::write(dir.join("8.Map.Gbx"), gbx_map("other", "new")).unwrap();
+        let maps_dir = dir.display().to_string();
+        let server = MockServer::start(move |method, _| match method {
+            "GetMapsDirectory" => Some(Reply::of(maps_dir.as_str())),
+            "GetMapList" => Some(Reply::of(vec![map_info("old.Map.Gbx", "same")])),
+            _ => None,
+        });
+        let mut config = test_config(&server);
+        config.on_duplicate = policy;
+        (server, test_client(config), dir)
+    }
+
+    #[test]
+    fn skipped_duplicates_are_deleted() {
+        let (server, mut client, dir) = downloaded_twice(DuplicatePolicy::Skip);
+        let err = client.resolve_duplicate("7.Map.Gbx").unwrap_err();
+        assert!(
+            matches!(&err, ClientError::MapInRotation(old) if old == "old.Map.Gbx"),
+            "{err}"
+        );
+        assert!(!dir.join("7.Map.Gbx").exists());
+        // other maps are fine
+        client.resolve_duplicate("8.Map.Gbx").unwrap();
+        assert!(dir.join("8.Map.Gbx").exists());
+        assert!(server.params_of("RemoveMap").is_empty());
+    }
+
+    #[test]
+    fn replaced_duplicates_take_the_old_entry_out() {
+        let (server, mut client, dir) = downloaded_twice(DuplicatePolicy::Replace);
+        client.resolve_duplicate("7.Map.Gbx").unwrap();
+        assert!(dir.join("7.Map.Gbx").exists());
+        let removed = server.params_of("RemoveMap");
+        assert_eq!(removed, [vec![Value::string("old.Map.Gbx".to_owned())]]);
+    }
+
+    #[test]
+    fn kept_duplicates_are_left_alone() {
+        let (server, mut client, dir) = downloaded_twice(DuplicatePolicy::KeepBoth);
+        client.resolve_duplicate("7.Map.Gbx").unwrap();
+        assert!(dir.join("7.Map.Gbx").exists());
+        assert!(server.params_of("GetMapList").is_empty());
+    }
+}
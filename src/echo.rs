@@ -0,0 +1,129 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use dxr::{MethodCall, TryFromParams, Value};
+
+use crate::{callback::callback_names, Client, ClientError};
+
+/// what the server sends back for `Echo`
+const ECHO_CALLBACK: &str = "ManiaPlanet.Echo";
+/// how long `--health-check` waits for the echo to come back
+const ECHO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// an echo we sent, until its callback is back
+#[derive(Debug, Clone)]
+pub(crate) struct PendingEcho {
+    /// the response to the call itself, `None` once it was read
+    handle: Option<u32>,
+    /// what the callback has to carry to be ours
+    token: String,
+    sent: Instant,
+}
+
+impl Client {
+    /// makes the server send a `ManiaPlanet.Echo` callback with both strings
+    ///
+    /// the callback only arrives if callbacks are enabled.
+    pub fn echo(&mut self, public: &str, private: &str) -> Result<(), ClientError> {
+        if !self.call::<bool>("Echo", (public, private))? {
+            return Err(ClientError::Rejected("Echo"));
+        }
+        Ok(())
+    }
+
+    /// sends an echo from the keep-alive, without waiting for anything
+    pub(crate) fn send_echo(&mut self) -> Result<(), ClientError> {
+        let token = echo_token();
+        let handle = self.send_call(
+            "Echo",
+            vec![
+                Value::string("controller".to_owned()),
+                Value::string(token.clone()),
+            ],
+        )?;
+        self.echo = Some(PendingEcho {
+            handle: Some(handle),
+            token,
+            sent: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// takes the response to an echo or its callback, returns whether the
+    /// frame was one of them
+    pub(crate) fn echo_frame(&mut self, handle: u32, msg: &str, received: Instant) -> bool {
+        let Some(pending) = &mut self.echo else {
+            return false;
+        };
+        if pending.handle == Some(handle) {
+            pending.handle = None;
+            return true;
+        }
+        if callback_names(msg) != Some((ECHO_CALLBACK, None)) {
+            return false;
+        }
+        let Ok(call) = dxr::deserialize_xml::<MethodCall>(msg) else {
+            return false;
+        };
+        // the server does not keep them in the order they were sent
+        let ours = <(String, String)>::try_from_params(&call.params())
+            .is_ok_and(|(a, b)| a == pending.token || b == pending.token);
+        if !ours {
+            return false;
+        }
+        self.stats.echo_rtt = Some(received.saturating_duration_since(pending.sent));
+        self.echo = None;
+        true
+    }
+
+    /// sends an echo and waits for the callback, for `--health-check`
+    ///
+    /// a fault on `EnableCallbacks` means the server does not send them at
+    /// all, which is not checked.
+    pub(crate) fn check_callbacks(&mut self) -> Result<(), ClientError> {
+        match self.call::<bool>("EnableCallbacks", [true]) {
+            Ok(true) => {}
+            Ok(false) | Err(ClientError::Fault(_)) => return Ok(()),
+            Err(err) => return Err(err),
+        }
+        // nothing but the echo is looked at while we wait
+        let closing = std::mem::replace(&mut self.closing, true);
+        let res = self.await_echo();
+        self.closing = closing;
+        res
+    }
+
+    fn await_echo(&mut self) -> Result<(), ClientError> {
+        let token = echo_token();
+        let sent = Instant::now();
+        self.echo = Some(PendingEcho {
+            handle: None,
+            token: token.clone(),
+            sent,
+        });
+        self.echo("controller", &token)?;
+        let deadline = sent + ECHO_TIMEOUT;
+        while self.echo.is_some() {
+            if Instant::now() >= deadline {
+                self.echo = None;
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "the echo callback did not arrive, callbacks have stopped",
+                )
+                .into());
+            }
+            // callbacks do not end the wait, so look again every now and then
+            let step = Instant::now() + Duration::from_millis(50);
+            self.await_messages_until(Some(step.min(deadline)))?;
+        }
+        debug!("the echo came back after {:?}", sent.elapsed());
+        Ok(())
+    }
+}
+
+/// tells our echo apart from one sent by anybody else on this connection
+fn echo_token() -> String {
+    format!("echo-{:08x}", rand::random::<u32>())
+}
@@ -0,0 +1,81 @@
+use dxr::TryFromValue;
+
+use crate::{Client, ClientError, MapCandidate};
+
+/// the environments tmx knows, with the title ids that load only them
+const TITLE_ENVIRONMENTS: &[(&str, &str)] = &[
+    ("Stadium", "TMStadium"),
+    ("Canyon", "TMCanyon"),
+    ("Valley", "TMValley"),
+    ("Lagoon", "TMLagoon"),
+];
+
+/// what `GetVersion` says about the server
+#[allow(non_snake_case)]
+#[derive(TryFromValue, Debug, Clone, PartialEq)]
+pub struct ServerVersion {
+    pub Name: String,
+    /// like "Trackmania" or "TMCanyon@nadeo"
+    pub TitleId: String,
+    pub Version: String,
+    pub Build: String,
+}
+
+/// the environments a server running `title_id` can load
+///
+/// `None` for titles that load several, or that we do not know.
+pub fn title_environments(title_id: &str) -> Option<Vec<String>> {
+    // the 2020 game only has the stadium
+    if title_id == "Trackmania" {
+        return Some(vec!["Stadium".to_owned()]);
+    }
+    let title = title_id.split('@').next().unwrap_or_default();
+    TITLE_ENVIRONMENTS
+        .iter()
+        .find(|(_, prefix)| title.eq_ignore_ascii_case(prefix))
+        .map(|(environment, _)| vec![environment.to_string()])
+}
+
+impl Client {
+    pub fn get_version(&mut self) -> Result<ServerVersion, ClientError> {
+        self.call("GetVersion", ())
+    }
+
+    /// remembers the environments for [`Client::fits_environment`]
+    ///
+    /// `environments` from the config wins, otherwise they follow from the
+    /// title the server runs.
+    pub(crate) fn refresh_environments(&mut self) -> Result<(), ClientError> {
+        self.environments = match self.config().environments.clone() {
+            Some(environments) if environments.is_empty() => None,
+            Some(environments) => Some(environments),
+            None => {
+                let title = self.get_version()?.TitleId;
+                let environments = title_environments(&title);
+                match &environments {
+                    Some(environments) => {
+                        debug!("{title} only loads {} maps", environments.join(", "))
+                    }
+                    None => debug!("not limiting the environment for {title}"),
+                }
+                environments
+            }
+        };
+        Ok(())
+    }
+
+    /// whether the server can load the map, maps of unknown environments
+    /// always pass
+    pub(crate) fn fits_environment(&self, candidate: &MapCandidate) -> bool {
+        let (Some(allowed), Some(environment)) = (&self.environments, &candidate.environment)
+        else {
+            return true;
+        };
+        allowed.iter().any(|a| a.eq_ignore_ascii_case(environment))
+    }
+
+    /// whether maps are checked against an environment at all
+    pub(crate) fn limits_environment(&self) -> bool {
+        self.environments.is_some()
+    }
+}
@@ -0,0 +1,122 @@
+use std::{fmt, io};
+
+use dxr::{DxrError, Fault};
+
+use crate::{DownloadError, GameMode};
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// the connection broke, reconnecting might fix it
+    Io(io::Error),
+    /// the server is not speaking the protocol we expect
+    Handshake(String),
+    /// whatever is on the port is not a gbxremote server at all, with what it
+    /// sent instead
+    NotGbxRemote(String),
+    /// the server refused one of the setup calls
+    Rejected(&'static str),
+    /// the server did not accept the login and password, with its reason
+    Auth(String),
+    Fault(Fault),
+    /// a map could not be fetched from tmx, some other map might work
+    Download(DownloadError),
+    /// restricted mode does not allow this method, nothing was sent
+    Forbidden(String),
+    /// safe mode does not allow this until it is armed, nothing was done
+    SafeModeBlocked(String),
+    /// the server does not know a map with this file name
+    MapNotFound(String),
+    /// the path would leave the maps directory
+    OutsideMapsDirectory(String),
+    /// the map is in the map list already
+    MapInRotation(String),
+    /// there is no picture of map with this tmx id anywhere
+    NoImage(u64),
+    /// nobody with this login is connected
+    PlayerNotFound(String),
+    /// the call only works in script modes, with the mode the server runs
+    NotScriptMode(GameMode),
+    /// we cannot write to the maps directory, so downloads would fail
+    MapsDirectory {
+        dir: String,
+        err: io::Error,
+    },
+    /// the params could not be turned into a method call
+    Encode(String),
+    /// the server sent something that is not valid xml-rpc
+    Decode(String),
+    /// the response was valid, but not of the type we expected
+    Type {
+        method: String,
+        err: DxrError,
+    },
+}
+
+impl ClientError {
+    /// whether the supervisor should reconnect instead of giving up
+    pub fn is_connection(&self) -> bool {
+        matches!(self, ClientError::Io(_))
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Io(err) => write!(f, "connection error: {err}"),
+            ClientError::Handshake(hello) => write!(f, "unexpected hello: {hello:?}"),
+            ClientError::NotGbxRemote(what) => write!(
+                f,
+                "this does not look like a gbxremote xml-rpc port, {what}, check the port number"
+            ),
+            ClientError::Rejected(method) => write!(f, "server rejected {method}"),
+            ClientError::Auth(reason) => write!(f, "could not log in: {reason}"),
+            ClientError::Fault(fault) => {
+                write!(f, "fault {}: {}", fault.code(), fault.string())
+            }
+            ClientError::Download(err) => err.fmt(f),
+            ClientError::Forbidden(method) => write!(f, "{method} is not allowed"),
+            ClientError::SafeModeBlocked(what) => {
+                write!(f, "safe mode does not allow {what}, arm it first")
+            }
+            ClientError::MapNotFound(file) => write!(f, "no map {file:?} on the server"),
+            ClientError::OutsideMapsDirectory(path) => {
+                write!(f, "{path:?} is not inside the maps directory")
+            }
+            ClientError::MapInRotation(file) => write!(f, "{file:?} is in the map list already"),
+            ClientError::NoImage(id) => write!(f, "no image of map {id}"),
+            ClientError::PlayerNotFound(login) => write!(f, "{login} is not connected"),
+            ClientError::NotScriptMode(mode) => {
+                write!(f, "the server is not running a script mode but {mode}")
+            }
+            ClientError::MapsDirectory { dir, err } => write!(
+                f,
+                "cannot write to the maps directory {dir}: {err}, the controller has to run on the same host as the server"
+            ),
+            ClientError::Encode(err) => write!(f, "could not encode call: {err}"),
+            ClientError::Decode(err) => write!(f, "could not decode response: {err}"),
+            ClientError::Type { method, err } => {
+                write!(f, "unexpected response to {method}: {err}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<io::Error> for ClientError {
+    fn from(err: io::Error) -> Self {
+        ClientError::Io(err)
+    }
+}
+
+impl From<DownloadError> for ClientError {
+    fn from(err: DownloadError) -> Self {
+        ClientError::Download(err)
+    }
+}
+
+impl From<Fault> for ClientError {
+    fn from(fault: Fault) -> Self {
+        ClientError::Fault(fault)
+    }
+}
@@ -0,0 +1,153 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{tmx_id_of, Client, ClientError};
+
+/// a map file we downloaded, as found in the maps directory
+pub(crate) struct Download {
+    pub(crate) id: u64,
+    pub(crate) path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// the `<tmx id>.Map.Gbx` files in `dir`, other files are not ours to delete
+pub(crate) fn downloads(dir: &Path) -> io::Result<Vec<Download>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let Some(id) = tmx_id_of(&entry.file_name().to_string_lossy()) else {
+            continue;
+        };
+        let meta = entry.metadata()?;
+        if meta.is_file() {
+            files.push(Download {
+                id,
+                path: entry.path(),
+                size: meta.len(),
+                modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            });
+        }
+    }
+    Ok(files)
+}
+
+impl Client {
+    /// deletes the least recently played downloads in `dir` until they fit in
+    /// `max_maps_dir_bytes`
+    ///
+    /// maps that were not played recently go first, oldest file first, then
+    /// those in the history from its oldest end. maps in the rotation, staged
+    /// and prefetched maps, fallback maps and `keep` are never deleted, and
+    /// neither are the files the catalog has as local or external. deleted
+    /// maps leave the catalog.
+    pub(crate) fn evict_maps(&mut self, dir: &Path, keep: u64) -> Result<(), ClientError> {
+        let Some(cap) = self.config().max_maps_dir_bytes else {
+            return Ok(());
+        };
+        let mut files = match downloads(dir) {
+            Ok(files) => files,
+            Err(err) => {
+                say!("could not look at the size of {}: {err}", dir.display());
+                return Ok(());
+            }
+        };
+        let mut total: u64 = files.iter().map(|file| file.size).sum();
+        if total <= cap {
+            return Ok(());
+        }
+
+        let mut protected: HashSet<u64> = self
+            .get_map_list()?
+            .iter()
+            .filter_map(|map| tmx_id_of(&map.FileName))
+            .collect();
+        protected.insert(keep);
+        protected.extend(self.staged.iter().map(|candidate| candidate.id));
+        protected.extend(self.prefetch.ids());
+
+        let played: HashMap<u64, usize> = self
+            .history
+            .iter()
+            .enumerate()
+            .map(|(pos, id)| (id, pos))
+            .collect();
+        files.sort_by_key(|file| (played.get(&file.id).copied(), file.modified));
+
+        let mut evicted = false;
+        for file in files {
+            if total <= cap {
+                break;
+            }
+            if protected.contains(&file.id) || self.is_fallback_path(&file.path) {
+                continue;
+            }
+            let rel_path = self.map_path(file.id);
+            let ours = self
+                .catalog
+                .get(&rel_path)
+                .is_none_or(|entry| entry.tmx_id() == Some(file.id));
+            if !ours {
+                continue;
+            }
+            if let Err(err) = fs::remove_file(&file.path) {
+                say!("could not delete {}: {err}", file.path.display());
+                continue;
+            }
+            // it might have none
+            let _ = fs::remove_file(file.path.with_file_name(format!("{}.jpg", file.id)));
+            self.metadata.remove(file.id);
+            evicted |= self.catalog.remove(&rel_path).is_some();
+            total -= file.size;
+            say!("deleted map {} to stay below max_maps_dir_bytes", file.id);
+        }
+        if evicted {
+            self.save_catalog();
+        }
+        if total > cap {
+            say!("the downloaded maps take {total} bytes, but every one left is in the rotation");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        testing::{map_info, temp_dir, test_client, test_config, MockServer, Reply},
+        CatalogEntry, MapOrigin,
+    };
+
+    #[test]
+    fn evicted_maps_leave_the_catalog() {
+        let dir = temp_dir("evict");
+        for id in 1..=3 {
+            std::fs::write(dir.join(format!("{id}.Map.Gbx")), [0; 10]).unwrap();
+        }
+        let server = MockServer::start(|method, _| match method {
+            "GetMapList" => Some(Reply::of(vec![map_info("3.Map.Gbx", "c")])),
+            _ => None,
+        });
+        let mut config = test_config(&server);
+        config.max_maps_dir_bytes = Some(15);
+        let mut client = test_client(config);
+        // put there by hand, under a name that looks like a download
+        client
+            .catalog
+            .insert("1.Map.Gbx", CatalogEntry::new(MapOrigin::Local));
+        client
+            .catalog
+            .insert("2.Map.Gbx", CatalogEntry::new(MapOrigin::Tmx { id: 2 }));
+
+        client.evict_maps(&dir, 3).unwrap();
+        assert!(dir.join("1.Map.Gbx").exists());
+        assert!(!dir.join("2.Map.Gbx").exists());
+        assert!(dir.join("3.Map.Gbx").exists());
+        assert!(client.catalog.get("1.Map.Gbx").is_some());
+        assert!(client.catalog.get("2.Map.Gbx").is_none());
+    }
+}
@@ -0,0 +1,185 @@
+use std::{fmt::Write, fs, io, path::Path};
+
+use crate::{
+    manialink::escape_xml, maps::download_path, tmx_id_of, Catalog, Client, ClientError, MapInfo,
+    MapOrigin,
+};
+
+/// the maps of a rotation, as far as they can be downloaded again
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RotationExport {
+    /// tmx ids, in the order of the map list
+    pub ids: Vec<u64>,
+    /// file names of maps that were not downloaded by us, like the ones that
+    /// were added by hand
+    pub unknown: Vec<String>,
+    /// file names of maps added with [`Client::add_local_map`]
+    pub local: Vec<String>,
+    /// where the maps go on the other server, see `maps_subdir`
+    pub maps_subdir: Option<String>,
+}
+
+impl RotationExport {
+    /// maps the catalog does not know are ours if their file name is a tmx id,
+    /// like the ones downloaded before there was a catalog
+    pub fn new(maps: &[MapInfo], catalog: &Catalog) -> Self {
+        let mut export = RotationExport::default();
+        for map in maps {
+            let origin = match catalog.get(&map.FileName) {
+                Some(entry) => Some(entry.origin),
+                None => tmx_id_of(&map.FileName).map(|id| MapOrigin::Tmx { id }),
+            };
+            match origin {
+                Some(MapOrigin::Tmx { id }) => export.ids.push(id),
+                Some(MapOrigin::Local) => export.local.push(map.FileName.clone()),
+                Some(MapOrigin::External) | None => export.unknown.push(map.FileName.clone()),
+            }
+        }
+        export
+    }
+
+    /// one tmx id per line, the unknown maps are listed as comments
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for id in &self.ids {
+            let _ = writeln!(text, "{id}");
+        }
+        for file_name in &self.unknown {
+            let _ = writeln!(text, "# unknown origin: {file_name}");
+        }
+        for file_name in &self.local {
+            let _ = writeln!(text, "# origin local: {file_name}");
+        }
+        text
+    }
+
+    /// a match settings file with just the map list, for the maps once they
+    /// are downloaded to the same file names
+    pub fn to_match_settings(&self) -> String {
+        let subdir = self.maps_subdir.as_deref();
+        let files: Vec<String> = self
+            .ids
+            .iter()
+            .map(|&id| download_path(subdir, id))
+            .collect();
+        match_settings_xml(files.iter().map(String::as_str))
+    }
+
+    /// writes the list of ids to `path`, and the match settings if asked for
+    pub fn save(&self, path: &Path, match_settings: Option<&Path>) -> io::Result<()> {
+        fs::write(path, self.to_text())?;
+        if let Some(match_settings) = match_settings {
+            fs::write(match_settings, self.to_match_settings())?;
+        }
+        Ok(())
+    }
+}
+
+/// a match settings file with just a map list, the files are relative to the
+/// maps directory
+pub(crate) fn match_settings_xml<'a>(files: impl IntoIterator<Item = &'a str>) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\" ?>\n<playlist>\n");
+    xml.push_str("\t<startindex>0</startindex>\n");
+    for file in files {
+        let _ = writeln!(xml, "\t<map><file>{}</file></map>", escape_xml(file));
+    }
+    xml.push_str("</playlist>\n");
+    xml
+}
+
+impl Client {
+    /// every map in the rotation, in order
+    pub fn get_map_list(&mut self) -> Result<Vec<MapInfo>, ClientError> {
+        self.call_paged("GetMapList")
+    }
+
+    pub fn export_rotation(&mut self) -> Result<RotationExport, ClientError> {
+        let mut export = RotationExport::new(&self.get_map_list()?, &self.catalog);
+        export.maps_subdir = self.config().maps_subdir.clone();
+        for file_name in &export.unknown {
+            say!("{file_name} is of unknown origin, it is not exported");
+        }
+        Ok(export)
+    }
+
+    /// `/export-rotation <file> [match settings file]`, both are written to
+    /// the directory the controller runs in
+    pub(crate) fn export_rotation_command(
+        &mut self,
+        login: &str,
+        args: &[&str],
+    ) -> Result<(), ClientError> {
+        let (path, match_settings) = match args {
+            [path] => (*path, None),
+            [path, match_settings] => (*path, Some(*match_settings)),
+            _ => {
+                return self.chat_send_to_login(
+                    login,
+                    "usage: /export-rotation <file> [match settings file]",
+                );
+            }
+        };
+        // chat should not get to write anywhere else
+        let is_plain = |name: &str| Path::new(name).file_name() == Some(name.as_ref());
+        if !is_plain(path) || !match_settings.is_none_or(is_plain) {
+            return self.chat_send_to_login(login, "only plain file names are allowed");
+        }
+
+        let export = self.export_rotation()?;
+        let reply = match export.save(path.as_ref(), match_settings.map(Path::new)) {
+            Err(err) => format!("could not write {path}: {err}"),
+            Ok(()) if export.unknown.is_empty() && export.local.is_empty() => {
+                format!("exported {} maps to {path}", export.ids.len())
+            }
+            Ok(()) => format!(
+                "exported {} maps to {path}, {} local ones and {} of unknown origin were left out",
+                export.ids.len(),
+                export.local.len(),
+                export.unknown.len()
+            ),
+        };
+        self.chat_send_to_login(login, &reply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RotationExport;
+    use crate::{testing::map_info, Catalog, CatalogEntry, MapOrigin};
+
+    #[test]
+    fn the_catalog_tells_where_maps_came_from() {
+        let mut catalog = Catalog::default();
+        catalog.insert(
+            "renamed.Map.Gbx",
+            CatalogEntry::new(MapOrigin::Tmx { id: 2 }),
+        );
+        catalog.insert("mine.Map.Gbx", CatalogEntry::new(MapOrigin::Local));
+        catalog.insert("3.Map.Gbx", CatalogEntry::new(MapOrigin::External));
+        let maps = [
+            map_info("Downloaded\\1.Map.Gbx", "a"),
+            map_info("renamed.Map.Gbx", "b"),
+            map_info("mine.Map.Gbx", "c"),
+            map_info("3.Map.Gbx", "d"),
+            map_info("other.Map.Gbx", "e"),
+        ];
+        let export = RotationExport::new(&maps, &catalog);
+        assert_eq!(export.ids, [1, 2]);
+        assert_eq!(export.local, ["mine.Map.Gbx"]);
+        assert_eq!(export.unknown, ["3.Map.Gbx", "other.Map.Gbx"]);
+    }
+
+    #[test]
+    fn match_settings_use_the_maps_subdir() {
+        let export = RotationExport {
+            ids: vec![1],
+            maps_subdir: Some("tmx/".to_owned()),
+            ..RotationExport::default()
+        };
+        let xml = export.to_match_settings();
+        assert!(
+            xml.contains("<map><file>tmx/1.Map.Gbx</file></map>"),
+            "{xml}"
+        );
+    }
+}
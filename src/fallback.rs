@@ -0,0 +1,65 @@
+use std::path::Path;
+
+use crate::{local::is_inside, Client, ClientError};
+
+/// map file names as the server writes them, with either separator
+fn same_file(a: &str, b: &str) -> bool {
+    a.replace('\\', "/") == b.replace('\\', "/")
+}
+
+impl Client {
+    /// whether `file_name` is one of the fallback maps, which are never deleted
+    pub(crate) fn is_fallback_map(&self, file_name: &str) -> bool {
+        self.config()
+            .fallback_maps
+            .iter()
+            .any(|fallback| same_file(fallback, file_name))
+    }
+
+    /// whether `path` in the maps directory is one of the fallback maps
+    pub(crate) fn is_fallback_path(&self, path: &Path) -> bool {
+        self.config()
+            .fallback_maps
+            .iter()
+            .any(|fallback| path.ends_with(fallback.replace('\\', "/")))
+    }
+
+    /// makes sure the fallback maps are files in the maps directory
+    pub(crate) fn check_fallback_maps(&mut self) -> Result<(), ClientError> {
+        for fallback in self.config().fallback_maps.clone() {
+            if !is_inside(&fallback) {
+                return Err(ClientError::OutsideMapsDirectory(fallback));
+            }
+            if !self.maps_dir_path(&fallback)?.is_file() {
+                return Err(ClientError::MapNotFound(fallback));
+            }
+        }
+        Ok(())
+    }
+
+    /// inserts a fallback map unless one is in the rotation already, for when
+    /// no new map could be downloaded
+    ///
+    /// the next map that downloads fine is inserted as usual, the fallback
+    /// just stays as one map of the rotation.
+    pub(crate) fn keep_fallback_map(&mut self) -> Result<(), ClientError> {
+        let Some(fallback) = self.config().fallback_maps.first().cloned() else {
+            return Ok(());
+        };
+        let maps = match self.get_map_list() {
+            Ok(maps) => maps,
+            Err(err) if !err.is_connection() => {
+                self.report_error("listing the maps", err);
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
+        if maps.iter().any(|map| self.is_fallback_map(&map.FileName)) {
+            return Ok(());
+        }
+        if self.add_map(&fallback, self.config().insert_mode)? {
+            say!("no new map, added the fallback {fallback}");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+/// `text` without the `$` codes trackmania formats names and chat with
+///
+/// colors like `$f00`, styles like `$o` or `$z` and links like
+/// `$l[https://example.com]` are dropped, `$$` becomes a plain `$`.
+pub fn strip_tm_formatting(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            plain.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('$') => plain.push('$'),
+            // colors have up to three hex digits, the game pads short ones
+            Some(c) if c.is_ascii_hexdigit() => {
+                for _ in 0..2 {
+                    chars.next_if(char::is_ascii_hexdigit);
+                }
+            }
+            Some('l' | 'L' | 'h' | 'H' | 'p' | 'P') => {
+                if chars.next_if_eq(&'[').is_some() {
+                    for c in chars.by_ref() {
+                        if c == ']' {
+                            break;
+                        }
+                    }
+                }
+            }
+            // any other code is a single letter
+            Some(_) | None => {}
+        }
+    }
+    plain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::strip_tm_formatting;
+
+    #[test]
+    fn codes_are_dropped() {
+        assert_eq!(
+            strip_tm_formatting("$f00Red $oBold$z plain"),
+            "Red Bold plain"
+        );
+        assert_eq!(strip_tm_formatting("$fShort$12Padded"), "ShortPadded");
+        assert_eq!(strip_tm_formatting("$l[https://example.com]link$l"), "link");
+        assert_eq!(strip_tm_formatting("$$5 a map"), "$5 a map");
+        assert_eq!(strip_tm_formatting("ends with $"), "ends with ");
+    }
+}
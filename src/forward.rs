@@ -0,0 +1,61 @@
+use std::{io::Write, net::TcpStream};
+
+use dxr::Value;
+
+use crate::{value::value_to_json, ServerTimezone};
+
+/// sends every callback as a line of json to an external process
+///
+/// the connection is (re)made lazily, so the other side can be restarted
+/// without restarting the controller. callbacks that arrive while it is
+/// unreachable are dropped.
+pub struct CallbackForwarder {
+    addr: String,
+    stream: Option<TcpStream>,
+    /// so we only complain once until the connection works again
+    failing: bool,
+}
+
+impl CallbackForwarder {
+    pub fn new(addr: impl Into<String>) -> Self {
+        CallbackForwarder {
+            addr: addr.into(),
+            stream: None,
+            failing: false,
+        }
+    }
+
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    pub fn forward(&mut self, name: &str, params: &[Value], zone: ServerTimezone) {
+        let msg = serde_json::json!({
+            "name": name,
+            "params": params.iter().map(|p| value_to_json(p, zone)).collect::<Vec<_>>(),
+        });
+
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => match TcpStream::connect(&self.addr) {
+                Ok(stream) => self.stream.insert(stream),
+                Err(err) => return self.report(err),
+            },
+        };
+
+        match writeln!(stream, "{msg}") {
+            Ok(()) => self.failing = false,
+            Err(err) => {
+                self.stream = None;
+                self.report(err);
+            }
+        }
+    }
+
+    fn report(&mut self, err: std::io::Error) {
+        if !self.failing {
+            say!("could not forward callbacks to {}: {err}", self.addr);
+            self.failing = true;
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::fmt;
+
+/// frames longer than this are refused unless told otherwise, the longest
+/// real responses are map lists of a few megabytes
+pub const DEFAULT_MAX_FRAME_LEN: u32 = 64 * 1024 * 1024;
+
+/// a response or callback, as sent by the server
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub handle: u32,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Length,
+    Handle { len: u32 },
+    Body { len: u32, handle: u32 },
+}
+
+/// splits the byte stream from the server back into frames
+///
+/// bytes can be pushed in pieces of any size, the decoder remembers whether
+/// it is in the middle of the length, handle or body of a frame. so a read
+/// that times out halfway through a frame does not desynchronize the stream,
+/// the next read just continues where the last one stopped.
+///
+/// a frame that claims to be longer than `max_len` is an error, rather than
+/// something to buffer gigabytes for. the stream cannot be trusted after it.
+#[derive(Debug, Clone)]
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+    state: State,
+    max_len: u32,
+}
+
+/// the stream does not look like frames from a gbxremote server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameError {
+    TooLarge { len: u32, max_len: u32 },
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::TooLarge { len, max_len } => write!(
+                f,
+                "the server sent a frame of {len} bytes, more than the {max_len} we allow"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::with_max_len(DEFAULT_MAX_FRAME_LEN)
+    }
+
+    pub fn with_max_len(max_len: u32) -> Self {
+        FrameDecoder {
+            buf: Vec::new(),
+            state: State::Length,
+            max_len,
+        }
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// whether we are in between frames
+    pub fn is_idle(&self) -> bool {
+        self.state == State::Length && self.buf.is_empty()
+    }
+
+    /// returns the next complete frame, if enough bytes were pushed for it
+    ///
+    /// frames can be empty, it is up to the caller what to make of them.
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, FrameError> {
+        loop {
+            match self.state {
+                State::Length => {
+                    let Some(len) = self.take_u32() else {
+                        return Ok(None);
+                    };
+                    if len > self.max_len {
+                        return Err(FrameError::TooLarge {
+                            len,
+                            max_len: self.max_len,
+                        });
+                    }
+                    self.state = State::Handle { len };
+                }
+                State::Handle { len } => {
+                    let Some(handle) = self.take_u32() else {
+                        return Ok(None);
+                    };
+                    self.state = State::Body { len, handle };
+                }
+                State::Body { len, handle } => {
+                    if self.buf.len() < len as usize {
+                        return Ok(None);
+                    }
+                    let body = self.buf.drain(..len as usize).collect();
+                    self.state = State::Length;
+                    return Ok(Some(Frame { handle, body }));
+                }
+            }
+        }
+    }
+
+    fn take_u32(&mut self) -> Option<u32> {
+        let bytes = self.buf.get(..4)?.try_into().unwrap();
+        self.buf.drain(..4);
+        Some(u32::from_le_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame_bytes(handle: u32, body: &[u8]) -> Vec<u8> {
+        let mut bytes = (body.len() as u32).to_le_bytes().to_vec();
+        bytes.extend(handle.to_le_bytes());
+        bytes.extend(body);
+        bytes
+    }
+
+    #[test]
+    fn frames_survive_being_pushed_a_byte_at_a_time() {
+        let mut decoder = FrameDecoder::new();
+        let bytes = frame_bytes(0x8000_0001, b"<methodResponse/>");
+        let (last, rest) = bytes.split_last().unwrap();
+        for byte in rest {
+            decoder.push(&[*byte]);
+            assert_eq!(decoder.next_frame(), Ok(None));
+            assert!(!decoder.is_idle());
+        }
+        decoder.push(&[*last]);
+        let frame = decoder.next_frame().unwrap().unwrap();
+        assert_eq!(frame.handle, 0x8000_0001);
+        assert_eq!(frame.body, b"<methodResponse/>");
+        assert!(decoder.is_idle());
+    }
+
+    #[test]
+    fn one_push_can_hold_several_frames() {
+        let mut decoder = FrameDecoder::new();
+        let mut bytes = frame_bytes(1, b"first");
+        bytes.extend(frame_bytes(2, b"second"));
+        // and the start of a third
+        bytes.extend(&frame_bytes(3, b"third")[..6]);
+        decoder.push(&bytes);
+        assert_eq!(decoder.next_frame().unwrap().unwrap().body, b"first");
+        assert_eq!(decoder.next_frame().unwrap().unwrap().body, b"second");
+        assert_eq!(decoder.next_frame(), Ok(None));
+        decoder.push(&frame_bytes(3, b"third")[6..]);
+        let frame = decoder.next_frame().unwrap().unwrap();
+        assert_eq!((frame.handle, frame.body.as_slice()), (3, &b"third"[..]));
+    }
+
+    #[test]
+    fn empty_frames_are_frames() {
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&frame_bytes(7, b""));
+        let frame = decoder.next_frame().unwrap().unwrap();
+        assert_eq!((frame.handle, frame.body.len()), (7, 0));
+        assert!(decoder.is_idle());
+    }
+
+    #[test]
+    fn frames_longer_than_the_limit_are_refused() {
+        let mut decoder = FrameDecoder::with_max_len(4);
+        decoder.push(&frame_bytes(1, b"four"));
+        assert_eq!(decoder.next_frame().unwrap().unwrap().body, b"four");
+        // only the length is needed to tell
+        decoder.push(&u32::MAX.to_le_bytes());
+        assert_eq!(
+            decoder.next_frame(),
+            Err(FrameError::TooLarge {
+                len: u32::MAX,
+                max_len: 4
+            })
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use std::{fmt, time::Duration};
+
+use dxr::TryFromValue;
+
+use crate::{Client, ClientError};
+
+/// the game settings of a map, as `GetCurrentGameInfo` returns them
+///
+/// times are in ms. most of these only apply to the legacy modes, script
+/// modes keep their settings in the mode script settings instead.
+#[allow(non_snake_case)]
+#[derive(TryFromValue, Debug, Clone, PartialEq)]
+pub struct GameInfo {
+    /// see [`GameInfo::game_mode`]
+    pub GameMode: i32,
+    pub ScriptName: String,
+    pub NbMaps: i32,
+    pub ChatTime: i32,
+    /// see [`GameInfo::finish_timeout`]
+    pub FinishTimeout: i32,
+    pub AllWarmUpDuration: i32,
+    pub DisableRespawn: bool,
+    pub ForceShowAllOpponents: i32,
+    pub RoundsPointsLimit: i32,
+    pub RoundsForcedLaps: i32,
+    pub TimeAttackLimit: i32,
+    pub TeamPointsLimit: i32,
+    pub TeamMaxPoints: i32,
+    pub LapsNbLaps: i32,
+    pub LapsTimeLimit: i32,
+    pub CupPointsLimit: i32,
+    pub CupRoundsPerMap: i32,
+    pub CupNbWinners: i32,
+}
+
+#[allow(non_snake_case)]
+#[derive(TryFromValue, Debug, Clone, PartialEq)]
+pub struct GameInfos {
+    pub CurrentGameInfos: GameInfo,
+    pub NextGameInfos: GameInfo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    /// a mode script decides everything, see [`GameInfo::ScriptName`]
+    Script,
+    Rounds,
+    TimeAttack,
+    Team,
+    Laps,
+    Cup,
+    Stunts,
+    Unknown(i32),
+}
+
+impl GameMode {
+    pub fn from_code(code: i32) -> Self {
+        match code {
+            0 => GameMode::Script,
+            1 => GameMode::Rounds,
+            2 => GameMode::TimeAttack,
+            3 => GameMode::Team,
+            4 => GameMode::Laps,
+            5 => GameMode::Cup,
+            6 => GameMode::Stunts,
+            code => GameMode::Unknown(code),
+        }
+    }
+}
+
+impl fmt::Display for GameMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameMode::Unknown(code) => write!(f, "unknown mode {code}"),
+            mode => write!(f, "{mode:?}"),
+        }
+    }
+}
+
+/// how long players get to finish once the first player finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinishTimeout {
+    /// 15 seconds
+    Default,
+    /// depends on the author time of the map
+    Adaptive,
+    Fixed(Duration),
+}
+
+impl GameInfo {
+    pub fn game_mode(&self) -> GameMode {
+        GameMode::from_code(self.GameMode)
+    }
+
+    pub fn finish_timeout(&self) -> FinishTimeout {
+        match self.FinishTimeout {
+            0 => FinishTimeout::Default,
+            1 => FinishTimeout::Adaptive,
+            ms => FinishTimeout::Fixed(Duration::from_millis(ms.max(0) as u64)),
+        }
+    }
+
+    /// the mode and the setting that decides when a map ends
+    pub fn summary(&self) -> String {
+        let secs = |ms: i32| ms / 1000;
+        match self.game_mode() {
+            GameMode::Script => format!("Script {}", self.ScriptName),
+            GameMode::TimeAttack => format!("TimeAttack, {}s", secs(self.TimeAttackLimit)),
+            GameMode::Rounds => format!("Rounds, {} points", self.RoundsPointsLimit),
+            GameMode::Team => format!("Team, {} points", self.TeamPointsLimit),
+            GameMode::Laps => format!("Laps, {} laps", self.LapsNbLaps),
+            GameMode::Cup => format!("Cup, {} points", self.CupPointsLimit),
+            mode => mode.to_string(),
+        }
+    }
+}
+
+impl Client {
+    pub fn get_current_game_info(&mut self) -> Result<GameInfo, ClientError> {
+        self.call("GetCurrentGameInfo", ())
+    }
+
+    /// the settings that apply from the next map on
+    pub fn get_next_game_info(&mut self) -> Result<GameInfo, ClientError> {
+        self.call("GetNextGameInfo", ())
+    }
+
+    pub fn get_game_infos(&mut self) -> Result<GameInfos, ClientError> {
+        self.call("GetGameInfos", ())
+    }
+
+    /// `/gameinfo` shows the mode, and the next one if it changes
+    pub(crate) fn gameinfo_command(&mut self, login: &str) -> Result<(), ClientError> {
+        let infos = self.get_game_infos()?;
+        let current = infos.CurrentGameInfos.summary();
+        let next = infos.NextGameInfos.summary();
+        let reply = if current == next {
+            current
+        } else {
+            format!("{current}, next map: {next}")
+        };
+        self.chat_send_to_login(login, &reply)
+    }
+}
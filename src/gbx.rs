@@ -0,0 +1,241 @@
+use std::{fs, time::Duration};
+
+use crate::{Client, ClientError, MapCandidate, MapInfo};
+
+/// the header chunk of a map with its description as xml
+const XML_CHUNK: u32 = 0x0304_3005;
+/// the header chunk of a map with the login and nickname of its author
+const AUTHOR_CHUNK: u32 = 0x0304_3008;
+
+pub(crate) struct GbxReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> GbxReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        GbxReader { bytes, pos: 0 }
+    }
+
+    pub fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    /// a length and that many bytes of utf-8
+    pub fn string(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+}
+
+/// the chunks in the header of a gbx file, by id
+///
+/// only the body is compressed, both in files flagged as compressed and in
+/// those that are not, so the header chunks are read as they are. `None` for
+/// files from before version 6, which have no chunks in the header.
+pub(crate) fn header_chunks(file: &[u8]) -> Option<Vec<(u32, &[u8])>> {
+    let mut reader = GbxReader::new(file);
+    if reader.take(3)? != b"GBX" {
+        return None;
+    }
+    let version = u16::from_le_bytes(reader.take(2)?.try_into().ok()?);
+    if version < 6 {
+        return None;
+    }
+    // the format, compression and "R" or "E" flags
+    reader.take(4)?;
+    let _class = reader.u32()?;
+    let _user_data_size = reader.u32()?;
+    let count = reader.u32()?;
+    let mut sizes = Vec::new();
+    for _ in 0..count {
+        let id = reader.u32()?;
+        // the top bit marks heavy chunks
+        let size = reader.u32()? & 0x7fff_ffff;
+        sizes.push((id, size as usize));
+    }
+    sizes
+        .into_iter()
+        .map(|(id, size)| Some((id, reader.take(size)?)))
+        .collect()
+}
+
+/// the value of `name` in the first `<element .../>` of `xml`, unescaped
+fn attribute(xml: &str, element: &str, name: &str) -> Option<String> {
+    let start = xml.find(&format!("<{element} "))?;
+    let tag = &xml[start..];
+    let tag = &tag[..tag.find('>')?];
+    let value_start = tag.find(&format!(" {name}=\""))? + name.len() + 3;
+    let value = &tag[value_start..];
+    let value = &value[..value.find('"')?];
+    Some(
+        value
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    )
+}
+
+/// what dedup and logs need from a map, read from the header of its file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GbxMapHeader {
+    pub uid: String,
+    /// with its formatting codes
+    pub name: String,
+    pub author_login: String,
+    /// `None` in maps from before the game kept it
+    pub author_nickname: Option<String>,
+    /// like "Stadium"
+    pub environment: String,
+    /// in milliseconds
+    pub author_time: Option<i32>,
+    pub gold_time: Option<i32>,
+    pub copper_price: Option<i32>,
+    pub map_type: String,
+    pub map_style: String,
+}
+
+impl GbxMapHeader {
+    /// `None` if the file is not a gbx map, or it has no xml in its header
+    pub fn parse(file: &[u8]) -> Option<GbxMapHeader> {
+        let chunks = header_chunks(file)?;
+        let chunk = |id| chunks.iter().find(|(chunk, _)| *chunk == id).map(|c| c.1);
+        let xml = GbxReader::new(chunk(XML_CHUNK)?).string()?;
+        if attribute(&xml, "header", "type").as_deref() != Some("map") {
+            return None;
+        }
+        let author_nickname = chunk(AUTHOR_CHUNK).and_then(|data| {
+            let mut reader = GbxReader::new(data);
+            let _version = reader.u32()?;
+            let _author_version = reader.u32()?;
+            let _login = reader.string()?;
+            reader.string()
+        });
+        let number = |element, name| attribute(&xml, element, name)?.parse().ok();
+        Some(GbxMapHeader {
+            uid: attribute(&xml, "ident", "uid")?,
+            name: attribute(&xml, "ident", "name").unwrap_or_default(),
+            author_login: attribute(&xml, "ident", "author").unwrap_or_default(),
+            author_nickname: author_nickname.filter(|nickname| !nickname.is_empty()),
+            environment: attribute(&xml, "desc", "envir").unwrap_or_default(),
+            author_time: number("times", "authortime"),
+            gold_time: number("times", "gold"),
+            copper_price: number("desc", "displaycost"),
+            map_type: attribute(&xml, "desc", "maptype").unwrap_or_default(),
+            map_style: attribute(&xml, "desc", "mapstyle").unwrap_or_default(),
+        })
+    }
+
+    /// like `GetMapInfo` would say, for the map at `file_name`
+    pub fn to_map_info(&self, file_name: &str) -> MapInfo {
+        MapInfo {
+            Name: self.name.clone(),
+            UId: self.uid.clone(),
+            FileName: file_name.to_owned(),
+            Environnement: self.environment.clone(),
+            Author: self.author_login.clone(),
+            AuthorNickname: self
+                .author_nickname
+                .clone()
+                .unwrap_or_else(|| self.author_login.clone()),
+            GoldTime: self.gold_time.unwrap_or(-1),
+            CopperPrice: self.copper_price.unwrap_or(0),
+            MapType: self.map_type.clone(),
+            MapStyle: self.map_style.clone(),
+        }
+    }
+
+    /// fills in what tmx would have told us about the candidate
+    pub(crate) fn describe(&self, candidate: &mut MapCandidate) {
+        let non_empty = |text: &str| (!text.is_empty()).then(|| text.to_owned());
+        candidate.name = non_empty(&self.name);
+        candidate.uid = non_empty(&self.uid);
+        candidate.author_login = non_empty(&self.author_login);
+        candidate.environment = non_empty(&self.environment);
+        candidate.author_time = self
+            .author_time
+            .and_then(|ms| u64::try_from(ms).ok())
+            .map(Duration::from_millis);
+    }
+}
+
+impl Client {
+    /// like [`Client::get_map_info`], but read from the file itself
+    ///
+    /// only if the header can not be read, the server is asked.
+    pub fn local_map_info(&mut self, file_name: &str) -> Result<MapInfo, ClientError> {
+        let path = self.maps_dir_path(file_name)?;
+        match fs::read(&path)
+            .ok()
+            .as_deref()
+            .and_then(GbxMapHeader::parse)
+        {
+            Some(header) => Ok(header.to_map_info(file_name)),
+            None => {
+                debug!("could not read the header of {file_name}, asking the server");
+                self.get_map_info(file_name)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GbxMapHeader;
+    use crate::testing::{gbx_map, temp_dir, test_client, test_config, MockServer, Reply};
+
+    #[test]
+    fn the_header_has_what_the_server_would_say() {
+        let header = GbxMapHeader::parse(&gbx_map("uid", "$f00Fast &amp; Loud")).unwrap();
+        assert_eq!(header.uid, "uid");
+        assert_eq!(header.name, "$f00Fast & Loud");
+        assert_eq!(header.author_login, "author");
+        assert_eq!(header.author_nickname, None);
+        assert_eq!(header.environment, "Stadium");
+        assert_eq!(header.map_type, "TrackMania\\TM_Race");
+        assert_eq!(header.gold_time, None);
+
+        let info = header.to_map_info("a.Map.Gbx");
+        assert_eq!(info.FileName, "a.Map.Gbx");
+        assert_eq!(info.AuthorNickname, "author");
+        assert_eq!(info.GoldTime, -1);
+    }
+
+    #[test]
+    fn other_files_have_no_header() {
+        assert_eq!(GbxMapHeader::parse(b""), None);
+        assert_eq!(GbxMapHeader::parse(b"PNG not a map"), None);
+        let mut old = gbx_map("uid", "old");
+        old[3] = 5;
+        assert_eq!(GbxMapHeader::parse(&old), None);
+        // cut off in the middle of the xml
+        let map = gbx_map("uid", "cut");
+        assert_eq!(GbxMapHeader::parse(&map[..map.len() - 10]), None);
+    }
+
+    #[test]
+    fn the_server_is_only_asked_without_a_header() {
+        let dir = temp_dir("gbx");
+        std::fs::write(dir.join("ours.Map.Gbx"), gbx_map("uid", "ours")).unwrap();
+        std::fs::write(dir.join("broken.Map.Gbx"), b"GBX").unwrap();
+        let maps_dir = dir.display().to_string();
+        let server = MockServer::start(move |method, _| match method {
+            "GetMapsDirectory" => Some(Reply::of(maps_dir.as_str())),
+            "GetMapInfo" => Some(Reply::Fault(-1000, "Map not found.")),
+            _ => None,
+        });
+        let mut client = test_client(test_config(&server));
+        assert_eq!(client.local_map_info("ours.Map.Gbx").unwrap().UId, "uid");
+        assert!(server.params_of("GetMapInfo").is_empty());
+        assert!(client.local_map_info("broken.Map.Gbx").is_err());
+        assert_eq!(server.params_of("GetMapInfo").len(), 1);
+    }
+}
@@ -0,0 +1,77 @@
+use dxr::TryFromValue;
+
+use crate::{Client, ClientError};
+
+/// what the server returns for each entry of its login lists
+#[allow(non_snake_case)]
+#[derive(TryFromValue, Debug)]
+pub(crate) struct LoginEntry {
+    pub Login: String,
+}
+
+impl Client {
+    pub fn get_guest_list(&mut self) -> Result<Vec<String>, ClientError> {
+        let entries: Vec<LoginEntry> = self.call_paged("GetGuestList")?;
+        Ok(entries.into_iter().map(|e| e.Login).collect())
+    }
+
+    /// adding a login that is already a guest is not an error
+    pub fn add_guest(&mut self, login: &str) -> Result<(), ClientError> {
+        match self.call::<bool>("AddGuest", login) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(ClientError::Rejected("AddGuest")),
+            Err(ClientError::Fault(fault)) if fault.string().contains("already") => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn remove_guest(&mut self, login: &str) -> Result<(), ClientError> {
+        let suc: bool = self.call("RemoveGuest", login)?;
+        if !suc {
+            return Err(ClientError::Rejected("RemoveGuest"));
+        }
+        Ok(())
+    }
+
+    /// `filename` is relative to the server's `UserData/Config` directory
+    pub fn save_guest_list(&mut self, filename: &str) -> Result<(), ClientError> {
+        let suc: bool = self.call("SaveGuestList", filename)?;
+        if !suc {
+            return Err(ClientError::Rejected("SaveGuestList"));
+        }
+        Ok(())
+    }
+
+    /// `/guests` lists them, `/guest add|remove <login>` and `/guest save <file>` change them
+    pub(crate) fn guest_command(&mut self, login: &str, args: &[&str]) -> Result<(), ClientError> {
+        let res = match args {
+            [] => {
+                let guests = self.get_guest_list()?;
+                let reply = if guests.is_empty() {
+                    "there are no guests".to_owned()
+                } else {
+                    format!("guests: {}", guests.join(", "))
+                };
+                return self.chat_send_to_login(login, &reply);
+            }
+            ["add", guest] => self.add_guest(guest).map(|()| format!("added {guest}")),
+            ["remove", guest] => self
+                .remove_guest(guest)
+                .map(|()| format!("removed {guest}")),
+            ["save", file] => self
+                .save_guest_list(file)
+                .map(|()| format!("saved to {file}")),
+            _ => {
+                let usage = "usage: /guest [add <login> | remove <login> | save <file>]";
+                return self.chat_send_to_login(login, usage);
+            }
+        };
+
+        let reply = match res {
+            Ok(reply) => reply,
+            Err(err) if !err.is_connection() => err.to_string(),
+            Err(err) => return Err(err),
+        };
+        self.chat_send_to_login(login, &reply)
+    }
+}
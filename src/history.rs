@@ -0,0 +1,114 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// a map that was picked, and when
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    /// in seconds since the unix epoch, 0 if we do not know
+    pub picked_at: u64,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+/// the most recently picked maps, oldest first
+///
+/// a map counts as recent if it is one of the last `capacity` maps, or if it
+/// was picked within `window`. maps are kept for as long as either is true.
+#[derive(Debug, Clone)]
+pub struct History {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+    window: Option<Duration>,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        History {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            window: None,
+        }
+    }
+
+    /// also counts maps picked within `window` as recent, however many came
+    /// after them
+    pub fn with_window(mut self, window: Option<Duration>) -> Self {
+        self.set_window(window);
+        self
+    }
+
+    fn within_window(&self, entry: &HistoryEntry) -> bool {
+        self.window
+            .is_some_and(|window| unix_now().saturating_sub(entry.picked_at) < window.as_secs())
+    }
+
+    pub fn contains(&self, id: u64) -> bool {
+        self.entries
+            .iter()
+            .rev()
+            .enumerate()
+            .any(|(n, entry)| entry.id == id && (n < self.capacity || self.within_window(entry)))
+    }
+
+    /// remembers `id` as picked now, forgetting maps that are no longer recent
+    pub fn push(&mut self, id: u64) {
+        self.push_entry(HistoryEntry {
+            id,
+            picked_at: unix_now(),
+        });
+    }
+
+    /// like [`History::push`], for maps that were picked before
+    pub fn push_entry(&mut self, entry: HistoryEntry) {
+        if self.capacity == 0 && self.window.is_none() {
+            return;
+        }
+        self.entries.push_back(entry);
+        self.prune();
+    }
+
+    /// forgets the oldest maps if there are more than `capacity`
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.prune();
+    }
+
+    pub fn set_window(&mut self, window: Option<Duration>) {
+        self.window = window;
+        self.prune();
+    }
+
+    fn prune(&mut self) {
+        while self.entries.len() > self.capacity {
+            match self.entries.front() {
+                Some(oldest) if !self.within_window(oldest) => self.entries.pop_front(),
+                _ => break,
+            };
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.entries.iter().map(|entry| entry.id)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &HistoryEntry> + '_ {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
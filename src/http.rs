@@ -0,0 +1,183 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, Sender},
+    thread,
+    time::Duration,
+};
+
+use serde_json::json;
+
+use crate::{client::Event, Client, ClientError};
+
+/// how long a request waits for the message loop before we give up
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct HttpResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn json(value: serde_json::Value) -> Self {
+        HttpResponse {
+            status: 200,
+            content_type: "application/json",
+            body: value.to_string().into_bytes(),
+        }
+    }
+
+    pub fn text(body: impl Into<String>) -> Self {
+        HttpResponse {
+            status: 200,
+            content_type: "text/plain",
+            body: body.into().into_bytes(),
+        }
+    }
+
+    pub fn jpeg(body: Vec<u8>) -> Self {
+        HttpResponse {
+            status: 200,
+            content_type: "image/jpeg",
+            body,
+        }
+    }
+
+    pub fn error(status: u16, msg: impl Into<String>) -> Self {
+        HttpResponse {
+            status,
+            content_type: "text/plain",
+            body: msg.into().into_bytes(),
+        }
+    }
+}
+
+impl Client {
+    /// serves the status endpoints on `addr`
+    ///
+    /// requests are answered by the message loop, so they see the same state
+    /// as everything else and never race with it.
+    pub fn spawn_http_status(&self, addr: &str) -> io::Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        say!("serving status on http://{addr}/status");
+        let events = self.events_tx();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let events = events.clone();
+                thread::spawn(move || {
+                    if let Err(err) = serve(stream, &events) {
+                        say!("while serving status request: {err}");
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+
+    pub(crate) fn http_response(&mut self, path: &str) -> HttpResponse {
+        match path {
+            "/status" => {
+                let network = match self.get_network_stats() {
+                    Ok(stats) => serde_json::to_value(stats).unwrap_or_default(),
+                    Err(err) => json!({ "error": err.to_string() }),
+                };
+                let server_name = self
+                    .get_server_options()
+                    .ok()
+                    .map(|options| options.display_name());
+                HttpResponse::json(json!({
+                    "address": self.config().address,
+                    "server_name": server_name,
+                    "state": self.state().to_string(),
+                    "current_map": self.current_map.as_ref().map(|m| &m.Name),
+                    "rotation_paused": self.rotation_paused,
+                    "history_size": self.history().len(),
+                    "network": network,
+                }))
+            }
+            "/rankings" => HttpResponse::json(json!(self.player_rankings())),
+            "/metrics" => self.metrics(),
+            // for container probes, anything but 200 means unhealthy
+            "/healthz" if self.state().is_healthy() => HttpResponse::text("ok"),
+            "/healthz" => HttpResponse::error(503, self.state().to_string()),
+            _ => match map_image_id(path) {
+                Some(id) => match self.capture_map_image(id) {
+                    Ok(image) => HttpResponse::jpeg(image),
+                    Err(err @ ClientError::NoImage(_)) => HttpResponse::error(404, err.to_string()),
+                    Err(err) => HttpResponse::error(503, err.to_string()),
+                },
+                None => HttpResponse::error(404, "not found"),
+            },
+        }
+    }
+}
+
+/// the tmx id in `/maps/<id>/image`
+fn map_image_id(path: &str) -> Option<u64> {
+    path.strip_prefix("/maps/")?
+        .strip_suffix("/image")?
+        .parse()
+        .ok()
+}
+
+fn serve(stream: TcpStream, events: &Sender<Event>) -> io::Result<()> {
+    stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // we don't care about the headers, but the client wants them read
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let res = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) => {
+            let (reply, response) = mpsc::channel();
+            let path = path.to_owned();
+            let _ = events.send(Event::Http { path, reply });
+            response
+                .recv_timeout(REPLY_TIMEOUT)
+                .unwrap_or_else(|_| HttpResponse::error(503, "controller is busy"))
+        }
+        _ => HttpResponse::error(405, "only GET is supported"),
+    };
+
+    let mut stream = reader.into_inner();
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        res.status,
+        reason(res.status),
+        res.content_type,
+        res.body.len(),
+    )?;
+    stream.write_all(&res.body)
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::map_image_id;
+
+    #[test]
+    fn only_map_image_paths_have_an_id() {
+        assert_eq!(map_image_id("/maps/123/image"), Some(123));
+        assert_eq!(map_image_id("/maps/123"), None);
+        assert_eq!(map_image_id("/maps/abc/image"), None);
+        assert_eq!(map_image_id("/maps//image"), None);
+        assert_eq!(map_image_id("/status"), None);
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use dxr::{TryFromValue, TryToValue};
+
+use crate::{strip_tm_formatting, Client, ClientError};
+
+/// how long we remember players that left, they might be back in a moment
+const LEFT_GRACE: Duration = Duration::from_secs(10 * 60);
+
+/// a player, as `GetPlayerList` and `GetPlayerInfo` return them
+#[allow(non_snake_case)]
+#[derive(TryFromValue, TryToValue, Debug, Clone)]
+pub(crate) struct PlayerEntry {
+    pub Login: String,
+    pub NickName: String,
+    pub PlayerId: i32,
+}
+
+#[derive(Debug, Clone)]
+struct Identity {
+    nickname: String,
+    player_id: i32,
+    /// when the player left, `None` while they are on the server
+    left: Option<Instant>,
+}
+
+/// the nicknames and player ids of everyone on the server, and of whoever
+/// left not long ago
+#[derive(Debug, Clone, Default)]
+pub struct PlayerIdentities {
+    by_login: HashMap<String, Identity>,
+}
+
+impl PlayerIdentities {
+    pub(crate) fn remember(&mut self, player: PlayerEntry) {
+        self.prune();
+        self.by_login.insert(
+            player.Login,
+            Identity {
+                nickname: player.NickName,
+                player_id: player.PlayerId,
+                left: None,
+            },
+        );
+    }
+
+    /// the player left, we forget them once the grace period is over
+    pub fn left(&mut self, login: &str) {
+        if let Some(identity) = self.by_login.get_mut(login) {
+            identity.left = Some(Instant::now());
+        }
+        self.prune();
+    }
+
+    fn prune(&mut self) {
+        self.by_login
+            .retain(|_, identity| identity.left.is_none_or(|left| left.elapsed() < LEFT_GRACE));
+    }
+
+    pub fn nickname(&self, login: &str) -> Option<&str> {
+        self.by_login.get(login).map(|i| i.nickname.as_str())
+    }
+
+    pub fn player_id(&self, login: &str) -> Option<i32> {
+        self.by_login.get(login).map(|i| i.player_id)
+    }
+}
+
+impl Client {
+    /// the nickname of `login`, if they are or were on the server recently
+    ///
+    /// nicknames keep their formatting codes, like `$f00`.
+    pub fn nickname_of(&self, login: &str) -> Option<String> {
+        self.identities.nickname(login).map(str::to_owned)
+    }
+
+    pub fn player_id_of(&self, login: &str) -> Option<i32> {
+        self.identities.player_id(login)
+    }
+
+    /// the nickname and login for logs, or just the login if we do not know
+    /// the nickname
+    pub(crate) fn display_name(&self, login: &str) -> String {
+        match self.identities.nickname(login) {
+            Some(nickname) => format!("{} ({login})", strip_tm_formatting(nickname)),
+            None => login.to_owned(),
+        }
+    }
+
+    pub(crate) fn get_player_info(&mut self, login: &str) -> Result<PlayerEntry, ClientError> {
+        // 1 is the struct version with the player id
+        self.call("GetPlayerInfo", (login, 1))
+    }
+
+    /// looks up who just connected, the callback only has the login
+    pub(crate) fn remember_player(&mut self, login: &str) -> Result<(), ClientError> {
+        match self.get_player_info(login) {
+            Ok(player) => {
+                debug!("{} joined", strip_tm_formatting(&player.NickName));
+                self.identities.remember(player);
+                Ok(())
+            }
+            Err(err) if !err.is_connection() => {
+                debug!("no nickname for {login}: {err}");
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use crate::{metrics::Histogram, netstats::format_duration, Client, ClientError};
+
+/// what the controller did since it started, for `/info`
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SessionStats {
+    /// `None` while disconnected
+    pub connected_since: Option<Instant>,
+    pub maps_downloaded: u32,
+    /// every map that started, restarts included
+    pub maps_played: u32,
+    /// searches and downloads
+    pub tmx_requests: u32,
+    pub tmx_failures: u32,
+    pub rpc_faults: u32,
+    /// connections that were lost and got back, not counting the first one
+    pub reconnects: u32,
+    /// from writing a call until the reader had the response
+    pub rpc_latency: Histogram,
+    /// the method that took the longest to be answered, and how long
+    pub slowest_call: Option<(String, Duration)>,
+    /// the most calls that waited for a response at once
+    pub max_in_flight: usize,
+    /// calls that had to wait for others to be answered first
+    pub in_flight_waits: u32,
+    /// how long the last keep-alive took to be answered
+    pub ping_rtt: Option<Duration>,
+    /// how long the last keep-alive echo took to come back as a callback
+    pub echo_rtt: Option<Duration>,
+    pub last_error: Option<String>,
+}
+
+impl Client {
+    /// remembers an error for `/info`, without printing it
+    pub fn note_error(&mut self, err: impl Display) {
+        self.stats.last_error = Some(err.to_string());
+    }
+
+    /// prints an error we can carry on after and remembers it for `/info`
+    pub(crate) fn report_error(&mut self, context: &str, err: impl Display) {
+        say!("while {context}: {err}");
+        self.note_error(format!("while {context}: {err}"));
+    }
+
+    /// `/info` shows what the controller itself is up to
+    pub(crate) fn info_command(&mut self, login: &str) -> Result<(), ClientError> {
+        let stats = &self.stats;
+        let connected = match stats.connected_since {
+            Some(since) => format!(
+                "{}, connected for {}",
+                self.state(),
+                format_duration(since.elapsed().as_secs() as i32)
+            ),
+            None => self.state().to_string(),
+        };
+        let search = &self.config().search;
+        let tags: Vec<String> = search.tags.iter().map(|t| t.to_string()).collect();
+        let mut reply = format!(
+            "{connected}, {} maps downloaded, {} tmx requests, history {}/{}, searching {} with tags {}",
+            stats.maps_downloaded,
+            stats.tmx_requests,
+            self.history().len(),
+            self.config().history_size,
+            search.map_type,
+            tags.join(","),
+        );
+        if !search.tag_weights.is_empty() {
+            reply += &format!(" ({} weighted)", search.tag_weights.len());
+        }
+        if self.rotation_paused {
+            reply += ", rotation paused";
+        }
+        if let Some(mean) = stats.rpc_latency.mean() {
+            reply += &format!(
+                ", {} calls taking {}ms on average",
+                stats.rpc_latency.count(),
+                mean.as_millis()
+            );
+        }
+        if let Some((method, latency)) = &stats.slowest_call {
+            reply += &format!(", slowest {method} with {}ms", latency.as_millis());
+        }
+        if let Some(err) = &stats.last_error {
+            reply += &format!(", last error: {err}");
+        }
+        self.chat_send_to_login(login, &reply)
+    }
+}
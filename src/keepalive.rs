@@ -0,0 +1,50 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use crate::{Client, ClientError};
+
+impl Client {
+    /// how long the message loop may wait for the next event before it has to
+    /// call [`Client::keepalive`], `None` for as long as it likes
+    pub(crate) fn keepalive_timeout(&self) -> Option<Duration> {
+        let interval = self.config().keepalive?;
+        // only the outermost loop pings, so the ping never gets between a
+        // call and its response
+        if !self.is_connected() || !self.calls.is_empty() {
+            return None;
+        }
+        Some(interval.saturating_sub(self.last_frame.elapsed()))
+    }
+
+    /// sends a cheap call after the connection was idle for a while
+    ///
+    /// the response is not waited for, but if it does not arrive within
+    /// another interval the connection is considered dead. with callbacks
+    /// on, an echo goes along, and the connection is dead as well if its
+    /// callback does not come back, even though responses still do.
+    pub(crate) fn keepalive(&mut self) -> Result<(), ClientError> {
+        if self.ping.is_some() {
+            self.disconnect();
+            return Err(
+                io::Error::new(io::ErrorKind::TimedOut, "keep-alive was not answered").into(),
+            );
+        }
+        // the health check waits for its echo on its own
+        if self.callbacks_enabled && self.echo.is_some() {
+            self.disconnect();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "the keep-alive echo did not come back, callbacks have stopped",
+            )
+            .into());
+        }
+        self.last_frame = Instant::now();
+        self.ping = Some((self.send_call("GetVersion", Vec::new())?, Instant::now()));
+        if self.callbacks_enabled {
+            self.send_echo()?;
+        }
+        Ok(())
+    }
+}
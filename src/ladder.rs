@@ -0,0 +1,126 @@
+use dxr::TryFromValue;
+
+use crate::{Client, ClientError, CurrentNext, MapCandidate};
+
+/// the ladder points players need to join the server
+#[allow(non_snake_case)]
+#[derive(TryFromValue, Debug, Clone, Copy, PartialEq)]
+pub struct LadderLimits {
+    pub LadderServerLimitMin: f64,
+    pub LadderServerLimitMax: f64,
+}
+
+impl LadderLimits {
+    pub fn contains(&self, points: f64) -> bool {
+        (self.LadderServerLimitMin..=self.LadderServerLimitMax).contains(&points)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ladder {
+    /// ladder is disabled, or the server does not know about it
+    NotLadder,
+    Limits(LadderLimits),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LadderMode {
+    Disabled,
+    Forced,
+}
+
+impl LadderMode {
+    fn from_i32(mode: i32) -> Self {
+        if mode == 0 {
+            LadderMode::Disabled
+        } else {
+            LadderMode::Forced
+        }
+    }
+
+    fn to_i32(self) -> i32 {
+        match self {
+            LadderMode::Disabled => 0,
+            LadderMode::Forced => 1,
+        }
+    }
+}
+
+impl Client {
+    pub fn get_ladder_mode(&mut self) -> Result<CurrentNext<LadderMode>, ClientError> {
+        let mode: CurrentNext<i32> = self.call("GetLadderMode", ())?;
+        Ok(CurrentNext {
+            CurrentValue: LadderMode::from_i32(mode.CurrentValue),
+            NextValue: LadderMode::from_i32(mode.NextValue),
+        })
+    }
+
+    /// only applies from the next map on
+    pub fn set_ladder_mode(&mut self, mode: LadderMode) -> Result<(), ClientError> {
+        let suc: bool = self.call("SetLadderMode", mode.to_i32())?;
+        if !suc {
+            return Err(ClientError::Rejected("SetLadderMode"));
+        }
+        Ok(())
+    }
+
+    /// [`Ladder::NotLadder`] when the ladder is disabled or the server faults
+    pub fn get_ladder_server_limits(&mut self) -> Result<Ladder, ClientError> {
+        match self.get_ladder_mode() {
+            Ok(mode) if mode.CurrentValue == LadderMode::Disabled => return Ok(Ladder::NotLadder),
+            Err(ClientError::Fault(_)) => return Ok(Ladder::NotLadder),
+            res => {
+                res?;
+            }
+        }
+        match self.call("GetLadderServerLimits", ()) {
+            Ok(limits) => Ok(Ladder::Limits(limits)),
+            Err(ClientError::Fault(_)) => Ok(Ladder::NotLadder),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// remembers the limits for [`Client::fits_ladder`]
+    pub(crate) fn refresh_ladder(&mut self) -> Result<Ladder, ClientError> {
+        self.ladder = self.get_ladder_server_limits()?;
+        Ok(self.ladder)
+    }
+
+    /// whether the difficulty of the map is within the ladder limits, as far
+    /// as `ladder_difficulties` knows
+    pub(crate) fn fits_ladder(&self, candidate: &MapCandidate) -> bool {
+        let Ladder::Limits(limits) = self.ladder else {
+            return true;
+        };
+        let points = candidate
+            .difficulty
+            .as_ref()
+            .and_then(|d| self.config().ladder_difficulties.get(d));
+        points.is_none_or(|&points| limits.contains(points))
+    }
+
+    /// `/ladder` shows the limits, `/ladder on|off` changes the mode
+    pub(crate) fn ladder_command(&mut self, login: &str, args: &[&str]) -> Result<(), ClientError> {
+        let mode = match args {
+            [] => {
+                let reply = match self.refresh_ladder()? {
+                    Ladder::NotLadder => "not a ladder server".to_owned(),
+                    Ladder::Limits(limits) => format!(
+                        "ladder limits {} to {}",
+                        limits.LadderServerLimitMin, limits.LadderServerLimitMax
+                    ),
+                };
+                return self.chat_send_to_login(login, &reply);
+            }
+            ["on"] => LadderMode::Forced,
+            ["off"] => LadderMode::Disabled,
+            _ => return self.chat_send_to_login(login, "usage: /ladder [on|off]"),
+        };
+        let reply = match self.set_ladder_mode(mode) {
+            Ok(()) => "ladder mode changed, it applies from the next map".to_owned(),
+            Err(err) if !err.is_connection() => err.to_string(),
+            Err(err) => return Err(err),
+        };
+        self.chat_send_to_login(login, &reply)
+    }
+}
@@ -0,0 +1,151 @@
+#[macro_use]
+mod output;
+
+mod announce;
+mod bans;
+mod callback;
+mod catalog;
+mod check;
+mod client;
+mod commands;
+mod config;
+mod control;
+mod countdown;
+mod datetime;
+mod debounce;
+mod decay;
+mod dislike;
+mod display;
+mod download;
+mod duplicate;
+mod echo;
+mod environment;
+mod error;
+mod evict;
+mod export;
+mod fallback;
+mod formatting;
+mod forward;
+mod frame;
+mod gameinfo;
+mod gbx;
+mod guests;
+mod history;
+mod http;
+mod identity;
+mod info;
+mod keepalive;
+mod ladder;
+mod lifecycle;
+mod local;
+mod manialink;
+mod maps;
+mod metadata;
+mod metrics;
+mod mode;
+mod mods;
+mod netstats;
+mod offline;
+mod password;
+mod pause;
+mod player_info;
+mod players;
+mod prefetch;
+mod preview;
+mod queue;
+mod rankings;
+mod rate_limit;
+mod reconcile;
+mod reconnect;
+mod repl;
+mod replay;
+mod restrict;
+mod retry;
+mod roles;
+mod safe_mode;
+mod seed;
+mod server_options;
+mod settings;
+mod shutdown;
+mod skipped;
+mod spectator;
+mod staging;
+mod startup;
+mod storage;
+mod summary;
+#[cfg(test)]
+mod testing;
+mod time_limit;
+mod tmx;
+mod transcript;
+mod value;
+mod vote;
+mod warmup;
+
+pub use announce::fill_template;
+pub use bans::BanEntry;
+pub use callback::{Callback, CallbackError, ManialinkEntry, WaitError};
+pub use catalog::{Catalog, CatalogEntry, MapOrigin};
+pub use check::CheckItem;
+pub use client::{Client, ConnectionState, MapInfo};
+pub use config::{Config, ConfigError};
+pub use control::ControlCommand;
+pub use countdown::SwitchCountdown;
+pub use datetime::{tm_datetime, InvalidTimezone, ServerTimezone};
+pub use decay::TagDecay;
+pub use dislike::{tmx_id_of, Blocklist};
+pub use display::DisplaySettings;
+pub use download::{download_file, DownloadError};
+pub use duplicate::DuplicatePolicy;
+pub use environment::{title_environments, ServerVersion};
+pub use error::ClientError;
+pub use export::RotationExport;
+pub use formatting::strip_tm_formatting;
+pub use forward::CallbackForwarder;
+pub use frame::{Frame, FrameDecoder, FrameError, DEFAULT_MAX_FRAME_LEN};
+pub use gameinfo::{FinishTimeout, GameInfo, GameInfos, GameMode};
+pub use gbx::GbxMapHeader;
+pub use history::{History, HistoryEntry};
+pub use http::HttpResponse;
+pub use ladder::{Ladder, LadderLimits, LadderMode};
+pub use lifecycle::{Lifecycle, LifecycleHandler};
+pub use manialink::{text_manialink, ManialinkHandler};
+pub use maps::{in_maps_dir, InsertMode};
+pub use metadata::MetadataCache;
+pub use mode::{ModeScriptInfo, ModeScriptParam};
+pub use mods::{ForcedMod, ForcedMods};
+pub use netstats::{NetworkStats, PlayerNetInfo};
+pub use offline::{CacheDry, CacheMapSource};
+#[doc(hidden)]
+pub use output::{debug_enabled, print_line};
+pub use output::{
+    set_debug_log, set_log_file, set_log_prefix, set_log_to_stderr, set_protocol_trace, LogFile,
+};
+pub use player_info::{PlayerFlags, PlayerInfo, SpectatorStatus};
+pub use preview::{embedded_thumbnail, is_gbx_map};
+pub use rankings::PlayerRanking;
+pub use rate_limit::RateLimiter;
+pub use reconcile::MapListSync;
+pub use reconnect::ConnectHandler;
+pub use repl::parse_call;
+pub use restrict::{method_allowed, DEFAULT_ALLOWED_METHODS, SECRET_METHODS};
+pub use retry::{retryable_fault, DEFAULT_RETRYABLE_FAULTS};
+pub use roles::Role;
+pub use safe_mode::DESTRUCTIVE_METHODS;
+pub use server_options::ServerOptions;
+pub use settings::bool_setting;
+pub use shutdown::ShutdownHandle;
+pub use spectator::{SpectatorCamera, SpectatorMode};
+pub use storage::{
+    migrate, schema_version, FileStorage, MemoryStorage, StateFile, Storage, SCHEMA_VERSION,
+};
+pub use summary::SessionSummary;
+pub use time_limit::TimeBudget;
+pub use tmx::{
+    pick_weighted, MapCandidate, MapFilter, MapSearch, MapSource, StubMapSource, TmxMapMetadata,
+    TmxMapSource, TmxUrlError,
+};
+pub use transcript::{ReplayError, ReplayServer, TraceFrame, Transcript, TranscriptError};
+pub use value::{value_as_bytes, value_to_json, CurrentNext};
+pub use vote::MapVoteSettings;
+pub use warmup::WarmUpStatus;
@@ -0,0 +1,106 @@
+use std::{fmt, sync::Arc};
+
+use crate::{Client, ClientError};
+
+/// runs at a point of the match, see [`Client::on_lifecycle`]
+pub type LifecycleHandler = Arc<dyn Fn(&mut Client) -> Result<(), ClientError> + Send + Sync>;
+
+/// the points of a match that script modes and legacy modes both tell us about
+///
+/// a map goes `BeginMap`, [`Lifecycle::BeginMatch`], the racing,
+/// [`Lifecycle::EndMatch`], [`Lifecycle::PodiumStart`],
+/// [`Lifecycle::PodiumEnd`] and `EndMap`. modes with several matches per map,
+/// like cup, repeat the part from `BeginMatch` to `EndMatch`. legacy modes
+/// have no podium callbacks, their podium is the time between `EndMatch` and
+/// `EndMap`. the script callbacks only arrive once they are enabled, which
+/// connecting does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lifecycle {
+    BeginMatch,
+    EndMatch,
+    /// the scores are shown and nobody is racing, a good time for changes
+    PodiumStart,
+    PodiumEnd,
+}
+
+impl Lifecycle {
+    /// for the callbacks of legacy modes, like `ManiaPlanet.BeginMatch`
+    pub fn from_method(name: &str) -> Option<Self> {
+        match name {
+            "ManiaPlanet.BeginMatch" => Some(Lifecycle::BeginMatch),
+            "ManiaPlanet.EndMatch" => Some(Lifecycle::EndMatch),
+            _ => None,
+        }
+    }
+
+    /// for the script callbacks, like `Maniaplanet.Podium_Start`
+    pub fn from_script(name: &str) -> Option<Self> {
+        match name {
+            "Maniaplanet.StartMatch_Start" => Some(Lifecycle::BeginMatch),
+            "Maniaplanet.EndMatch_Start" => Some(Lifecycle::EndMatch),
+            "Maniaplanet.Podium_Start" => Some(Lifecycle::PodiumStart),
+            "Maniaplanet.Podium_End" => Some(Lifecycle::PodiumEnd),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Lifecycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let event = match self {
+            Lifecycle::BeginMatch => "match start",
+            Lifecycle::EndMatch => "match end",
+            Lifecycle::PodiumStart => "podium start",
+            Lifecycle::PodiumEnd => "podium end",
+        };
+        f.write_str(event)
+    }
+}
+
+impl Client {
+    /// calls `handler` every time `event` happens, after the handlers that
+    /// were registered before it
+    pub fn on_lifecycle(
+        &mut self,
+        event: Lifecycle,
+        handler: impl Fn(&mut Client) -> Result<(), ClientError> + Send + Sync + 'static,
+    ) {
+        self.lifecycle_handlers
+            .entry(event)
+            .or_default()
+            .push(Arc::new(handler));
+    }
+
+    pub fn clear_lifecycle_handlers(&mut self, event: Lifecycle) {
+        self.lifecycle_handlers.remove(&event);
+    }
+
+    pub(crate) fn handle_lifecycle(&mut self, event: Lifecycle) -> Result<(), ClientError> {
+        debug!("{event}");
+        if event == Lifecycle::PodiumStart && !self.rotation_paused {
+            // legacy modes got these at the end of the match already
+            self.insert_staged()?;
+            match self.announce_next_map() {
+                Err(err) if !err.is_connection() => {
+                    self.report_error("announcing the next map", err)
+                }
+                res => res?,
+            }
+        }
+        // the handlers may register or clear handlers themselves
+        let handlers = self
+            .lifecycle_handlers
+            .get(&event)
+            .cloned()
+            .unwrap_or_default();
+        for handler in handlers {
+            match handler(self) {
+                Err(err) if !err.is_connection() => {
+                    self.report_error(&format!("handling the {event}"), err)
+                }
+                res => res?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{CatalogEntry, Client, ClientError, MapInfo, MapOrigin};
+
+/// whether `rel_path` stays inside the directory it is relative to
+///
+/// both separators count, since the server might run on windows.
+pub(crate) fn is_inside(rel_path: &str) -> bool {
+    !rel_path.is_empty()
+        && !rel_path.starts_with(['/', '\\'])
+        && !rel_path.contains(':')
+        && rel_path
+            .split(['/', '\\'])
+            .all(|part| !part.is_empty() && part != "." && part != "..")
+}
+
+impl Client {
+    /// inserts a map that was put into the maps directory by hand
+    ///
+    /// `rel_path` is relative to the maps directory and may not leave it. the
+    /// map is kept in the catalog as local, so exports list it as such and it
+    /// is never evicted.
+    pub fn add_local_map(&mut self, rel_path: &str) -> Result<MapInfo, ClientError> {
+        if !is_inside(rel_path) {
+            return Err(ClientError::OutsideMapsDirectory(rel_path.to_owned()));
+        }
+        if !self.maps_dir_path(rel_path)?.is_file() {
+            return Err(ClientError::MapNotFound(rel_path.to_owned()));
+        }
+        let info = self.local_map_info(rel_path)?;
+        if self
+            .get_map_list()?
+            .iter()
+            .any(|map| map.UId == info.UId || map.FileName == info.FileName)
+        {
+            return Err(ClientError::MapInRotation(rel_path.to_owned()));
+        }
+        if !self.insert_map(rel_path)? {
+            return Err(ClientError::Rejected("InsertMap"));
+        }
+        let mut entry = CatalogEntry::new(MapOrigin::Local);
+        entry.uid = Some(info.UId.clone());
+        entry.name = Some(info.Name.clone());
+        self.catalog.insert(&info.FileName, entry);
+        self.save_catalog();
+        Ok(info)
+    }
+
+    /// `/addlocal <file>` inserts a map from the maps directory
+    ///
+    /// `rel_path` is the rest of the chat line as it was typed, since file
+    /// names may have spaces of any kind.
+    pub(crate) fn addlocal_command(
+        &mut self,
+        login: &str,
+        rel_path: &str,
+    ) -> Result<(), ClientError> {
+        if rel_path.is_empty() {
+            return self.chat_send_to_login(login, "usage: /addlocal <file>");
+        }
+        let reply = match self.add_local_map(rel_path) {
+            Ok(info) => format!("added {} by {}", info.Name, info.AuthorNickname),
+            Err(err) if err.is_connection() => return Err(err),
+            Err(err) => err.to_string(),
+        };
+        self.chat_send_to_login(login, &reply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dxr::Value;
+
+    use super::is_inside;
+    use crate::{
+        testing::{chat_xml, gbx_map, temp_dir, test_client, test_config, MockServer, Reply},
+        MapOrigin, MemoryStorage,
+    };
+
+    #[test]
+    fn local_maps_are_kept_in_the_catalog() {
+        let dir = temp_dir("local");
+        // with the spaces a chat line would lose when split into words
+        let file_name = "My  Map.Map.Gbx";
+        std::fs::write(dir.join(file_name), gbx_map("uid", "mine")).unwrap();
+        let maps_dir = dir.display().to_string();
+        let server = MockServer::start(move |method, _| match method {
+            "GetMapsDirectory" => Some(Reply::of(maps_dir.as_str())),
+            "GetMapList" => Some(Reply::of(Vec::<Value>::new())),
+            _ => None,
+        });
+        let mut config = test_config(&server);
+        config.admins = vec!["owner".to_owned()];
+        let mut client = test_client(config.clone());
+        let storage = MemoryStorage::default();
+        client.set_storage(Box::new(storage.clone()));
+
+        let msg = chat_xml("owner", &format!("/addlocal {file_name}"));
+        client.handle_callback(&msg, 0).unwrap();
+        let inserted = server.params_of("InsertMap");
+        assert_eq!(inserted, [vec![Value::string(file_name.to_owned())]]);
+
+        // a restart still knows where it came from
+        let mut client = test_client(config);
+        client.set_storage(Box::new(storage));
+        let entry = client.catalog.get(file_name).unwrap();
+        assert_eq!(entry.origin, MapOrigin::Local);
+        assert_eq!(entry.uid.as_deref(), Some("uid"));
+    }
+
+    #[test]
+    fn paths_may_not_leave_the_maps_directory() {
+        assert!(is_inside("My Maps/a.Map.Gbx"));
+        assert!(!is_inside("../a.Map.Gbx"));
+        assert!(!is_inside("My Maps\\..\\..\\a.Map.Gbx"));
+        assert!(!is_inside("C:\\a.Map.Gbx"));
+        assert!(!is_inside("/a.Map.Gbx"));
+    }
+}
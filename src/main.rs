@@ -1,203 +1,307 @@
 use std::{
-    collections::{HashMap, HashSet},
-    fs::File,
-    io::{Read, Write},
-    net::TcpStream,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
 };
 
-use dxr::{Fault, FaultResponse, MethodCall, MethodResponse, TryFromValue};
-
-use color_eyre::eyre::ContextCompat;
-
-struct Client {
-    client: TcpStream,
-    exchange: reqwest::blocking::Client,
-    handle: u32,
-
-    calls: HashSet<u32>,
-    responses: HashMap<u32, String>,
-}
+use color_eyre::eyre::eyre;
+use trackmania_controller::{
+    say, set_debug_log, set_log_file, set_log_prefix, set_log_to_stderr, set_protocol_trace,
+    Client, Config, LogFile, ShutdownHandle,
+};
 
-impl Client {
-    pub fn new() -> Self {
-        let mut client = Client {
-            client: TcpStream::connect("localhost:5000").unwrap(),
-            // trackmania.exchange does not like it if we don't give a user_agent
-            exchange: reqwest::blocking::Client::builder()
-                .user_agent("hytak-server-util")
-                .build()
-                .unwrap(),
-            handle: 0x80000000,
-            calls: HashSet::new(),
-            responses: HashMap::new(),
-        };
-        let len = client.read_u32();
-        let hello = client.read_msg(len);
-        assert_eq!(hello, "GBXRemote 2");
+const CONFIG_PATH: &str = "controller.toml";
 
-        let suc: bool = client.call("SetApiVersion", "2023-04-24").unwrap();
-        assert!(suc);
-        let suc: bool = client
-            .call("Authenticate", ["SuperAdmin", "SuperAdmin"])
-            .unwrap();
-        assert!(suc);
+/// what can follow the config instead of running the controller
+const COMMANDS: &[&str] = &["download", "next"];
 
-        let suc: bool = client.call("EnableCallbacks", [true]).unwrap();
-        assert!(suc);
+/// reconnect delays start here and double on every failed attempt
+const BACKOFF_MIN: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(60);
 
-        client
+fn main() -> color_eyre::Result<()> {
+    // flags can go anywhere, the first other argument is the config and the
+    // rest a command, if any
+    let (flags, args): (Vec<_>, Vec<_>) =
+        std::env::args().skip(1).partition(|a| a.starts_with("--"));
+    if let Some(flag) = flags
+        .iter()
+        .find(|f| !["--offline", "--health-check", "--check", "--repl"].contains(&f.as_str()))
+    {
+        return Err(eyre!("unknown flag {flag}"));
     }
-
-    pub fn read_u32(&mut self) -> u32 {
-        let mut val = [0; 4];
-        self.client.read_exact(&mut val).unwrap();
-        u32::from_le_bytes(val)
+    let (path, command) = match args.first().map(String::as_str) {
+        Some(name) if COMMANDS.contains(&name) => (CONFIG_PATH.to_owned(), &args[..]),
+        Some(path) => (path.to_owned(), &args[1..]),
+        None => (CONFIG_PATH.to_owned(), &args[..]),
+    };
+    let check = flags.iter().any(|f| f == "--check");
+    let mut configs = if Path::new(&path).exists() {
+        match Config::load_all(&path) {
+            Ok(configs) => configs,
+            Err(err) if check => {
+                println!("FAIL config: {err}");
+                process::exit(1);
+            }
+            Err(err) => return Err(err.into()),
+        }
+    } else {
+        println!("no config at {path}, using the defaults");
+        vec![Config::default()]
+    };
+    if flags.iter().any(|f| f == "--offline") {
+        for config in &mut configs {
+            config.offline = true;
+        }
     }
 
-    pub fn write_u32(&mut self, val: u32) {
-        self.client.write_all(&val.to_le_bytes()).unwrap();
+    if configs.is_empty() {
+        return Err(eyre!("{path} lists no servers"));
+    }
+    if flags.iter().any(|f| f == "--health-check") {
+        health_check(configs);
+    }
+    if check {
+        check_setup(configs);
     }
+    open_logs(&configs);
+    set_debug_log(configs.iter().any(|c| c.debug_log));
+    // stdout is left to the replies of the control channel
+    set_log_to_stderr(configs.iter().any(|c| c.stdin_control));
+    if flags.iter().any(|f| f == "--repl") {
+        return repl(configs);
+    }
+    if !command.is_empty() {
+        return run_command(configs, command);
+    }
+    let timeout = configs.iter().map(|c| c.shutdown_timeout).max();
+    let shutdown = Shutdown::install(timeout.unwrap_or_default())?;
 
-    pub fn new_handle(&mut self) -> u32 {
-        self.handle += 1;
-        if self.handle >= 0xffffff00 {
-            self.handle = 0x80000000;
-        }
-        self.handle
+    if let [config] = &configs[..] {
+        return run(config.clone(), &path, &shutdown);
     }
 
-    pub fn read_msg(&mut self, len: u32) -> String {
-        let mut msg = vec![0; len as usize];
-        self.client.read_exact(&mut msg).unwrap();
-        String::from_utf8(msg).unwrap()
+    // every server gets a client and a thread of its own, they share nothing
+    let threads: Vec<_> = configs
+        .into_iter()
+        .map(|config| {
+            let path = path.clone();
+            let shutdown = shutdown.clone();
+            let id = config.server_id().to_owned();
+            thread::Builder::new().name(id.clone()).spawn(move || {
+                set_log_prefix(&id);
+                let res = run(config, &path, &shutdown);
+                if let Err(err) = &res {
+                    say!("stopped: {err}");
+                }
+                res
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    for thread in threads {
+        // the errors were printed by the thread already
+        let _ = thread.join();
     }
+    Ok(())
+}
 
-    pub fn call<R>(&mut self, f: &'static str, args: impl dxr::TryToParams) -> Result<R, Fault>
-    where
-        R: TryFromValue,
-    {
-        let method = MethodCall::new(f.to_owned(), args.try_to_params().unwrap());
-        let msg = dxr::serialize_xml(&method).unwrap();
-        self.write_u32(msg.len() as u32);
-        let handle = self.new_handle();
-        self.write_u32(handle);
-        self.client.write_all(msg.as_bytes()).unwrap();
-
-        self.calls.insert(handle);
-        let msg = loop {
-            self.await_messages();
-
-            if let Some(msg) = self.responses.remove(&handle) {
-                break msg;
+/// the servers share the output of the process, so the first one that sets a
+/// file decides where it goes
+fn open_logs(configs: &[Config]) {
+    let open = |file: fn(&Config) -> &Option<PathBuf>| {
+        let config = configs.iter().find(|c| file(c).is_some())?;
+        let path = file(config).as_ref()?;
+        match LogFile::open(path, config.log_max_bytes, config.log_keep) {
+            Ok(file) => Some(file),
+            Err(err) => {
+                say!(
+                    "could not open {}: {err}, logging to stdout only",
+                    path.display()
+                );
+                None
             }
-        };
-
-        if let Ok(res) = dxr::deserialize_xml::<FaultResponse>(&msg) {
-            let fault = Fault::try_from(res).unwrap();
-            return Err(fault);
         }
-        let res: MethodResponse = dxr::deserialize_xml(&msg).unwrap();
-        Ok(R::try_from_value(&res.inner()).unwrap())
-    }
-
-    /// this will wait for callbacks or response for one of `self.msgs`
-    pub fn await_messages(&mut self) {
-        loop {
-            let len = self.read_u32();
-            let handle = self.read_u32();
-            let msg = self.read_msg(len);
+    };
+    set_log_file(open(|c| &c.log_file));
+    set_protocol_trace(open(|c| &c.protocol_trace));
+}
 
-            // were we expecting a response for this handle?
-            if self.calls.remove(&handle) {
-                self.responses.insert(handle, msg);
-                return;
+/// logs in to every server once and exits, with 1 if any of them failed
+fn health_check(configs: Vec<Config>) -> ! {
+    let mut healthy = true;
+    for config in configs {
+        let id = config.server_id().to_owned();
+        match Client::new(config).check_health() {
+            Ok(()) => println!("{id}: OK"),
+            Err(err) => {
+                println!("{id}: FAIL {err}");
+                healthy = false;
             }
-
-            self.handle_callback(&msg, handle);
         }
     }
+    process::exit(if healthy { 0 } else { 1 })
+}
 
-    pub fn handle_callback(&mut self, msg: &str, _handle: u32) {
-        let call: MethodCall = dxr::deserialize_xml(msg).unwrap();
-
-        if call.name() == "ManiaPlanet.BeginMap" {
-            let random_id = self.random_map_id().unwrap();
-            println!("downloading map {random_id}");
-            self.download_map(random_id);
+/// checks everything short of running the controller on every server, and
+/// exits with 1 if anything failed
+fn check_setup(configs: Vec<Config>) -> ! {
+    let mut passed = true;
+    let several = configs.len() > 1;
+    for config in configs {
+        let id = config.server_id().to_owned();
+        for item in Client::new(config).check_setup() {
+            passed &= item.result.is_ok();
+            if several {
+                println!("{id}: {item}");
+            } else {
+                println!("{item}");
+            }
         }
-
-        // println!("{call:?}")
     }
+    process::exit(if passed { 0 } else { 1 })
+}
 
-    fn random_map_id(&mut self) -> color_eyre::Result<u64> {
-        let res = self
-            .exchange
-            .get("http://trackmania.exchange/mapsearch2/search?api=on&random=1&etags=23,37,40&mtype=TM_Race")
-            .send()?;
-
-        let val: serde_json::Value = serde_json::from_str(&res.text()?)?;
-        let id = val
-            .get("results")
-            .context("no results")?
-            .get(0)
-            .context("no results")?
-            .get("TrackID")
-            .context("no track id")?
-            .as_u64()
-            .context("not a number")?;
-
-        Ok(id)
+/// runs `download <id>` or `next` on every server, then exits
+fn run_command(configs: Vec<Config>, command: &[String]) -> color_eyre::Result<()> {
+    let id = match command {
+        [name] if name == "next" => None,
+        [name, id] if name == "download" => Some(
+            id.parse::<u64>()
+                .map_err(|_| eyre!("invalid map id {id}"))?,
+        ),
+        _ => return Err(eyre!("usage: [config] download <id> | next")),
+    };
+    for config in configs {
+        let server = config.server_id().to_owned();
+        Client::oneshot(config, |client| match id {
+            Some(id) => client.download_map(id).map(drop),
+            None => client.call::<bool>("NextMap", ()).map(drop),
+        })
+        .map_err(|err| eyre!("{server}: {err}"))?;
     }
+    Ok(())
+}
 
-    fn download_map(&mut self, id: u64) {
-        let dir: String = self.call("GetMapsDirectory", ()).unwrap();
-
-        if let Ok(mut file) = File::create_new(format!("{dir}{id}.Map.Gbx")) {
-            let url = format!("https://trackmania.exchange/maps/download/{id}");
-            let req = self.exchange.get(url);
-            req.send().unwrap().copy_to(&mut file).unwrap();
-        } else {
-            println!("map is already downloaded")
-        }
-
-        let rel_path = format!("{id}.Map.Gbx");
-        // let next: MapInfo = self.call("GetNextMapInfo", ());
+/// sends calls typed on stdin to the first server
+fn repl(configs: Vec<Config>) -> color_eyre::Result<()> {
+    let config = configs.into_iter().next().unwrap();
+    println!("connecting to {}", config.server_id());
+    Client::new(config).run_repl()?;
+    Ok(())
+}
 
-        if let Err(err) = self.call::<bool>("InsertMap", rel_path.as_str()) {
-            println!("while inserting map: {}", err.string())
+fn run(config: Config, path: &str, shutdown: &Shutdown) -> color_eyre::Result<()> {
+    // the client is kept across reconnects so its state is not lost
+    let mut client = Client::new(config);
+    client.set_config_path(path);
+    shutdown.register(client.shutdown_handle());
+    if client.config().offline {
+        say!("offline, maps are only picked from the download cache");
+    }
+    if client.config().stdin_control {
+        client.spawn_stdin_control();
+    }
+    if client.config().enable_http_status {
+        client.spawn_http_status(&client.config().http_status_addr)?;
+    }
+    let mut backoff = BACKOFF_MIN;
+    let mut first_connect = true;
+
+    loop {
+        say!("connecting to {}", client.config().address);
+        let res = client.connect().and_then(|()| {
+            backoff = BACKOFF_MIN;
+            if first_connect {
+                if client.config().seed_on_start {
+                    match client.seed_to_min_maps() {
+                        Err(err) if !err.is_connection() => {
+                            say!("while seeding the rotation: {err}")
+                        }
+                        res => {
+                            res?;
+                        }
+                    }
+                }
+                match client.call::<bool>("NextMap", ()) {
+                    Err(err) if !err.is_connection() => {
+                        say!("while skipping to the first map: {err}")
+                    }
+                    res => {
+                        res?;
+                    }
+                }
+                first_connect = false;
+            }
+            client.run_messages()
+        });
+
+        match res {
+            // only happens once we are asked to stop
+            Ok(()) => break,
+            Err(err) if err.is_connection() => {
+                say!("{err}, reconnecting in {}s", backoff.as_secs());
+                client.note_error(&err);
+                client.disconnect();
+                if client.sleep_unless_shutdown(backoff) {
+                    break;
+                }
+                backoff = (backoff * 2).min(BACKOFF_MAX);
+            }
+            // only the setup in connect gets here, the config or the server
+            // is set up wrong and trying again does not help
+            Err(err) => return Err(err.into()),
         }
-        // self.call::<bool>("ChooseNextMap", rel_path.as_str())
-        //     .unwrap();
-        // self.call::<bool>("NextMap", ()).unwrap();
     }
+    client.close();
+    say!("stopped");
+    Ok(())
 }
 
-fn main() {
-    // let arg: Vec<String> = std::env::args().collect();
-
-    let mut client = Client::new();
-
-    // client.download_map(&arg[1]);
-
-    // for _ in 0..20 {
-    //     let random_id = client.random_map_id().unwrap();
-    //     client.download_map(random_id);
-    // }
-    client.call::<bool>("NextMap", ()).unwrap();
-    client.await_messages();
+/// tells every client to stop on SIGINT or SIGTERM
+#[derive(Clone)]
+struct Shutdown {
+    requested: Arc<AtomicBool>,
+    handles: Arc<Mutex<Vec<ShutdownHandle>>>,
 }
 
-#[allow(non_snake_case, dead_code)]
-#[derive(TryFromValue, Debug)]
-struct MapInfo {
-    Name: String,
-    UId: String,
-    FileName: String,
-    Environnement: String,
-    Author: String,
-    AuthorNickname: String,
-    GoldTime: i32,
-    CopperPrice: i32,
-    MapType: String,
-    MapStyle: String,
+impl Shutdown {
+    /// exits anyway if the clients take longer than `timeout`, for example
+    /// because a download is stuck
+    fn install(timeout: Duration) -> Result<Self, ctrlc::Error> {
+        let shutdown = Shutdown {
+            requested: Arc::new(AtomicBool::new(false)),
+            handles: Arc::new(Mutex::new(Vec::new())),
+        };
+        let handler = shutdown.clone();
+        ctrlc::set_handler(move || {
+            if handler.requested.swap(true, Ordering::SeqCst) {
+                // a second signal means nobody wants to wait
+                process::exit(130);
+            }
+            say!("shutting down");
+            for handle in handler.handles.lock().unwrap().iter() {
+                handle.shutdown();
+            }
+            thread::spawn(move || {
+                thread::sleep(timeout);
+                say!("did not stop within {}s, exiting", timeout.as_secs());
+                process::exit(1);
+            });
+        })?;
+        Ok(shutdown)
+    }
+
+    fn register(&self, handle: ShutdownHandle) {
+        let mut handles = self.handles.lock().unwrap();
+        // the signal came before this client existed
+        if self.requested.load(Ordering::SeqCst) {
+            handle.shutdown();
+        }
+        handles.push(handle);
+    }
 }
@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use crate::{Client, ClientError, ManialinkEntry};
+
+/// runs when a player clicks an element with the answer it was registered for,
+/// with the login of the player and the text fields on the page
+pub type ManialinkHandler =
+    Arc<dyn Fn(&mut Client, &str, &[ManialinkEntry]) -> Result<(), ClientError> + Send + Sync>;
+
+/// a manialink with a single line of text at the top of the screen
+///
+/// with `action`, clicking the text sends it back as the answer, see
+/// [`Client::on_manialink_answer`]. a page replaces any earlier page
+/// with the same `id`.
+pub fn text_manialink(id: &str, text: &str, action: Option<&str>) -> String {
+    let action = action
+        .map(|action| format!(" action=\"{}\"", escape_xml(action)))
+        .unwrap_or_default();
+    format!(
+        "<manialink id=\"{}\" version=\"3\">\
+         <label pos=\"0 80\" halign=\"center\" valign=\"center\" textsize=\"2\" text=\"{}\"{action}/>\
+         </manialink>",
+        escape_xml(id),
+        escape_xml(text),
+    )
+}
+
+pub(crate) fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Client {
+    /// shows `xml` to every player
+    ///
+    /// it disappears after `timeout` ms, or stays with 0. `hide_on_click`
+    /// hides it once a player clicks anything with an action.
+    pub fn send_display_manialink_page(
+        &mut self,
+        xml: &str,
+        timeout: i32,
+        hide_on_click: bool,
+    ) -> Result<(), ClientError> {
+        self.call::<bool>("SendDisplayManialinkPage", (xml, timeout, hide_on_click))?;
+        Ok(())
+    }
+
+    /// like [`Client::send_display_manialink_page`], but only for `login`
+    pub fn send_display_manialink_page_to_login(
+        &mut self,
+        login: &str,
+        xml: &str,
+        timeout: i32,
+        hide_on_click: bool,
+    ) -> Result<(), ClientError> {
+        self.call::<bool>(
+            "SendDisplayManialinkPageToLogin",
+            (login, xml, timeout, hide_on_click),
+        )?;
+        Ok(())
+    }
+
+    /// calls `handler` whenever a player clicks an element whose action is
+    /// `answer`, replacing the handler that was there before
+    pub fn on_manialink_answer(
+        &mut self,
+        answer: impl Into<String>,
+        handler: impl Fn(&mut Client, &str, &[ManialinkEntry]) -> Result<(), ClientError>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.manialink_handlers
+            .insert(answer.into(), Arc::new(handler));
+    }
+
+    pub fn remove_manialink_handler(&mut self, answer: &str) {
+        self.manialink_handlers.remove(answer);
+    }
+
+    /// a player clicked an element of a manialink
+    ///
+    /// answers without a handler that start with `/` run like the chat
+    /// command they spell.
+    pub(crate) fn handle_manialink_answer(
+        &mut self,
+        login: &str,
+        answer: &str,
+        entries: &[ManialinkEntry],
+    ) -> Result<(), ClientError> {
+        // the handler may register or remove handlers itself
+        if let Some(handler) = self.manialink_handlers.get(answer).cloned() {
+            return handler(self, login, entries);
+        }
+        if answer.starts_with('/') {
+            self.handle_chat(login, answer)?;
+        }
+        Ok(())
+    }
+}
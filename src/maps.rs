@@ -0,0 +1,181 @@
+use std::{fs, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::{control::map_file_name, Client, ClientError, MapInfo};
+
+/// where new maps go in the map list, see [`Client::add_map`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InsertMode {
+    /// right after the current map, with `InsertMap`
+    #[default]
+    Next,
+    /// at the end of the list, with `AddMap`, so it comes up in its turn
+    Pool,
+}
+
+/// `rel_path` inside the maps directory `dir`
+///
+/// some servers end `dir` with a separator and some do not. without one, the
+/// separator is the one `dir` already uses, so windows paths work too.
+pub fn in_maps_dir(dir: &str, rel_path: &str) -> PathBuf {
+    if dir.is_empty() || dir.ends_with(['/', '\\']) {
+        return format!("{dir}{rel_path}").into();
+    }
+    let sep = if dir.contains('\\') && !dir.contains('/') {
+        '\\'
+    } else {
+        '/'
+    };
+    format!("{dir}{sep}{rel_path}").into()
+}
+
+/// where map `id` is downloaded to relative to the maps directory, with
+/// `maps_subdir`
+pub(crate) fn download_path(subdir: Option<&str>, id: u64) -> String {
+    match subdir {
+        Some(subdir) => format!("{}/{}", subdir.trim_end_matches('/'), map_file_name(id)),
+        None => map_file_name(id),
+    }
+}
+
+impl Client {
+    /// the full path of `rel_path` inside the server's maps directory
+    pub fn maps_dir_path(&mut self, rel_path: &str) -> Result<PathBuf, ClientError> {
+        let dir: String = self.call("GetMapsDirectory", ())?;
+        Ok(in_maps_dir(&dir, rel_path))
+    }
+
+    /// the full path of `rel_path` inside the server's replay directory, which
+    /// is next to the maps directory
+    pub fn replays_dir_path(&mut self, rel_path: &str) -> Result<PathBuf, ClientError> {
+        let dir: String = self.call("GetMapsDirectory", ())?;
+        let dir = dir.trim_end_matches(['/', '\\']);
+        let user_data = dir
+            .rsplit_once(['/', '\\'])
+            .map_or("", |(parent, _)| parent);
+        Ok(in_maps_dir(user_data, &format!("Replays/{rel_path}")))
+    }
+
+    /// where a downloaded map ends up, relative to the maps directory
+    pub fn map_path(&self, id: u64) -> String {
+        download_path(self.config().maps_subdir.as_deref(), id)
+    }
+
+    /// makes sure we can download maps at all, creating `maps_subdir` if needed
+    ///
+    /// the maps directory is a path on the server's host, so this fails if
+    /// the controller runs somewhere else.
+    pub(crate) fn check_maps_directory(&mut self) -> Result<(), ClientError> {
+        let dir: String = self.call("GetMapsDirectory", ())?;
+        let mut path = PathBuf::from(&dir);
+        if let Some(subdir) = &self.config().maps_subdir {
+            path.push(subdir);
+        }
+        let probe = path.join(format!(".controller-{}", rand::random::<u32>()));
+        let res = fs::create_dir_all(&path).and_then(|()| fs::write(&probe, b""));
+        let _ = fs::remove_file(&probe);
+        res.map_err(|err| ClientError::MapsDirectory {
+            dir: path.display().to_string(),
+            err,
+        })
+    }
+
+    /// metadata for any map in the maps directory, `file_name` is relative to it
+    pub fn get_map_info(&mut self, file_name: &str) -> Result<MapInfo, ClientError> {
+        match self.call("GetMapInfo", file_name) {
+            Err(ClientError::Fault(fault))
+                if fault.string().to_lowercase().contains("not found") =>
+            {
+                Err(ClientError::MapNotFound(file_name.to_owned()))
+            }
+            res => res,
+        }
+    }
+
+    /// `/mapinfo <file>` shows what the server knows about a map file
+    pub(crate) fn mapinfo_command(
+        &mut self,
+        login: &str,
+        args: &[&str],
+    ) -> Result<(), ClientError> {
+        let [file_name] = args else {
+            return self.chat_send_to_login(login, "usage: /mapinfo <file>");
+        };
+        let reply = match self.get_map_info(file_name) {
+            Ok(info) => format!(
+                "{} by {} ({}), gold {:.3}s, uid {}",
+                info.Name,
+                info.AuthorNickname,
+                info.Environnement,
+                info.GoldTime as f64 / 1000.0,
+                info.UId
+            ),
+            Err(err @ ClientError::MapNotFound(_)) => err.to_string(),
+            Err(err) => return Err(err),
+        };
+        self.chat_send_to_login(login, &reply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use dxr::{TryFromValue, Value};
+
+    use super::in_maps_dir;
+    use crate::{
+        testing::{map_info, test_client, test_config, MockServer, Reply},
+        InsertMode,
+    };
+
+    #[test]
+    fn paths_join_with_or_without_a_separator() {
+        let expected = PathBuf::from("/srv/UserData/Maps/1.Map.Gbx");
+        assert_eq!(in_maps_dir("/srv/UserData/Maps", "1.Map.Gbx"), expected);
+        assert_eq!(in_maps_dir("/srv/UserData/Maps/", "1.Map.Gbx"), expected);
+    }
+
+    #[test]
+    fn windows_dirs_keep_their_separator() {
+        assert_eq!(
+            in_maps_dir("C:\\Server\\UserData\\Maps", "1.Map.Gbx"),
+            PathBuf::from("C:\\Server\\UserData\\Maps\\1.Map.Gbx")
+        );
+        assert_eq!(
+            in_maps_dir("C:\\Server\\UserData\\Maps\\", "1.Map.Gbx"),
+            PathBuf::from("C:\\Server\\UserData\\Maps\\1.Map.Gbx")
+        );
+        assert_eq!(in_maps_dir("", "1.Map.Gbx"), PathBuf::from("1.Map.Gbx"));
+    }
+
+    #[test]
+    fn the_insert_mode_decides_where_maps_go() {
+        let server = MockServer::start(|method, params| match method {
+            "GetMapInfo" => {
+                let file_name = String::try_from_value(&params[0]).unwrap();
+                Some(Reply::of(map_info(&file_name, "uid")))
+            }
+            _ => None,
+        });
+        let mut client = test_client(test_config(&server));
+
+        assert!(client.add_map("1.Map.Gbx", InsertMode::Pool).unwrap());
+        assert_eq!(
+            server.params_of("AddMap"),
+            [vec![Value::string("1.Map.Gbx".to_owned())]]
+        );
+        assert!(server.params_of("InsertMap").is_empty());
+        // maps in the pool come up whenever their turn is
+        assert!(client.expected_next.is_none());
+
+        assert!(client.add_map("2.Map.Gbx", InsertMode::Next).unwrap());
+        assert_eq!(
+            server.params_of("InsertMap"),
+            [vec![Value::string("2.Map.Gbx".to_owned())]]
+        );
+        assert!(client.expected_next.is_some());
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::eyre;
+
+use crate::{Client, MapCandidate, TmxMapMetadata};
+
+/// what tmx told us about recent maps, so we don't ask again
+///
+/// entries expire after `ttl`, so maps that were updated on tmx eventually
+/// show their new data. once full, the least recently used map is dropped.
+#[derive(Debug, Clone)]
+pub struct MetadataCache {
+    entries: HashMap<u64, (MapCandidate, Instant)>,
+    /// least recently used first
+    order: VecDeque<u64>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl MetadataCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        MetadataCache {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            capacity,
+            ttl,
+        }
+    }
+
+    /// forgets the least recently used maps if there are more than `capacity`
+    pub fn set_limits(&mut self, capacity: usize, ttl: Duration) {
+        self.capacity = capacity;
+        self.ttl = ttl;
+        self.evict();
+    }
+
+    /// `None` if the map is not cached or its entry expired
+    pub fn get(&mut self, id: u64) -> Option<&MapCandidate> {
+        let fetched = self.entries.get(&id)?.1;
+        if fetched.elapsed() >= self.ttl {
+            self.remove(id);
+            return None;
+        }
+        self.touch(id);
+        self.entries.get(&id).map(|(candidate, _)| candidate)
+    }
+
+    pub fn insert(&mut self, candidate: MapCandidate) {
+        if self.capacity == 0 {
+            return;
+        }
+        let id = candidate.id;
+        if self
+            .entries
+            .insert(id, (candidate, Instant::now()))
+            .is_some()
+        {
+            self.touch(id);
+            return;
+        }
+        self.order.push_back(id);
+        self.evict();
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.entries.remove(&id);
+        self.order.retain(|&other| other != id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn evict(&mut self) {
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    /// moves `id` to the back of the queue
+    fn touch(&mut self, id: u64) {
+        if let Some(pos) = self.order.iter().position(|&other| other == id) {
+            self.order.remove(pos);
+            self.order.push_back(id);
+        }
+    }
+}
+
+impl Client {
+    /// what tmx knows about the map, from the cache if we asked recently
+    pub fn tmx_metadata(&mut self, id: u64) -> color_eyre::Result<MapCandidate> {
+        if let Some(candidate) = self.metadata.get(id) {
+            return Ok(candidate.clone());
+        }
+        if self.config().offline {
+            return Err(eyre!(
+                "no metadata of map {id} cached, not asking tmx offline"
+            ));
+        }
+        self.stats.tmx_requests += 1;
+        let url = format!("https://trackmania.exchange/api/maps/get_map_info/id/{id}");
+        let res = match self
+            .exchange()
+            .get(url)
+            .send()
+            .and_then(|r| r.error_for_status())
+        {
+            Ok(res) => res,
+            Err(err) => {
+                self.stats.tmx_failures += 1;
+                return Err(err.into());
+            }
+        };
+        let candidate = serde_json::from_str::<TmxMapMetadata>(&res.text()?)?.into_candidate()?;
+        self.metadata.insert(candidate.clone());
+        Ok(candidate)
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+use crate::{client::SentCall, Client, HttpResponse};
+
+/// upper bounds of the call latency buckets, in seconds
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// how long calls took to be answered, in prometheus buckets
+#[derive(Debug, Clone)]
+pub(crate) struct Histogram {
+    /// not cumulative, the last one is for everything above the last bound
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            counts: vec![0; LATENCY_BUCKETS.len() + 1],
+            sum: 0.0,
+        }
+    }
+}
+
+impl Histogram {
+    pub(crate) fn observe(&mut self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&bound| secs <= bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.counts[bucket] += 1;
+        self.sum += secs;
+    }
+
+    pub(crate) fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub(crate) fn mean(&self) -> Option<Duration> {
+        let count = self.count();
+        (count > 0).then(|| Duration::from_secs_f64(self.sum / count as f64))
+    }
+
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let mut total = 0;
+        for (count, bound) in self.counts.iter().zip(LATENCY_BUCKETS) {
+            total += count;
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {total}");
+        }
+        total += self.counts[LATENCY_BUCKETS.len()];
+        let _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {total}");
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{{{labels}}} {total}");
+    }
+}
+
+impl Client {
+    /// records how long `call` took to be answered, and logs it if it was slow
+    pub(crate) fn observe_call(&mut self, call: &SentCall, received: Instant) {
+        let latency = received.saturating_duration_since(call.sent);
+        self.stats.rpc_latency.observe(latency);
+        if self
+            .stats
+            .slowest_call
+            .as_ref()
+            .is_none_or(|(_, slowest)| latency > *slowest)
+        {
+            self.stats.slowest_call = Some((call.method.clone(), latency));
+        }
+        let threshold = self.config().slow_call_ms;
+        if threshold > 0 && latency >= Duration::from_millis(threshold) {
+            say!("slow call: {} took {}ms", call.method, latency.as_millis());
+        }
+    }
+
+    /// the counters behind `/metrics`, in the prometheus text format
+    ///
+    /// the rotation size costs a call, it is left out if that fails.
+    pub(crate) fn metrics(&mut self) -> HttpResponse {
+        let rotation = self.get_map_list().map(|maps| maps.len()).ok();
+        let stats = &self.stats;
+        let labels = format!("server=\"{}\"", self.config().server_id().replace('"', "'"));
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+            let _ = writeln!(out, "{name}{{{labels}}} {value}");
+        };
+        metric(
+            "controller_maps_downloaded_total",
+            "counter",
+            "maps downloaded from tmx",
+            stats.maps_downloaded.to_string(),
+        );
+        metric(
+            "controller_tmx_requests_total",
+            "counter",
+            "searches and downloads sent to tmx",
+            stats.tmx_requests.to_string(),
+        );
+        metric(
+            "controller_tmx_failures_total",
+            "counter",
+            "tmx requests that failed",
+            stats.tmx_failures.to_string(),
+        );
+        metric(
+            "controller_rpc_faults_total",
+            "counter",
+            "calls the server answered with a fault",
+            stats.rpc_faults.to_string(),
+        );
+        metric(
+            "controller_reconnects_total",
+            "counter",
+            "times the connection was lost and got back",
+            stats.reconnects.to_string(),
+        );
+        if let Some(rtt) = stats.ping_rtt {
+            metric(
+                "controller_keepalive_rtt_seconds",
+                "gauge",
+                "how long the last keep-alive took to be answered",
+                rtt.as_secs_f64().to_string(),
+            );
+        }
+        if let Some(rtt) = stats.echo_rtt {
+            metric(
+                "controller_echo_rtt_seconds",
+                "gauge",
+                "how long the last keep-alive echo took to come back",
+                rtt.as_secs_f64().to_string(),
+            );
+        }
+        metric(
+            "controller_rpc_in_flight_max",
+            "gauge",
+            "the most calls that waited for a response at once",
+            stats.max_in_flight.to_string(),
+        );
+        metric(
+            "controller_rpc_in_flight_waits_total",
+            "counter",
+            "calls that waited for max_in_flight_calls others to be answered",
+            stats.in_flight_waits.to_string(),
+        );
+        metric(
+            "controller_players",
+            "gauge",
+            "players and spectators on the server",
+            self.players.len().to_string(),
+        );
+        metric(
+            "controller_spectators",
+            "gauge",
+            "spectators on the server",
+            self.spectators.len().to_string(),
+        );
+        if let Some(rotation) = rotation {
+            metric(
+                "controller_rotation_size",
+                "gauge",
+                "maps in the rotation",
+                rotation.to_string(),
+            );
+        }
+        let name = "controller_rpc_latency_seconds";
+        out += &format!("# HELP {name} time until the server answered a call\n");
+        out += &format!("# TYPE {name} histogram\n");
+        stats.rpc_latency.write(&mut out, name, &labels);
+        HttpResponse {
+            status: 200,
+            content_type: "text/plain; version=0.0.4",
+            body: out.into_bytes(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Histogram;
+
+    #[test]
+    fn buckets_count_everything_up_to_their_bound() {
+        let mut histogram = Histogram::default();
+        // on a bound, between two, and above the last one
+        for millis in [5, 30, 30, 10_000] {
+            histogram.observe(Duration::from_millis(millis));
+        }
+        assert_eq!(histogram.count(), 4);
+
+        let mut out = String::new();
+        histogram.write(&mut out, "latency", "a=\"b\"");
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "latency_bucket{a=\"b\",le=\"0.005\"} 1");
+        assert_eq!(lines[2], "latency_bucket{a=\"b\",le=\"0.025\"} 1");
+        assert_eq!(lines[3], "latency_bucket{a=\"b\",le=\"0.05\"} 3");
+        assert_eq!(lines[9], "latency_bucket{a=\"b\",le=\"5\"} 3");
+        assert_eq!(lines[10], "latency_bucket{a=\"b\",le=\"+Inf\"} 4");
+        assert_eq!(lines[12], "latency_count{a=\"b\"} 4");
+    }
+}
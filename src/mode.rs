@@ -0,0 +1,315 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use dxr::{TryFromValue, Value};
+use quick_xml::{events::Event, Reader};
+
+use crate::{Client, ClientError, CurrentNext, GameMode};
+
+/// a setting or command a script mode accepts
+#[allow(non_snake_case)]
+#[derive(TryFromValue, Debug, Clone)]
+pub struct ModeScriptParam {
+    pub Name: String,
+    pub Desc: String,
+    /// like "integer", "boolean" or "text"
+    pub Type: String,
+    /// formatted as text, whatever the type
+    pub Default: String,
+}
+
+/// what the running script mode says about itself
+#[allow(non_snake_case)]
+#[derive(TryFromValue, Debug, Clone)]
+pub struct ModeScriptInfo {
+    pub Name: String,
+    pub CompatibleMapTypes: String,
+    pub Description: String,
+    pub Version: String,
+    pub ParamDescs: Vec<ModeScriptParam>,
+    pub CommandDescs: Vec<ModeScriptParam>,
+}
+
+impl ModeScriptInfo {
+    /// the tmx map types the mode can play, like `TM_Race`
+    pub fn map_types(&self) -> impl Iterator<Item = &str> {
+        // the server sends them as `TrackMania\TM_Race,TM_Race`
+        self.CompatibleMapTypes
+            .split(',')
+            .map(|t| t.rsplit('\\').next().unwrap_or(t).trim())
+            .filter(|t| !t.is_empty())
+    }
+
+    pub fn setting(&self, name: &str) -> Option<&ModeScriptParam> {
+        self.ParamDescs.iter().find(|p| p.Name == name)
+    }
+
+    /// the keys the mode would not know what to do with
+    pub fn unknown_settings<'a>(&self, settings: &'a HashMap<String, Value>) -> Vec<&'a str> {
+        let mut unknown: Vec<&str> = settings
+            .keys()
+            .map(String::as_str)
+            .filter(|name| self.setting(name).is_none())
+            .collect();
+        unknown.sort_unstable();
+        unknown
+    }
+}
+
+impl Client {
+    /// faults if the server is not running a script mode
+    pub fn get_mode_script_info(&mut self) -> Result<ModeScriptInfo, ClientError> {
+        self.call("GetModeScriptInfo", ())
+    }
+
+    /// `/settings` lists what the mode lets us change, the descriptions only go
+    /// to the log
+    pub(crate) fn settings_command(&mut self, login: &str) -> Result<(), ClientError> {
+        let info = match self.get_mode_script_info() {
+            Ok(info) => info,
+            Err(err @ ClientError::Fault(_)) => {
+                return self.chat_send_to_login(login, &err.to_string());
+            }
+            Err(err) => return Err(err),
+        };
+        for param in &info.ParamDescs {
+            say!(
+                "{} ({}, default {}): {}",
+                param.Name,
+                param.Type,
+                param.Default,
+                param.Desc
+            );
+        }
+        let settings: Vec<String> = info
+            .ParamDescs
+            .iter()
+            .map(|p| format!("{}={}", p.Name, p.Default))
+            .collect();
+        let reply = format!("{} {}: {}", info.Name, info.Version, settings.join(", "));
+        self.chat_send_to_login(login, &reply)
+    }
+
+    /// faults if the server is not running a script mode
+    pub fn get_mode_script_settings(&mut self) -> Result<HashMap<String, Value>, ClientError> {
+        self.call("GetModeScriptSettings", ())
+    }
+
+    /// only the given settings are changed, the others keep their value
+    pub fn set_mode_script_settings(
+        &mut self,
+        settings: HashMap<String, Value>,
+    ) -> Result<(), ClientError> {
+        let suc: bool = self.call("SetModeScriptSettings", settings)?;
+        if !suc {
+            return Err(ClientError::Rejected("SetModeScriptSettings"));
+        }
+        Ok(())
+    }
+
+    /// changes settings and sends commands in one call, so no map change can
+    /// come between them
+    ///
+    /// fails with [`ClientError::NotScriptMode`] before sending anything if
+    /// the server runs a legacy mode.
+    pub fn set_mode_script_settings_and_commands(
+        &mut self,
+        settings: HashMap<String, Value>,
+        commands: HashMap<String, Value>,
+    ) -> Result<(), ClientError> {
+        let mode = self.get_current_game_info()?.game_mode();
+        if mode != GameMode::Script {
+            return Err(ClientError::NotScriptMode(mode));
+        }
+        let suc: bool = self.call("SetModeScriptSettingsAndCommands", (settings, commands))?;
+        if !suc {
+            return Err(ClientError::Rejected("SetModeScriptSettingsAndCommands"));
+        }
+        Ok(())
+    }
+
+    pub fn get_script_name(&mut self) -> Result<CurrentNext<String>, ClientError> {
+        self.call("GetScriptName", ())
+    }
+
+    /// the mode is only loaded with the next map
+    pub fn set_script_name(&mut self, file_name: &str) -> Result<(), ClientError> {
+        let suc: bool = self.call("SetScriptName", file_name)?;
+        if !suc {
+            return Err(ClientError::Rejected("SetScriptName"));
+        }
+        self.pending_mode = Some(file_name.to_owned());
+        Ok(())
+    }
+
+    /// the settings of modes that take them as one xml text
+    pub fn get_mode_script_text(&mut self) -> Result<String, ClientError> {
+        self.call("GetModeScriptText", ())
+    }
+
+    /// a broken text could break the mode, so it is only sent if it is well formed
+    pub fn set_mode_script_text(&mut self, xml: &str) -> Result<(), ClientError> {
+        check_xml(xml).map_err(|err| ClientError::Encode(format!("invalid xml: {err}")))?;
+        let suc: bool = self.call("SetModeScriptText", xml)?;
+        if !suc {
+            return Err(ClientError::Rejected("SetModeScriptText"));
+        }
+        Ok(())
+    }
+
+    /// `/loadmode <file>` sends a file from the directory the controller runs
+    /// in with [`Client::set_mode_script_text`]
+    pub(crate) fn loadmode_command(
+        &mut self,
+        login: &str,
+        args: &[&str],
+    ) -> Result<(), ClientError> {
+        let [file_name] = args else {
+            return self.chat_send_to_login(login, "usage: /loadmode <file>");
+        };
+        // chat should not get to read anywhere else
+        if Path::new(file_name).file_name() != Some(file_name.as_ref()) {
+            return self.chat_send_to_login(login, "only plain file names are allowed");
+        }
+        let xml = match fs::read_to_string(file_name) {
+            Ok(xml) => xml,
+            Err(err) => {
+                return self
+                    .chat_send_to_login(login, &format!("could not read {file_name}: {err}"));
+            }
+        };
+        let reply = match self.set_mode_script_text(&xml) {
+            Ok(()) => format!("loaded {file_name}"),
+            Err(ClientError::Fault(fault)) => {
+                format!(
+                    "the server is not running a script mode: {}",
+                    fault.string()
+                )
+            }
+            Err(err) if !err.is_connection() => err.to_string(),
+            Err(err) => return Err(err),
+        };
+        self.chat_send_to_login(login, &reply)
+    }
+
+    /// once a new mode is loaded, makes sure the maps we pick next fit it
+    pub(crate) fn check_mode_change(&mut self) -> Result<(), ClientError> {
+        let Some(requested) = self.pending_mode.take() else {
+            return Ok(());
+        };
+        let script = self.get_script_name()?;
+        if script.CurrentValue != requested {
+            say!(
+                "{requested} did not load, the mode is still {}",
+                script.CurrentValue
+            );
+            return Ok(());
+        }
+        let info = self.get_mode_script_info()?;
+        let map_type = self.config().search.map_type.clone();
+        if !info.map_types().any(|t| t == map_type) {
+            if let Some(new_type) = info.map_types().next().map(str::to_owned) {
+                let mut search = self.config().search.clone();
+                search.map_type = new_type;
+                self.set_search(search);
+            }
+        }
+        say!(
+            "now playing {}, picking {} maps",
+            info.Name,
+            self.config().search.map_type
+        );
+        Ok(())
+    }
+
+    /// `/mode` shows the script, `/mode <file>` switches to another one from
+    /// the next map on
+    pub(crate) fn mode_command(&mut self, login: &str, args: &[&str]) -> Result<(), ClientError> {
+        let reply = match args {
+            [] => {
+                let script = self.get_script_name()?;
+                if script.CurrentValue == script.NextValue {
+                    format!("playing {}", script.CurrentValue)
+                } else {
+                    format!(
+                        "playing {}, {} from the next map on",
+                        script.CurrentValue, script.NextValue
+                    )
+                }
+            }
+            [file_name] => match self.set_script_name(file_name) {
+                Ok(()) => format!("switching to {file_name} from the next map on"),
+                Err(err) if !err.is_connection() => err.to_string(),
+                Err(err) => return Err(err),
+            },
+            _ => "usage: /mode [script file]".to_owned(),
+        };
+        self.chat_send_to_login(login, &reply)
+    }
+
+    /// sends an event to the mode script, which might answer with a
+    /// [`crate::Callback::Script`]
+    pub fn trigger_mode_script_event(
+        &mut self,
+        name: &str,
+        param: &str,
+    ) -> Result<(), ClientError> {
+        let suc: bool = self.call("TriggerModeScriptEvent", (name, param))?;
+        if !suc {
+            return Err(ClientError::Rejected("TriggerModeScriptEvent"));
+        }
+        Ok(())
+    }
+
+    pub fn trigger_mode_script_event_array(
+        &mut self,
+        name: &str,
+        params: &[&str],
+    ) -> Result<(), ClientError> {
+        let params: Vec<&str> = params.to_vec();
+        let suc: bool = self.call("TriggerModeScriptEventArray", (name, params))?;
+        if !suc {
+            return Err(ClientError::Rejected("TriggerModeScriptEventArray"));
+        }
+        Ok(())
+    }
+
+    /// asks the mode for a `Trackmania.Scores` callback
+    ///
+    /// returns the response id, which the callback's `responseid` repeats.
+    pub fn request_scores(&mut self) -> Result<String, ClientError> {
+        let response_id = format!("scores-{}", rand::random::<u32>());
+        self.trigger_mode_script_event_array("Trackmania.GetScores", &[&response_id])?;
+        Ok(response_id)
+    }
+
+    /// without this, the mode keeps its events to itself
+    pub(crate) fn enable_script_callbacks(&mut self) -> Result<(), ClientError> {
+        self.trigger_mode_script_event_array("XmlRpc.EnableCallbacks", &["true"])
+    }
+}
+
+/// whether `xml` has exactly one root element and every tag is closed
+fn check_xml(xml: &str) -> Result<(), String> {
+    let mut reader = Reader::from_str(xml);
+    let (mut depth, mut roots) = (0usize, 0);
+    loop {
+        match reader.read_event().map_err(|err| err.to_string())? {
+            Event::Start(_) => {
+                if depth == 0 {
+                    roots += 1;
+                }
+                depth += 1;
+            }
+            Event::End(_) => depth -= 1,
+            Event::Empty(_) if depth == 0 => roots += 1,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    match (depth, roots) {
+        (0, 1) => Ok(()),
+        (0, 0) => Err("no root element".to_owned()),
+        (0, _) => Err("more than one root element".to_owned()),
+        _ => Err("unclosed element".to_owned()),
+    }
+}
@@ -0,0 +1,52 @@
+use dxr::{TryFromValue, TryToValue};
+use serde::Deserialize;
+
+use crate::{Client, ClientError};
+
+/// a texture mod that players download when joining
+#[allow(non_snake_case)]
+#[derive(TryFromValue, TryToValue, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ForcedMod {
+    #[serde(rename = "env")]
+    pub EnvName: String,
+    #[serde(rename = "url")]
+    pub Url: String,
+}
+
+#[allow(non_snake_case)]
+#[derive(TryFromValue, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ForcedMods {
+    /// whether the mods replace the ones that come with the map
+    #[serde(rename = "override")]
+    pub Override: bool,
+    #[serde(rename = "mods")]
+    pub Mods: Vec<ForcedMod>,
+}
+
+impl Client {
+    pub fn get_forced_mods(&mut self) -> Result<ForcedMods, ClientError> {
+        self.call("GetForcedMods", ())
+    }
+
+    /// mod urls that can not be reached are still applied, but we warn about them
+    pub fn set_forced_mods(&mut self, mods: &ForcedMods) -> Result<(), ClientError> {
+        for m in &mods.Mods {
+            if let Err(err) = self.check_url(&m.Url) {
+                say!("mod {} for {} looks unreachable: {err}", m.Url, m.EnvName);
+            }
+        }
+
+        let suc: bool = self.call("SetForcedMods", (mods.Override, mods.Mods.clone()))?;
+        if !suc {
+            return Err(ClientError::Rejected("SetForcedMods"));
+        }
+        Ok(())
+    }
+
+    fn check_url(&self, url: &str) -> reqwest::Result<()> {
+        self.exchange().head(url).send()?.error_for_status()?;
+        Ok(())
+    }
+}
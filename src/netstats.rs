@@ -0,0 +1,61 @@
+use dxr::TryFromValue;
+use serde::Serialize;
+
+use crate::{Client, ClientError};
+
+#[allow(non_snake_case)]
+#[derive(TryFromValue, Serialize, Debug, Clone)]
+pub struct PlayerNetInfo {
+    pub Login: String,
+    pub IPAddress: String,
+    pub LastTransferTime: i32,
+    pub DeltaBetweenTwoLastNetState: i32,
+    pub PacketLossRate: f64,
+}
+
+#[allow(non_snake_case)]
+#[derive(TryFromValue, Serialize, Debug, Clone)]
+pub struct NetworkStats {
+    /// in seconds
+    pub Uptime: i32,
+    pub NbrConnection: i32,
+    /// in seconds
+    pub MeanConnectionTime: i32,
+    pub MeanNbrPlayer: i32,
+    /// in bytes per second
+    pub RecvNetRate: i32,
+    pub SendNetRate: i32,
+    pub PlayerNetInfos: Vec<PlayerNetInfo>,
+}
+
+impl Client {
+    pub fn get_network_stats(&mut self) -> Result<NetworkStats, ClientError> {
+        self.call("GetNetworkStats", ())
+    }
+
+    pub(crate) fn netstats_command(&mut self, login: &str) -> Result<(), ClientError> {
+        let stats = self.get_network_stats()?;
+        let reply = format!(
+            "up {}, {} connections, {} players, recv {}, send {}",
+            format_duration(stats.Uptime),
+            stats.NbrConnection,
+            stats.PlayerNetInfos.len(),
+            format_rate(stats.RecvNetRate),
+            format_rate(stats.SendNetRate),
+        );
+        self.chat_send_to_login(login, &reply)
+    }
+}
+
+fn format_rate(bytes_per_sec: i32) -> String {
+    format!("{:.1} KB/s", bytes_per_sec as f64 / 1024.0)
+}
+
+pub(crate) fn format_duration(secs: i32) -> String {
+    let (h, m) = (secs / 3600, secs % 3600 / 60);
+    if h > 0 {
+        format!("{h}h{m:02}m")
+    } else {
+        format!("{m}m")
+    }
+}
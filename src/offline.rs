@@ -0,0 +1,82 @@
+use std::{fmt, fs, path::PathBuf};
+
+use color_eyre::eyre::eyre;
+use rand::seq::SliceRandom;
+
+use crate::{
+    control::map_file_name, tmx_id_of, GbxMapHeader, History, MapCandidate, MapSearch, MapSource,
+};
+
+/// picks maps from the files in the download cache, for when tmx can not be
+/// reached
+///
+/// only the tmx id and what the header of the file says are known of those
+/// maps, so the search and the tag weights do not apply.
+pub struct CacheMapSource {
+    dir: Option<PathBuf>,
+    /// whether we already warned that every cached map was played recently
+    dry: bool,
+}
+
+impl CacheMapSource {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        CacheMapSource { dir, dry: false }
+    }
+
+    /// the tmx ids of every map in the cache
+    pub fn cached_ids(&self) -> color_eyre::Result<Vec<u64>> {
+        let dir = self
+            .dir
+            .as_ref()
+            .ok_or_else(|| eyre!("offline mode needs a download_cache"))?;
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            if let Some(id) = tmx_id_of(&entry?.file_name().to_string_lossy()) {
+                ids.push(id);
+            }
+        }
+        Ok(ids)
+    }
+}
+
+impl MapSource for CacheMapSource {
+    fn next_candidate(
+        &mut self,
+        _search: &MapSearch,
+        history: &History,
+    ) -> color_eyre::Result<MapCandidate> {
+        let mut ids = self.cached_ids()?;
+        ids.retain(|&id| !history.contains(id));
+        let Some(&id) = ids.choose(&mut rand::thread_rng()) else {
+            if !self.dry {
+                say!("every cached map was played recently, the rotation stays as it is");
+                self.dry = true;
+            }
+            return Err(CacheDry.into());
+        };
+        self.dry = false;
+        let mut candidate = MapCandidate::from_id(id);
+        // the name and environment, for the logs and the environment filter
+        let file = self.dir.as_ref().map(|dir| dir.join(map_file_name(id)));
+        if let Some(header) = file
+            .and_then(|file| fs::read(file).ok())
+            .as_deref()
+            .and_then(GbxMapHeader::parse)
+        {
+            header.describe(&mut candidate);
+        }
+        Ok(candidate)
+    }
+}
+
+/// the download cache has no map left that was not played recently
+#[derive(Debug)]
+pub struct CacheDry;
+
+impl fmt::Display for CacheDry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no unplayed map left in the download cache")
+    }
+}
+
+impl std::error::Error for CacheDry {}
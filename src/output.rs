@@ -0,0 +1,174 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use crate::password::redact_message;
+
+thread_local! {
+    static PREFIX: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// shared by every thread, so servers in one process share one file
+static LOG_FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+static TRACE_FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+static DEBUG: AtomicBool = AtomicBool::new(false);
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// every line printed with [`say!`](crate::say) on this thread starts with `[id]`
+///
+/// used to tell servers apart when one process manages several.
+pub fn set_log_prefix(id: impl Into<String>) {
+    PREFIX.with(|p| *p.borrow_mut() = Some(id.into()));
+}
+
+/// whether [`set_log_prefix`] was called on this thread
+pub(crate) fn has_log_prefix() -> bool {
+    PREFIX.with(|p| p.borrow().is_some())
+}
+
+/// [`say!`](crate::say) also writes to this file from now on, next to stdout
+pub fn set_log_file(file: Option<LogFile>) {
+    *LOG_FILE.lock().unwrap() = file;
+}
+
+/// [`say!`](crate::say) prints to stderr instead of stdout from now on
+///
+/// with the control channel on stdin, stdout is left to its replies.
+pub fn set_log_to_stderr(enabled: bool) {
+    TO_STDERR.store(enabled, Ordering::Relaxed);
+}
+
+/// whether [`debug!`](crate::debug) prints anything
+pub fn set_debug_log(enabled: bool) {
+    DEBUG.store(enabled, Ordering::Relaxed);
+}
+
+#[doc(hidden)]
+pub fn debug_enabled() -> bool {
+    DEBUG.load(Ordering::Relaxed)
+}
+
+/// every message sent to and received from the server goes here
+pub fn set_protocol_trace(file: Option<LogFile>) {
+    *TRACE_FILE.lock().unwrap() = file;
+}
+
+fn with_prefix(args: fmt::Arguments) -> String {
+    PREFIX.with(|p| match &*p.borrow() {
+        Some(id) => format!("[{id}] {args}"),
+        None => args.to_string(),
+    })
+}
+
+#[doc(hidden)]
+pub fn print_line(args: fmt::Arguments) {
+    let line = with_prefix(args);
+    if TO_STDERR.load(Ordering::Relaxed) {
+        eprintln!("{line}");
+    } else {
+        println!("{line}");
+    }
+    if let Some(file) = &mut *LOG_FILE.lock().unwrap() {
+        file.write_line(&line);
+    }
+}
+
+/// `out` tells whether we sent the message or received it, passwords are
+/// left out
+pub(crate) fn trace(out: bool, handle: u32, msg: &str) {
+    if let Some(file) = &mut *TRACE_FILE.lock().unwrap() {
+        let arrow = if out { "->" } else { "<-" };
+        let msg = redact_message(msg);
+        file.write_line(&with_prefix(format_args!("{arrow} {handle:#x} {msg}")));
+    }
+}
+
+/// a file that is rotated once it grows past `max_bytes`
+///
+/// the old contents move to `path.1`, the one before to `path.2` and so on,
+/// up to `keep` of them.
+#[derive(Debug)]
+pub struct LogFile {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    /// appends to `path` if it exists already
+    pub fn open(path: impl Into<PathBuf>, max_bytes: u64, keep: usize) -> io::Result<LogFile> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(LogFile {
+            path,
+            max_bytes,
+            keep,
+            file,
+            size,
+        })
+    }
+
+    /// failures are ignored, the log has nowhere to report them
+    pub fn write_line(&mut self, line: &str) {
+        let time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+        let line = format!("{time} {line}\n");
+        if self.size > 0 && self.size + line.len() as u64 > self.max_bytes {
+            let _ = self.rotate();
+        }
+        if self.file.write_all(line.as_bytes()).is_ok() {
+            self.size += line.len() as u64;
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep == 0 {
+            self.file.set_len(0)?;
+        } else {
+            for n in (1..self.keep).rev() {
+                let _ = fs::rename(rotated(&self.path, n), rotated(&self.path, n + 1));
+            }
+            fs::rename(&self.path, rotated(&self.path, 1))?;
+            self.file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    name.into()
+}
+
+/// like `println!`, but with the prefix set by [`set_log_prefix`]
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        $crate::print_line(format_args!($($arg)*))
+    };
+}
+
+/// like [`say!`], but only with `debug_log` set in the config
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::debug_enabled() {
+            $crate::say!($($arg)*)
+        }
+    };
+}
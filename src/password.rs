@@ -0,0 +1,175 @@
+use std::borrow::Cow;
+
+use dxr::{MethodCall, TryFromValue, Value};
+
+use crate::{callback::callback_names, method_allowed, Client, ClientError};
+
+/// calls with a password in their params, which the protocol trace leaves out
+const PASSWORD_CALLS: &[&str] = &[
+    "Authenticate",
+    "SetServerPassword*",
+    "SetRefereePassword",
+    "SetServerOptions",
+];
+
+impl Client {
+    /// empty when the server is not locked
+    pub fn get_server_password(&mut self) -> Result<String, ClientError> {
+        self.call("GetServerPassword", ())
+    }
+
+    /// an empty password unlocks the server
+    ///
+    /// reads the password back, since the server can accept the call and
+    /// still keep the old one.
+    pub fn set_server_password(&mut self, password: &str) -> Result<(), ClientError> {
+        self.set_and_check("SetServerPassword", "GetServerPassword", password)
+    }
+
+    pub fn get_server_password_for_spectator(&mut self) -> Result<String, ClientError> {
+        self.call("GetServerPasswordForSpectator", ())
+    }
+
+    pub fn set_server_password_for_spectator(&mut self, password: &str) -> Result<(), ClientError> {
+        self.set_and_check(
+            "SetServerPasswordForSpectator",
+            "GetServerPasswordForSpectator",
+            password,
+        )
+    }
+
+    fn set_and_check(
+        &mut self,
+        set: &'static str,
+        get: &'static str,
+        password: &str,
+    ) -> Result<(), ClientError> {
+        let suc: bool = self.call(set, password)?;
+        if !suc || self.call::<String>(get, ())? != password {
+            return Err(ClientError::Rejected(set));
+        }
+        Ok(())
+    }
+
+    /// `/lock <password> [spectator password]`
+    ///
+    /// the passwords are never repeated in replies or logs.
+    pub(crate) fn lock_command(&mut self, login: &str, args: &[&str]) -> Result<(), ClientError> {
+        match args {
+            [players] => self.lock(login, players, ""),
+            [players, spectators] => self.lock(login, players, spectators),
+            _ => self.chat_send_to_login(login, "usage: /lock <password> [spectator password]"),
+        }
+    }
+
+    pub(crate) fn unlock_command(&mut self, login: &str) -> Result<(), ClientError> {
+        self.lock(login, "", "")
+    }
+
+    fn lock(&mut self, login: &str, players: &str, spectators: &str) -> Result<(), ClientError> {
+        let res = self
+            .set_server_password(players)
+            .and_then(|()| self.set_server_password_for_spectator(spectators));
+
+        let reply = match res {
+            Ok(()) if players.is_empty() => "server unlocked".to_owned(),
+            Ok(()) if spectators.is_empty() => "server locked for players".to_owned(),
+            Ok(()) => "server locked for players and spectators".to_owned(),
+            Err(err) if !err.is_connection() => err.to_string(),
+            Err(err) => return Err(err),
+        };
+        say!("{}: {reply}", self.display_name(login));
+        self.chat_send_to_login(login, &reply)
+    }
+}
+
+/// hides the passwords of `/lock` commands in `PlayerChat` params, before we
+/// pass them on to others
+pub(crate) fn redact_chat(params: &[Value]) -> Cow<'_, [Value]> {
+    let text = params.get(2).and_then(|p| String::try_from_value(p).ok());
+    match text {
+        Some(text) if text.starts_with("/lock ") => {
+            let mut params = params.to_vec();
+            params[2] = Value::string("/lock ***".to_owned());
+            Cow::Owned(params)
+        }
+        _ => Cow::Borrowed(params),
+    }
+}
+
+/// `msg` without the passwords of [`PASSWORD_CALLS`] and `/lock` commands,
+/// for the protocol trace
+pub(crate) fn redact_message(msg: &str) -> Cow<'_, str> {
+    let Some((method, _)) = callback_names(msg) else {
+        return Cow::Borrowed(msg);
+    };
+    if method_allowed(PASSWORD_CALLS, method) {
+        let redacted = format!(
+            "<methodCall><methodName>{method}</methodName><params>***</params></methodCall>"
+        );
+        return Cow::Owned(redacted);
+    }
+    if method != "ManiaPlanet.PlayerChat" {
+        return Cow::Borrowed(msg);
+    }
+    let Ok(call) = dxr::deserialize_xml::<MethodCall>(msg) else {
+        return Cow::Borrowed(msg);
+    };
+    match redact_chat(&call.params()) {
+        Cow::Owned(params) => {
+            let call = MethodCall::new(method.to_owned(), params);
+            dxr::serialize_xml(&call).map_or(Cow::Borrowed(msg), Cow::Owned)
+        }
+        Cow::Borrowed(_) => Cow::Borrowed(msg),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dxr::Value;
+
+    use super::redact_message;
+    use crate::{
+        testing::{callback_xml, chat_xml, test_client, test_config, MockServer},
+        Role,
+    };
+
+    #[test]
+    fn traces_leave_out_passwords() {
+        let lock = chat_xml("alice", "/lock secret");
+        assert!(!redact_message(&lock).contains("secret"));
+        assert!(redact_message(&lock).contains("/lock ***"));
+        let set = callback_xml(
+            "SetServerPasswordForSpectator",
+            vec![Value::string("secret".to_owned())],
+        );
+        assert!(!redact_message(&set).contains("secret"));
+        assert!(redact_message(&set).contains("SetServerPasswordForSpectator"));
+        let chat = chat_xml("alice", "secret");
+        assert_eq!(redact_message(&chat), chat);
+    }
+
+    #[test]
+    fn mods_can_lock_the_server_in_safe_mode() {
+        let server = MockServer::start(|_, _| None);
+        let mut config = test_config(&server);
+        assert!(config.safe_mode);
+        config.roles.insert("alice".to_owned(), Role::Mod);
+        let mut client = test_client(config);
+
+        client
+            .handle_callback(&chat_xml("alice", "/lock pw"), 0)
+            .unwrap();
+        assert_eq!(
+            server.params_of("SetServerPassword"),
+            vec![vec![Value::string("pw".to_owned())]]
+        );
+        client
+            .handle_callback(&chat_xml("bob", "/unlock"), 0)
+            .unwrap();
+        assert_eq!(server.params_of("SetServerPassword").len(), 1);
+        // without a role check, safe mode still blocks it
+        let res = client.as_untrusted(|c| c.call::<bool>("SetServerPassword", ""));
+        assert!(matches!(res, Err(crate::ClientError::SafeModeBlocked(_))));
+    }
+}
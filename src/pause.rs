@@ -0,0 +1,88 @@
+use crate::{Client, ClientError, MapInfo};
+
+impl Client {
+    /// whether the current map stays, see [`Client::pause_rotation`]
+    pub fn is_rotation_paused(&self) -> bool {
+        self.rotation_paused
+    }
+
+    /// keeps the current map, it restarts when it ends
+    ///
+    /// nothing is downloaded, inserted or voted on until the rotation is
+    /// resumed. returns false if it was paused already.
+    pub fn pause_rotation(&mut self) -> Result<bool, ClientError> {
+        if self.rotation_paused {
+            return Ok(false);
+        }
+        self.rotation_paused = true;
+        // whatever would have come next does not
+        self.vote = None;
+        self.expected_next = None;
+        self.drop_map_switch();
+        self.hold_current_map()?;
+        self.call::<bool>(
+            "ChatSendServerMessage",
+            "the rotation is paused, this map stays until it is resumed",
+        )?;
+        Ok(true)
+    }
+
+    /// lets the rotation go on after this map, without switching right away
+    ///
+    /// returns false if it was not paused.
+    pub fn resume_rotation(&mut self) -> Result<bool, ClientError> {
+        if !self.rotation_paused {
+            return Ok(false);
+        }
+        self.rotation_paused = false;
+        self.release_current_map()?;
+        self.call::<bool>(
+            "ChatSendServerMessage",
+            "the rotation is resumed, the next map comes after this one",
+        )?;
+        Ok(true)
+    }
+
+    /// makes the current map the next one as well, again on every `BeginMap`
+    /// while paused
+    pub(crate) fn hold_current_map(&mut self) -> Result<(), ClientError> {
+        // we might have connected in the middle of it
+        let file_name = match &self.current_map {
+            Some(map) => map.FileName.clone(),
+            None => self.call::<MapInfo>("GetCurrentMapInfo", ())?.FileName,
+        };
+        if !self.call::<bool>("ChooseNextMap", file_name.as_str())? {
+            return Err(ClientError::Rejected("ChooseNextMap"));
+        }
+        Ok(())
+    }
+
+    /// the map after the current one in the list is next again
+    fn release_current_map(&mut self) -> Result<(), ClientError> {
+        let maps = self.get_map_list()?;
+        if maps.len() < 2 {
+            return Ok(());
+        }
+        let index: i32 = self.call("GetCurrentMapIndex", ())?;
+        let next = &maps[(index.max(0) as usize + 1) % maps.len()];
+        if !self.call::<bool>("ChooseNextMap", next.FileName.as_str())? {
+            return Err(ClientError::Rejected("ChooseNextMap"));
+        }
+        Ok(())
+    }
+
+    /// `/pause` and `/resume`
+    pub(crate) fn pause_command(&mut self, login: &str) -> Result<(), ClientError> {
+        if !self.pause_rotation()? {
+            return self.chat_send_to_login(login, "the rotation is paused already");
+        }
+        Ok(())
+    }
+
+    pub(crate) fn resume_command(&mut self, login: &str) -> Result<(), ClientError> {
+        if !self.resume_rotation()? {
+            return self.chat_send_to_login(login, "the rotation is not paused");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+use dxr::{DxrError, TryFromValue, Value};
+
+use crate::{identity::PlayerEntry, Client};
+
+/// the decimal digit of `packed` at `place`, counting from the right
+fn digit(packed: i32, place: u32) -> i32 {
+    (packed / 10_i32.pow(place)) % 10
+}
+
+/// whether and how a player is spectating, from the packed `SpectatorStatus`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SpectatorStatus {
+    pub spectator: bool,
+    /// spectating only until they respawn, like after a forced switch
+    pub temporary: bool,
+    /// spectating without taking a player slot
+    pub pure: bool,
+    pub auto_target: bool,
+    /// the player id they are watching, 0 if nobody in particular
+    pub target_id: i32,
+}
+
+impl SpectatorStatus {
+    /// the server packs these into decimal digits, the target id being the
+    /// digits from the fifth on
+    pub fn from_packed(packed: i32) -> Self {
+        SpectatorStatus {
+            spectator: digit(packed, 0) != 0,
+            temporary: digit(packed, 1) != 0,
+            pure: digit(packed, 2) != 0,
+            auto_target: digit(packed, 3) != 0,
+            target_id: packed / 10_000,
+        }
+    }
+}
+
+/// the packed `Flags` of a player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PlayerFlags {
+    /// 0 if they choose, 1 if forced to spectate, 2 if forced to play
+    pub force_spectator: i32,
+    pub referee: bool,
+    pub podium_ready: bool,
+    pub using_stereoscopy: bool,
+    pub managed_by_other_server: bool,
+    /// the server itself, or a relay
+    pub server: bool,
+    pub has_player_slot: bool,
+    pub broadcasting: bool,
+    pub has_joined_game: bool,
+}
+
+impl PlayerFlags {
+    pub fn from_packed(packed: i32) -> Self {
+        PlayerFlags {
+            force_spectator: digit(packed, 0),
+            referee: digit(packed, 1) != 0,
+            podium_ready: digit(packed, 2) != 0,
+            using_stereoscopy: digit(packed, 3) != 0,
+            managed_by_other_server: digit(packed, 4) != 0,
+            server: digit(packed, 5) != 0,
+            has_player_slot: digit(packed, 6) != 0,
+            broadcasting: digit(packed, 7) != 0,
+            has_joined_game: digit(packed, 8) != 0,
+        }
+    }
+}
+
+/// a player, as `ManiaPlanet.PlayerInfoChanged` sends them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerInfo {
+    pub login: String,
+    pub nickname: String,
+    pub player_id: i32,
+    /// -1 outside of team modes
+    pub team_id: i32,
+    pub ladder_ranking: i32,
+    pub spectator: SpectatorStatus,
+    pub flags: PlayerFlags,
+}
+
+impl PlayerInfo {
+    pub fn is_spectator(&self) -> bool {
+        self.spectator.spectator
+    }
+}
+
+#[allow(non_snake_case)]
+#[derive(TryFromValue)]
+struct StoredPlayerInfo {
+    Login: String,
+    NickName: String,
+    PlayerId: i32,
+    TeamId: i32,
+    SpectatorStatus: i32,
+    LadderRanking: i32,
+    Flags: i32,
+}
+
+impl TryFromValue for PlayerInfo {
+    fn try_from_value(value: &Value) -> Result<Self, DxrError> {
+        let info = StoredPlayerInfo::try_from_value(value)?;
+        Ok(PlayerInfo {
+            login: info.Login,
+            nickname: info.NickName,
+            player_id: info.PlayerId,
+            team_id: info.TeamId,
+            ladder_ranking: info.LadderRanking,
+            spectator: SpectatorStatus::from_packed(info.SpectatorStatus),
+            flags: PlayerFlags::from_packed(info.Flags),
+        })
+    }
+}
+
+impl Client {
+    /// logins of everyone on the server that is spectating
+    pub fn spectators(&self) -> impl Iterator<Item = &str> {
+        self.spectators.iter().map(String::as_str)
+    }
+
+    /// players on the server that are not spectating, they are the ones
+    /// that get a say in votes
+    pub fn active_player_count(&self) -> usize {
+        self.players
+            .iter()
+            .filter(|login| !self.spectators.contains(*login))
+            .count()
+    }
+
+    /// keeps the players, spectators and nicknames up to date
+    pub(crate) fn player_info_changed(&mut self, info: PlayerInfo) {
+        // relays show up as players too, but nobody is playing there
+        if info.flags.server {
+            return;
+        }
+        if info.is_spectator() {
+            self.spectators.insert(info.login.clone());
+        } else {
+            self.spectators.remove(&info.login);
+        }
+        self.players.insert(info.login.clone());
+        self.standings
+            .set_ladder_ranking(&info.login, info.ladder_ranking);
+        self.identities.remember(PlayerEntry {
+            Login: info.login,
+            NickName: info.nickname,
+            PlayerId: info.player_id,
+        });
+    }
+}
@@ -0,0 +1,66 @@
+use std::time::{Duration, Instant};
+
+use dxr::{TryFromValue, Value};
+
+use crate::{identity::PlayerEntry, Client, ClientError, PlayerInfo};
+
+impl Client {
+    /// logins of everyone on the server, kept up to date by the connect and
+    /// disconnect callbacks
+    pub fn players(&self) -> impl Iterator<Item = &str> {
+        self.players.iter().map(String::as_str)
+    }
+
+    /// asks the server who is there, the callbacks only tell us about changes
+    pub(crate) fn refresh_players(&mut self) -> Result<(), ClientError> {
+        let entries: Vec<Value> = self.call_paged("GetPlayerList")?;
+        self.players.clear();
+        self.spectators.clear();
+        for entry in &entries {
+            // older servers leave out the spectator status
+            if let Ok(info) = PlayerInfo::try_from_value(entry) {
+                self.player_info_changed(info);
+            } else {
+                let entry =
+                    PlayerEntry::try_from_value(entry).map_err(|err| ClientError::Type {
+                        method: "GetPlayerList".to_owned(),
+                        err,
+                    })?;
+                self.players.insert(entry.Login.clone());
+                self.identities.remember(entry);
+            }
+        }
+        Ok(())
+    }
+
+    /// handles messages until at least `min` players are on the server, and
+    /// returns how many there are
+    ///
+    /// gives up after `timeout` or on a shutdown, with however many players
+    /// there are by then.
+    pub fn wait_for_players(
+        &mut self,
+        min: usize,
+        timeout: Duration,
+    ) -> Result<usize, ClientError> {
+        let deadline = Instant::now() + timeout;
+        // a callback handler might be waiting already
+        let outer = self.awaited_players.replace(min);
+        let mut res = Ok(());
+        while res.is_ok()
+            && self.players.len() < min
+            && Instant::now() < deadline
+            && !self.shutdown_requested()
+        {
+            res = self.await_messages_until(Some(deadline));
+        }
+        self.awaited_players = outer;
+        res.map(|()| self.players.len())
+    }
+
+    /// whether the message loop should return to [`Client::wait_for_players`]
+    pub(crate) fn enough_players(&self) -> bool {
+        self.awaited_players
+            .is_some_and(|min| self.players.len() >= min)
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    thread,
+};
+
+use crate::{
+    client::Event, control::map_file_name, download_file, is_gbx_map, CacheDry, Client,
+    ClientError, ConnectionState, DownloadError, MapCandidate,
+};
+
+/// maps that were picked and downloaded ahead of time, see
+/// `prefetch_pool_size`
+#[derive(Debug, Default)]
+pub(crate) struct Prefetch {
+    /// downloaded and checked, with their path relative to the maps directory
+    ready: VecDeque<(MapCandidate, String)>,
+    /// being downloaded right now
+    in_flight: HashSet<u64>,
+    /// we are filling the pool up, until it is full
+    filling: bool,
+    /// a refill is waiting in the queue of events already
+    scheduled: bool,
+}
+
+impl Prefetch {
+    /// the maps that are ready or on their way
+    pub fn ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.ready
+            .iter()
+            .map(|(candidate, _)| candidate.id)
+            .chain(self.in_flight.iter().copied())
+    }
+
+    fn len(&self) -> usize {
+        self.ready.len() + self.in_flight.len()
+    }
+}
+
+/// downloads the map on a thread of its own, through the cache if there is
+/// one, and checks that it is a map
+fn prefetch_file(
+    exchange: &reqwest::blocking::Client,
+    id: u64,
+    cached: Option<&Path>,
+    dest: &Path,
+) -> Result<(), DownloadError> {
+    if !dest.exists() {
+        let url = format!("https://trackmania.exchange/maps/download/{id}");
+        match cached {
+            Some(cached) => {
+                if !cached.exists() {
+                    download_file(exchange, &url, cached)?;
+                }
+                if let Err(err) = fs::copy(cached, dest) {
+                    let _ = fs::remove_file(dest);
+                    return Err(err.into());
+                }
+            }
+            None => download_file(exchange, &url, dest)?,
+        }
+    }
+    if !is_gbx_map(&fs::read(dest)?) {
+        let _ = fs::remove_file(dest);
+        return Err(DownloadError::NotAMap);
+    }
+    Ok(())
+}
+
+impl Client {
+    /// how many maps are downloaded ahead of time and ready to be inserted
+    pub fn prefetched_maps(&self) -> usize {
+        self.prefetch.ready.len()
+    }
+
+    /// refills the pool once the message loop has nothing else to do
+    pub(crate) fn schedule_prefetch(&mut self) {
+        if self.config().prefetch_pool_size > 0 && !self.prefetch.scheduled {
+            self.prefetch.scheduled = true;
+            self.deferred.push_back(Event::RefillPrefetch);
+        }
+    }
+
+    /// picks one more map for the pool and starts downloading it
+    ///
+    /// the picking needs the source, the filters and the history, so it
+    /// runs here, only the download and the check run in the background.
+    /// one map is picked per turn of the message loop, so whatever else
+    /// comes in does not wait for all of them.
+    pub(crate) fn refill_prefetch(&mut self) -> Result<(), ClientError> {
+        self.prefetch.scheduled = false;
+        let size = self.config().prefetch_pool_size;
+        let below = self.config().prefetch_refill_below.unwrap_or(size);
+        // offline, the maps come from the cache, which is quick anyway
+        if size == 0
+            || self.config().offline
+            || self.state() != ConnectionState::Authenticated
+            || self.grace_left().is_some()
+        {
+            return Ok(());
+        }
+        let have = self.prefetch.len();
+        if have >= size {
+            self.prefetch.filling = false;
+            return Ok(());
+        }
+        if !self.prefetch.filling && have >= below {
+            return Ok(());
+        }
+        self.prefetch.filling = true;
+        let candidate = match self.random_map() {
+            Ok(candidate) => candidate,
+            Err(err) => {
+                // try again once a map was used up
+                self.prefetch.filling = false;
+                if !err.is::<CacheDry>() {
+                    self.report_error("picking a map to prefetch", err);
+                }
+                return Ok(());
+            }
+        };
+        self.start_prefetch(candidate)?;
+        self.schedule_prefetch();
+        Ok(())
+    }
+
+    fn start_prefetch(&mut self, candidate: MapCandidate) -> Result<(), ClientError> {
+        let id = candidate.id;
+        let rel_path = self.map_path(id);
+        let dest = self.maps_dir_path(&rel_path)?;
+        let cached: Option<PathBuf> = self
+            .config()
+            .download_cache
+            .as_ref()
+            .map(|cache| cache.join(map_file_name(id)));
+        let exchange = self.exchange().clone();
+        let events = self.events_tx();
+        debug!("prefetching map {id}");
+        self.stats.tmx_requests += 1;
+        self.prefetch.in_flight.insert(id);
+        thread::spawn(move || {
+            let result = prefetch_file(&exchange, id, cached.as_deref(), &dest);
+            let _ = events.send(Event::Prefetched {
+                candidate: Box::new(candidate),
+                rel_path,
+                result,
+            });
+        });
+        Ok(())
+    }
+
+    pub(crate) fn prefetch_done(
+        &mut self,
+        candidate: Box<MapCandidate>,
+        rel_path: String,
+        result: Result<(), DownloadError>,
+    ) {
+        self.prefetch.in_flight.remove(&candidate.id);
+        match result {
+            Ok(()) => {
+                debug!("map {} is ready", candidate.id);
+                self.stats.maps_downloaded += 1;
+                self.prefetch.ready.push_back((*candidate, rel_path));
+            }
+            Err(err) => {
+                self.stats.tmx_failures += 1;
+                self.report_error("prefetching a map", err);
+            }
+        }
+        self.schedule_prefetch();
+    }
+
+    /// inserts or stages a map from the pool, returns false if there was
+    /// none
+    pub(crate) fn add_prefetched_map(&mut self) -> Result<bool, ClientError> {
+        while let Some((candidate, rel_path)) = self.prefetch.ready.pop_front() {
+            self.schedule_prefetch();
+            // it might have been disliked or deleted while it waited
+            if self.blocklist.contains(candidate.id) || !self.maps_dir_path(&rel_path)?.is_file() {
+                debug!("map {} is no longer usable", candidate.id);
+                continue;
+            }
+            say!("using prefetched map {}", candidate.id);
+            // for the thumbnail, the replay, making room and duplicates
+            match self.fetch_map(candidate.id) {
+                Err(ClientError::MapInRotation(_)) => continue,
+                res => {
+                    res?;
+                }
+            }
+            if self.config().deferred_insert {
+                self.staged.push_back(candidate);
+                return Ok(true);
+            }
+            if !self.add_map(&rel_path, self.config().insert_mode)? {
+                return Ok(false);
+            }
+            self.apply_time_budget(&candidate)?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
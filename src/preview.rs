@@ -0,0 +1,69 @@
+use std::fs;
+
+use crate::{
+    gbx::{header_chunks, GbxReader},
+    Client, ClientError,
+};
+
+/// the header chunk of a map that holds its thumbnail
+const THUMBNAIL_CHUNK: u32 = 0x0304_3007;
+/// the classes of maps, the second one from older games
+const MAP_CLASSES: [u32; 2] = [0x0304_3000, 0x2400_3000];
+
+/// whether `file` starts like a gbx map, so the server can load it
+pub fn is_gbx_map(file: &[u8]) -> bool {
+    let mut reader = GbxReader::new(file);
+    let mut header = || {
+        if reader.take(3)? != b"GBX" {
+            return None;
+        }
+        let version = u16::from_le_bytes(reader.take(2)?.try_into().ok()?);
+        // the format and compression flags, the "R" or "E" came later
+        match version {
+            3 => reader.take(3)?,
+            4.. => reader.take(4)?,
+            _ => return None,
+        };
+        reader.u32()
+    };
+    header().is_some_and(|class| MAP_CLASSES.contains(&class))
+}
+
+/// the thumbnail the game put into the header of a map file
+///
+/// `None` if the file is not a gbx map or has no thumbnail. the game stores
+/// the image upside down.
+pub fn embedded_thumbnail(map: &[u8]) -> Option<Vec<u8>> {
+    let (_, data) = header_chunks(map)?
+        .into_iter()
+        .find(|&(id, _)| id == THUMBNAIL_CHUNK)?;
+    let mut chunk = GbxReader::new(data);
+    if chunk.u32()? == 0 {
+        return None;
+    }
+    let len = chunk.u32()? as usize;
+    if chunk.take(15)? != b"<Thumbnail.jpg>" || len == 0 {
+        return None;
+    }
+    chunk.take(len).map(<[u8]>::to_vec)
+}
+
+impl Client {
+    /// a jpeg of map `id`, for previews
+    ///
+    /// the thumbnail from tmx next to the map comes first, then the one in the
+    /// map file itself. if neither is there, the tmx thumbnail is downloaded,
+    /// unless we are offline. maps other than ours have neither.
+    pub fn capture_map_image(&mut self, id: u64) -> Result<Vec<u8>, ClientError> {
+        let map = self.maps_dir_path(&self.map_path(id))?;
+        let thumbnail = map.with_file_name(format!("{id}.jpg"));
+        if let Ok(image) = fs::read(&thumbnail) {
+            return Ok(image);
+        }
+        if let Some(image) = fs::read(&map).ok().as_deref().and_then(embedded_thumbnail) {
+            return Ok(image);
+        }
+        self.fetch_thumbnail(id, &thumbnail);
+        fs::read(&thumbnail).map_err(|_| ClientError::NoImage(id))
+    }
+}
@@ -0,0 +1,86 @@
+use std::time::Instant;
+
+use serde_json::json;
+
+use crate::{Client, ClientError, ControlCommand};
+
+/// a control command that came in while the server could not be reached
+#[derive(Debug)]
+pub(crate) struct Pending {
+    command: ControlCommand,
+    since: Instant,
+}
+
+impl ControlCommand {
+    /// whether the command is pointless once it is late, like a chat message
+    ///
+    /// those are dropped after `queue_ttl`, the others wait as long as it
+    /// takes.
+    pub fn is_time_sensitive(&self) -> bool {
+        matches!(
+            self,
+            ControlCommand::Next | ControlCommand::Cancel | ControlCommand::Chat { .. }
+        )
+    }
+}
+
+impl Client {
+    /// runs `command` now, or once we are logged in again
+    ///
+    /// a queued command is answered with `{"ok":true,"queued":true}` right
+    /// away and gets its real reply when it ran.
+    pub(crate) fn dispatch_control(&mut self, command: ControlCommand) -> Result<(), ClientError> {
+        if !self.state().is_healthy() {
+            self.queue_control(command);
+            return Ok(());
+        }
+        match self.as_untrusted(|c| c.run_control(command.clone())) {
+            Ok(()) => self.control_reply(json!({ "ok": true })),
+            // it did not get through, or we do not know, so it runs again
+            Err(err) if err.is_connection() => {
+                self.queue_control(command);
+                return Err(err);
+            }
+            Err(err) => self.control_reply(json!({ "ok": false, "error": err.to_string() })),
+        }
+        Ok(())
+    }
+
+    fn queue_control(&mut self, command: ControlCommand) {
+        self.control_reply(json!({ "ok": true, "queued": true }));
+        self.pending.push_back(Pending {
+            command,
+            since: Instant::now(),
+        });
+    }
+
+    /// runs the commands that were queued while disconnected, in order
+    pub(crate) fn flush_pending(&mut self) -> Result<(), ClientError> {
+        if !self.pending.is_empty() {
+            say!("running {} queued commands", self.pending.len());
+        }
+        while let Some(pending) = self.pending.pop_front() {
+            if pending.command.is_time_sensitive()
+                && pending.since.elapsed() > self.config().queue_ttl
+            {
+                say!(
+                    "dropping the queued {:?}, it is too late for it",
+                    pending.command
+                );
+                let error = "dropped, the server was away for too long";
+                self.control_reply(json!({ "ok": false, "error": error }));
+                continue;
+            }
+            match self.as_untrusted(|c| c.run_control(pending.command.clone())) {
+                Ok(()) => self.control_reply(json!({ "ok": true })),
+                // still in order for the next attempt
+                Err(err) if err.is_connection() => {
+                    self.pending.push_front(pending);
+                    return Err(err);
+                }
+                Err(err) => self.control_reply(json!({ "ok": false, "error": err.to_string() })),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{strip_tm_formatting, Client};
+
+/// how a player is doing, on the ladder and on the current map
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlayerRanking {
+    pub login: String,
+    /// with the formatting codes stripped
+    pub nickname: Option<String>,
+    /// `None` if the player has no ladder rank
+    pub ladder_ranking: Option<i32>,
+    /// on the current map, from the last `Trackmania.Scores`
+    pub rank: Option<u32>,
+    pub map_points: Option<i64>,
+    /// the best finish on the current map, in milliseconds
+    pub best_race_time: Option<u64>,
+}
+
+/// a player in the `Trackmania.Scores` callback
+#[derive(Deserialize)]
+struct ScoreEntry {
+    login: String,
+    #[serde(default)]
+    rank: Option<u32>,
+    #[serde(default)]
+    mappoints: Option<i64>,
+    /// -1 without a finish
+    #[serde(default)]
+    bestracetime: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct Scores {
+    #[serde(default)]
+    players: Vec<ScoreEntry>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct MapScore {
+    rank: Option<u32>,
+    map_points: Option<i64>,
+    best_race_time: Option<u64>,
+}
+
+/// the rankings of the players, kept up to date by the callbacks so the
+/// status endpoint does not need to ask the server
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Standings {
+    ladder: HashMap<String, i32>,
+    scores: HashMap<String, MapScore>,
+}
+
+impl Standings {
+    pub fn set_ladder_ranking(&mut self, login: &str, ranking: i32) {
+        self.ladder.insert(login.to_owned(), ranking);
+    }
+
+    /// the scores only count for the map they were made on
+    pub fn clear_scores(&mut self) {
+        self.scores.clear();
+    }
+
+    pub fn forget(&mut self, login: &str) {
+        self.ladder.remove(login);
+        self.scores.remove(login);
+    }
+}
+
+impl Client {
+    /// the rankings of the players on the server, best first
+    ///
+    /// players without a score on the current map come last.
+    pub fn player_rankings(&self) -> Vec<PlayerRanking> {
+        let mut rankings: Vec<_> = self
+            .players
+            .iter()
+            .map(|login| {
+                let score = self
+                    .standings
+                    .scores
+                    .get(login)
+                    .cloned()
+                    .unwrap_or_default();
+                PlayerRanking {
+                    login: login.clone(),
+                    nickname: self.identities.nickname(login).map(strip_tm_formatting),
+                    ladder_ranking: self.standings.ladder.get(login).copied().filter(|&r| r > 0),
+                    rank: score.rank,
+                    map_points: score.map_points,
+                    best_race_time: score.best_race_time,
+                }
+            })
+            .collect();
+        rankings.sort_by(|a, b| {
+            let key = |r: &PlayerRanking| (r.rank.is_none(), r.rank, r.best_race_time.is_none());
+            key(a)
+                .cmp(&key(b))
+                .then_with(|| a.best_race_time.cmp(&b.best_race_time))
+                .then_with(|| a.login.cmp(&b.login))
+        });
+        rankings
+    }
+
+    /// keeps the scores up to date from the script callbacks
+    pub(crate) fn handle_score_callback(&mut self, name: &str, json: &serde_json::Value) {
+        match name {
+            "Trackmania.Scores" => match Scores::deserialize(json) {
+                Ok(scores) => {
+                    for entry in scores.players {
+                        let score = self.standings.scores.entry(entry.login).or_default();
+                        score.rank = entry.rank.filter(|&rank| rank > 0);
+                        score.map_points = entry.mappoints;
+                        score.best_race_time = entry
+                            .bestracetime
+                            .and_then(|ms| u64::try_from(ms).ok())
+                            .or(score.best_race_time);
+                    }
+                }
+                Err(err) => debug!("ignoring scores: {err}"),
+            },
+            // a finish counts right away, the ranks follow with the next scores
+            "Trackmania.Event.WayPoint" if json["isendrace"] == true => {
+                let (Some(login), Some(time)) = (json["login"].as_str(), json["racetime"].as_u64())
+                else {
+                    return;
+                };
+                let score = self.standings.scores.entry(login.to_owned()).or_default();
+                if score.best_race_time.is_none_or(|best| time < best) {
+                    score.best_race_time = Some(time);
+                }
+            }
+            _ => {}
+        }
+    }
+}
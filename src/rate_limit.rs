@@ -0,0 +1,56 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// a token bucket, so short bursts go through at once but the long term rate
+/// stays below `rate` per second
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    rate: f64,
+    /// how many calls can be made at once after being idle
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    /// a rate of 0 turns the limiter off
+    pub fn new(rate: f64) -> Self {
+        let burst = rate.max(1.0);
+        RateLimiter {
+            rate,
+            burst,
+            tokens: burst,
+            last: Instant::now(),
+        }
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// how long to wait before the next call may be made, takes its token
+    pub fn reserve(&mut self) -> Duration {
+        if self.rate <= 0.0 {
+            return Duration::ZERO;
+        }
+        let now = Instant::now();
+        let refill = now.duration_since(self.last).as_secs_f64() * self.rate;
+        self.tokens = (self.tokens + refill).min(self.burst) - 1.0;
+        self.last = now;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+
+    /// blocks until the next call may be made
+    pub fn acquire(&mut self) {
+        let wait = self.reserve();
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::{collections::HashSet, fs};
+
+use crate::{
+    catalog::key, evict::downloads, CatalogEntry, Client, ClientError, GbxMapHeader, MapOrigin,
+};
+
+/// how the map list of the server compares to the catalog, see
+/// `reconcile_on_start`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MapListSync {
+    /// file names in the map list the catalog knew, by file name or uid, and
+    /// fallback maps
+    pub matched: Vec<String>,
+    /// file names in the map list that were added some other way, they are
+    /// in the catalog as external from now on
+    pub external: Vec<String>,
+    /// file names in the catalog that are no longer in the map list, their
+    /// entries are marked as orphaned
+    pub orphaned: Vec<String>,
+}
+
+impl Client {
+    /// brings the catalog in line with the map list
+    ///
+    /// only the catalog changes, the map list and the files stay as they are.
+    /// downloads from before there was a catalog are added to it first, so
+    /// they are not taken for external maps.
+    pub fn reconcile_map_list(&mut self) -> Result<MapListSync, ClientError> {
+        let maps = self.get_map_list()?;
+        self.catalog_old_downloads()?;
+
+        let mut sync = MapListSync::default();
+        let mut listed = HashSet::new();
+        for map in maps {
+            let known = match self.catalog.get(&map.FileName) {
+                Some(_) => Some(key(&map.FileName)),
+                // the map list might have it under another file name
+                None => self.catalog.file_of_uid(&map.UId).map(str::to_owned),
+            };
+            if let Some(file_name) = known {
+                listed.insert(file_name);
+                sync.matched.push(map.FileName);
+            } else if self.is_fallback_map(&map.FileName) {
+                sync.matched.push(map.FileName);
+            } else {
+                debug!("{} is not in the catalog", map.FileName);
+                let mut entry = CatalogEntry::new(MapOrigin::External);
+                entry.uid = Some(map.UId.clone());
+                entry.name = Some(map.Name.clone());
+                self.catalog.insert(&map.FileName, entry);
+                listed.insert(key(&map.FileName));
+                sync.external.push(map.FileName);
+            }
+        }
+
+        let file_names: Vec<String> = self
+            .catalog
+            .iter()
+            .map(|(file_name, _)| file_name.to_owned())
+            .collect();
+        for file_name in file_names {
+            let orphaned = !listed.contains(&file_name);
+            if let Some(entry) = self.catalog.get_mut(&file_name) {
+                entry.orphaned = orphaned;
+            }
+            if orphaned {
+                debug!("{file_name} is in the catalog but not in the map list");
+                sync.orphaned.push(file_name);
+            }
+        }
+        self.save_catalog();
+        Ok(sync)
+    }
+
+    /// adds the downloads in the maps directory the catalog does not have
+    fn catalog_old_downloads(&mut self) -> Result<(), ClientError> {
+        // downloads all go to the same directory
+        let path = self.maps_dir_path(&self.map_path(0))?;
+        let dir = path.parent().unwrap_or(&path);
+        let files = match downloads(dir) {
+            Ok(files) => files,
+            Err(err) => {
+                say!(
+                    "could not look at the downloads in {}: {err}",
+                    dir.display()
+                );
+                return Ok(());
+            }
+        };
+        for file in files {
+            let rel_path = self.map_path(file.id);
+            if self.catalog.get(&rel_path).is_some() {
+                continue;
+            }
+            let mut entry = CatalogEntry::new(MapOrigin::Tmx { id: file.id });
+            if let Some(header) = fs::read(&file.path)
+                .ok()
+                .and_then(|bytes| GbxMapHeader::parse(&bytes))
+            {
+                entry.uid = Some(header.uid);
+                entry.name = Some(header.name);
+            }
+            self.catalog.insert(&rel_path, entry);
+        }
+        Ok(())
+    }
+
+    /// `reconcile_on_start`, right after the first connect
+    pub(crate) fn reconcile_on_start(&mut self) -> Result<(), ClientError> {
+        if !self.config().reconcile_on_start {
+            return Ok(());
+        }
+        let sync = match self.reconcile_map_list() {
+            Ok(sync) => sync,
+            Err(err) if !err.is_connection() => {
+                self.report_error("reconciling the map list", err);
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
+        say!(
+            "reconciled the map list: {} matched, {} new, {} orphaned",
+            sync.matched.len(),
+            sync.external.len(),
+            sync.orphaned.len()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        testing::{gbx_map, map_info, temp_dir, test_client, test_config, MockServer, Reply},
+        CatalogEntry, MapOrigin,
+    };
+
+    #[test]
+    fn the_catalog_follows_the_map_list() {
+        let dir = temp_dir("reconcile");
+        // downloaded before there was a catalog, and listed under another name
+        std::fs::write(dir.join("1.Map.Gbx"), gbx_map("uid1", "one")).unwrap();
+        // downloaded before there was a catalog, and no longer listed
+        std::fs::write(dir.join("2.Map.Gbx"), gbx_map("uid2", "two")).unwrap();
+        let maps_dir = dir.display().to_string();
+        let server = MockServer::start(move |method, _| match method {
+            "GetMapsDirectory" => Some(Reply::of(maps_dir.as_str())),
+            "GetMapList" => Some(Reply::of(vec![
+                map_info("renamed.Map.Gbx", "uid1"),
+                map_info("Campaign\\mine.Map.Gbx", "uid3"),
+                map_info("added.Map.Gbx", "uid4"),
+            ])),
+            _ => None,
+        });
+        let mut client = test_client(test_config(&server));
+        client
+            .catalog
+            .insert("Campaign/mine.Map.Gbx", CatalogEntry::new(MapOrigin::Local));
+        client
+            .catalog
+            .insert("gone.Map.Gbx", CatalogEntry::new(MapOrigin::Local));
+
+        let sync = client.reconcile_map_list().unwrap();
+        assert_eq!(sync.matched, ["renamed.Map.Gbx", "Campaign\\mine.Map.Gbx"]);
+        assert_eq!(sync.external, ["added.Map.Gbx"]);
+        assert_eq!(sync.orphaned, ["2.Map.Gbx", "gone.Map.Gbx"]);
+
+        let catalog = &client.catalog;
+        assert_eq!(
+            catalog.get("added.Map.Gbx").unwrap().origin,
+            MapOrigin::External
+        );
+        assert_eq!(
+            catalog.get("1.Map.Gbx").unwrap().origin,
+            MapOrigin::Tmx { id: 1 }
+        );
+        assert!(!catalog.get("1.Map.Gbx").unwrap().orphaned);
+        assert!(catalog.get("2.Map.Gbx").unwrap().orphaned);
+        assert!(catalog.get("gone.Map.Gbx").unwrap().orphaned);
+        // nothing but the map list was asked for
+        assert!(server.params_of("RemoveMap").is_empty());
+        assert!(server.params_of("InsertMap").is_empty());
+    }
+}
@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use crate::{Client, ClientError};
+
+/// runs once a connection is set up, see [`Client::on_reconnect`]
+pub type ConnectHandler = Arc<dyn Fn(&mut Client) -> Result<(), ClientError> + Send + Sync>;
+
+impl Client {
+    /// calls `handler` every time the connection is back after it was lost
+    ///
+    /// a restarted server forgets whatever was set up on it, this is the
+    /// place to set it up again. the handlers run at the end of
+    /// [`Client::connect`], in the order they were registered: after the
+    /// login, enabling callbacks and applying the config, and before queued
+    /// control commands and callbacks are handled.
+    pub fn on_reconnect(
+        &mut self,
+        handler: impl Fn(&mut Client) -> Result<(), ClientError> + Send + Sync + 'static,
+    ) {
+        self.connect_handlers.push((false, Arc::new(handler)));
+    }
+
+    /// like [`Client::on_reconnect`], but also on the first connection
+    pub fn on_connect(
+        &mut self,
+        handler: impl Fn(&mut Client) -> Result<(), ClientError> + Send + Sync + 'static,
+    ) {
+        self.connect_handlers.push((true, Arc::new(handler)));
+    }
+
+    pub fn clear_connect_handlers(&mut self) {
+        self.connect_handlers.clear();
+    }
+
+    pub(crate) fn handle_connected(&mut self, reconnect: bool) -> Result<(), ClientError> {
+        // the handlers may register or clear handlers themselves
+        let handlers: Vec<ConnectHandler> = self
+            .connect_handlers
+            .iter()
+            .filter(|(first, _)| reconnect || *first)
+            .map(|(_, handler)| handler.clone())
+            .collect();
+        for handler in handlers {
+            match handler(self) {
+                Err(err) if !err.is_connection() => {
+                    self.report_error("setting up the connection", err)
+                }
+                res => res?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{
+        testing::{temp_dir, test_client, test_config, MockServer, Reply},
+        Client, ClientError, MemoryStorage,
+    };
+
+    #[test]
+    fn reconnect_handlers_run_after_the_connection_dropped() {
+        let maps_dir = format!("{}/", temp_dir("reconnect").display());
+        let server = MockServer::start(move |method, _| match method {
+            "GetMapsDirectory" => Some(Reply::of(maps_dir.as_str())),
+            _ => None,
+        });
+        let mut client = Client::new(test_config(&server));
+        client.set_storage(Box::new(MemoryStorage::default()));
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let log = ran.clone();
+        client.on_connect(move |_| {
+            log.lock().unwrap().push("connect");
+            Ok(())
+        });
+        let log = ran.clone();
+        client.on_reconnect(move |_| {
+            log.lock().unwrap().push("reconnect");
+            Ok(())
+        });
+
+        client.connect().unwrap();
+        assert_eq!(*ran.lock().unwrap(), ["connect"]);
+        server.drop_connections();
+        assert!(client.await_messages().unwrap_err().is_connection());
+        // what the supervisor does before it connects again
+        client.disconnect();
+        client.connect().unwrap();
+        assert_eq!(*ran.lock().unwrap(), ["connect", "connect", "reconnect"]);
+        assert_eq!(client.stats.reconnects, 1);
+        assert_eq!(server.params_of("Authenticate").len(), 2);
+    }
+
+    #[test]
+    fn handlers_run_in_order_and_only_when_they_should() {
+        let server = MockServer::start(|method, _| match method {
+            "Custom" => Some(Reply::Fault(-1000, "not yet")),
+            _ => None,
+        });
+        let mut client = test_client(test_config(&server));
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let log = ran.clone();
+        client.on_connect(move |_| {
+            log.lock().unwrap().push("connect");
+            Ok(())
+        });
+        let log = ran.clone();
+        client.on_reconnect(move |client| {
+            log.lock().unwrap().push("reconnect");
+            // a fault is reported, the next handler still runs
+            client.call::<bool>("Custom", ())?;
+            Ok(())
+        });
+        let log = ran.clone();
+        client.on_reconnect(move |_| {
+            log.lock().unwrap().push("last");
+            Ok(())
+        });
+
+        client.handle_connected(false).unwrap();
+        assert_eq!(*ran.lock().unwrap(), ["connect"]);
+        ran.lock().unwrap().clear();
+        client.handle_connected(true).unwrap();
+        assert_eq!(*ran.lock().unwrap(), ["connect", "reconnect", "last"]);
+        assert!(client
+            .stats
+            .last_error
+            .as_ref()
+            .unwrap()
+            .contains("not yet"));
+
+        // connection problems still end the connect
+        client.clear_connect_handlers();
+        client.on_connect(|_| Err(ClientError::Io(std::io::ErrorKind::BrokenPipe.into())));
+        assert!(client.handle_connected(false).is_err());
+    }
+}
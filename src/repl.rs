@@ -0,0 +1,148 @@
+use std::{
+    io::{self, BufRead, Write},
+    thread,
+};
+
+use dxr::Value;
+
+use crate::{client::Event, value_to_json, Client, ClientError};
+
+/// splits `GetMapList 10 0` into the method and its params
+///
+/// params are ints, decimals, `true` and `false`, or strings. strings with
+/// spaces go in double quotes, with `\"` and `\\` inside them.
+pub fn parse_call(line: &str) -> Result<(String, Vec<Value>), String> {
+    let mut chars = line.trim().chars().peekable();
+    let mut words = Vec::new();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(&first) = chars.peek() else { break };
+        if first == '"' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some(c) => text.push(c),
+                        None => return Err("unfinished escape".to_owned()),
+                    },
+                    Some(c) => text.push(c),
+                    None => return Err("unclosed quote".to_owned()),
+                }
+            }
+            words.push((text, true));
+        } else {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                word.push(c);
+            }
+            words.push((word, false));
+        }
+    }
+    let mut words = words.into_iter();
+    let Some((method, false)) = words.next() else {
+        return Err("no method".to_owned());
+    };
+    let params = words
+        .map(|(word, quoted)| match word.as_str() {
+            _ if quoted => Value::string(word),
+            "true" => Value::boolean(true),
+            "false" => Value::boolean(false),
+            _ => {
+                if let Ok(int) = word.parse() {
+                    Value::i4(int)
+                } else if let Ok(double) = word.parse() {
+                    Value::double(double)
+                } else {
+                    Value::string(word)
+                }
+            }
+        })
+        .collect();
+    Ok((method, params))
+}
+
+fn prompt() {
+    print!("> ");
+    let _ = io::stdout().flush();
+}
+
+impl Client {
+    /// logs in and sends the calls typed on stdin, printing the responses
+    ///
+    /// callbacks are printed as they come, and nothing else reacts to them.
+    /// faults are printed too, only the connection failing or stdin closing
+    /// ends it.
+    pub fn run_repl(&mut self) -> Result<(), ClientError> {
+        self.log_in()?;
+        if let Err(err) = self.call::<bool>("EnableCallbacks", [true]) {
+            if err.is_connection() {
+                return Err(err);
+            }
+            println!("callbacks stay off: {err}");
+        }
+        self.repl = true;
+        let events = self.events_tx();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                if events.send(Event::Control(line)).is_err() {
+                    return;
+                }
+            }
+            let _ = events.send(Event::Shutdown);
+        });
+        println!("type a method and its params, or help to list the methods");
+        if !self.is_armed() {
+            println!("safe mode is on, type arm to allow kicks, bans and the like");
+        }
+        prompt();
+        let res = self.await_messages();
+        self.repl = false;
+        self.disconnect();
+        res
+    }
+
+    /// runs a line typed into [`Client::run_repl`]
+    pub(crate) fn repl_line(&mut self, line: &str) -> Result<(), ClientError> {
+        let line = line.trim();
+        let res = match line {
+            "" => Ok(None),
+            "arm" => {
+                self.arm();
+                Ok(None)
+            }
+            "disarm" => {
+                self.disarm();
+                Ok(None)
+            }
+            "help" | "methods" => self
+                .call::<Vec<String>>("system.listMethods", ())
+                .map(|methods| Some(methods.join("\n"))),
+            _ => match parse_call(line) {
+                Ok((method, params)) => self.call_raw(&method, params).map(|value| {
+                    let json = value_to_json(&value, self.config().server_timezone);
+                    Some(serde_json::to_string_pretty(&json).unwrap())
+                }),
+                Err(err) => Ok(Some(err)),
+            },
+        };
+        match res {
+            Ok(Some(reply)) => println!("{reply}"),
+            Ok(None) => {}
+            Err(err) if !err.is_connection() => println!("{err}"),
+            Err(err) => return Err(err),
+        }
+        prompt();
+        Ok(())
+    }
+
+    /// prints a callback that came in while the repl waits
+    pub(crate) fn print_repl_callback(&self, name: &str, params: &[Value]) {
+        let zone = self.config().server_timezone;
+        let params: Vec<_> = params.iter().map(|p| value_to_json(p, zone)).collect();
+        println!("\n{name} {}", serde_json::Value::Array(params));
+        prompt();
+    }
+}
@@ -0,0 +1,33 @@
+use std::{fs, path::Path};
+
+use crate::{Client, ClientError};
+
+impl Client {
+    /// the replay of the best run of `login` on the current map, as the
+    /// contents of a `.Replay.Gbx` file
+    pub fn get_validation_replay(&mut self, login: &str) -> Result<Vec<u8>, ClientError> {
+        self.call("GetValidationReplay", login)
+    }
+
+    /// `/replay <login>` saves the best run of a player to the directory the
+    /// controller runs in
+    pub(crate) fn replay_command(&mut self, login: &str, args: &[&str]) -> Result<(), ClientError> {
+        let [player] = args else {
+            return self.chat_send_to_login(login, "usage: /replay <login>");
+        };
+        let file_name = format!("{player}.Replay.Gbx");
+        // chat should not get to write anywhere else
+        if Path::new(&file_name).file_name() != Some(file_name.as_ref()) {
+            return self.chat_send_to_login(login, "that is not a login");
+        }
+        let reply = match self.get_validation_replay(player) {
+            Ok(replay) => match fs::write(&file_name, &replay) {
+                Ok(()) => format!("saved {file_name}, {} bytes", replay.len()),
+                Err(err) => format!("could not write {file_name}: {err}"),
+            },
+            Err(ClientError::Fault(fault)) => format!("no replay of {player}: {}", fault.string()),
+            Err(err) => return Err(err),
+        };
+        self.chat_send_to_login(login, &reply)
+    }
+}
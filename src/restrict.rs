@@ -0,0 +1,122 @@
+use crate::{Client, ClientError};
+
+/// what chat and control commands may call in restricted mode, unless the
+/// config lists others
+///
+/// kicking, banning, passwords and anything else that changes who can play
+/// is left out on purpose, and so are the [`SECRET_METHODS`].
+pub const DEFAULT_ALLOWED_METHODS: &[&str] = &[
+    "Get*",
+    "ChatSendServerMessage",
+    "ChatSendServerMessageToLogin",
+    "NextMap",
+    "RestartMap",
+    "InsertMap",
+    "ChooseNextMap",
+];
+
+/// getters that return passwords, which `Get*` and other patterns do not
+/// allow, only an entry with their exact name does
+pub const SECRET_METHODS: &[&str] = &["GetServerPassword*", "GetRefereePassword"];
+
+/// entries ending in `*` allow every method starting with the rest
+pub fn method_allowed(allowed: &[impl AsRef<str>], method: &str) -> bool {
+    allowed
+        .iter()
+        .map(AsRef::as_ref)
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => method.starts_with(prefix),
+            None => pattern == method,
+        })
+}
+
+/// like [`method_allowed`], but password getters have to be listed by name
+fn restricted_allowed(allowed: &[String], method: &str) -> bool {
+    if method_allowed(SECRET_METHODS, method) {
+        return allowed.iter().any(|entry| entry == method);
+    }
+    method_allowed(allowed, method)
+}
+
+impl Client {
+    /// runs `f` on behalf of whoever sent a chat or control command
+    ///
+    /// with `restricted` set in the config, the calls it makes are checked
+    /// against `allowed_methods`. everything else the controller does on its
+    /// own, like the handshake and the rotation, is trusted and bypasses the
+    /// check.
+    ///
+    /// the role of the sender is not checked yet, even if this runs while the
+    /// command of someone else waits for a response.
+    pub(crate) fn as_untrusted<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let outer = std::mem::replace(&mut self.untrusted, true);
+        let vetted = std::mem::replace(&mut self.vetted, false);
+        let res = f(self);
+        self.untrusted = outer;
+        self.vetted = vetted;
+        res
+    }
+
+    /// the opposite of [`Client::as_untrusted`], for callbacks that arrive
+    /// while an untrusted command waits for a response
+    pub(crate) fn as_trusted<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let outer = std::mem::replace(&mut self.untrusted, false);
+        let res = f(self);
+        self.untrusted = outer;
+        res
+    }
+
+    pub(crate) fn check_allowed(&self, method: &str) -> Result<(), ClientError> {
+        let config = self.config();
+        if self.untrusted
+            && config.restricted
+            && !restricted_allowed(&config.allowed_methods, method)
+        {
+            return Err(ClientError::Forbidden(method.to_owned()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> Vec<String> {
+        DEFAULT_ALLOWED_METHODS
+            .iter()
+            .map(|method| method.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn prefixes_allow_getters() {
+        assert!(restricted_allowed(&defaults(), "GetMapList"));
+        assert!(restricted_allowed(&defaults(), "ChooseNextMap"));
+        assert!(!restricted_allowed(&defaults(), "Kick"));
+    }
+
+    #[test]
+    fn prefixes_do_not_allow_passwords() {
+        let allowed = defaults();
+        assert!(!restricted_allowed(&allowed, "GetServerPassword"));
+        assert!(!restricted_allowed(
+            &allowed,
+            "GetServerPasswordForSpectator"
+        ));
+        assert!(!restricted_allowed(&allowed, "GetRefereePassword"));
+        let everything = vec!["*".to_owned()];
+        assert!(!restricted_allowed(&everything, "GetServerPassword"));
+    }
+
+    #[test]
+    fn passwords_can_be_listed_by_name() {
+        let mut allowed = defaults();
+        allowed.push("GetServerPassword".to_owned());
+        assert!(restricted_allowed(&allowed, "GetServerPassword"));
+        assert!(!restricted_allowed(
+            &allowed,
+            "GetServerPasswordForSpectator"
+        ));
+    }
+}
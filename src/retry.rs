@@ -0,0 +1,120 @@
+use std::time::{Duration, Instant};
+
+use dxr::{Fault, TryFromValue, TryToParams};
+
+use crate::{Client, ClientError};
+
+/// fault messages the server sends while it is switching maps or modes, the
+/// same call usually works a moment later
+pub const DEFAULT_RETRYABLE_FAULTS: &[&str] = &["busy", "try again", "not ready", "unavailable"];
+
+/// how long to wait before asking again
+const RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// whether `fault` matches one of `patterns`
+///
+/// a pattern is either a fault code, like `-1000`, or a part of the fault
+/// message, ignoring case.
+pub fn retryable_fault(patterns: &[String], fault: &Fault) -> bool {
+    let msg = fault.string().to_lowercase();
+    patterns.iter().any(|pattern| match pattern.parse::<i32>() {
+        Ok(code) => fault.code() == code,
+        Err(_) => msg.contains(&pattern.to_lowercase()),
+    })
+}
+
+/// asking again will not log us in or grant the permission
+fn auth_fault(fault: &Fault) -> bool {
+    let msg = fault.string().to_lowercase();
+    [
+        "permission",
+        "denied",
+        "not allowed",
+        "authenticat",
+        "login",
+    ]
+    .iter()
+    .any(|word| msg.contains(word))
+}
+
+/// only methods that change nothing may be sent twice
+fn read_only(method: &str) -> bool {
+    method.starts_with("Get") || method.starts_with("Is") || method.starts_with("system.")
+}
+
+impl Client {
+    /// like [`Client::call`], but tries up to `attempts` times while the
+    /// server answers with a fault from `retryable_faults` in the config
+    ///
+    /// only read-only methods, those starting with `Get` or `Is`, are ever
+    /// sent more than once. faults about logins or permissions are not
+    /// retried either.
+    pub fn call_with_retry<R>(
+        &mut self,
+        method: &'static str,
+        args: impl TryToParams,
+        attempts: usize,
+    ) -> Result<R, ClientError>
+    where
+        R: TryFromValue,
+    {
+        let patterns = self.config().retryable_faults.clone();
+        self.call_with_retry_if(method, args, attempts, |fault| {
+            retryable_fault(&patterns, fault)
+        })
+    }
+
+    /// like [`Client::call_with_retry`], with `retryable` deciding which
+    /// faults are worth another try
+    pub fn call_with_retry_if<R>(
+        &mut self,
+        method: &'static str,
+        args: impl TryToParams,
+        attempts: usize,
+        retryable: impl Fn(&Fault) -> bool,
+    ) -> Result<R, ClientError>
+    where
+        R: TryFromValue,
+    {
+        let params = args
+            .try_to_params()
+            .map_err(|err| ClientError::Encode(err.to_string()))?;
+        let attempts = if read_only(method) {
+            attempts
+        } else {
+            if attempts > 1 {
+                debug!("not retrying {method}, it might change something");
+            }
+            1
+        };
+        let mut attempt = 1;
+        let res = loop {
+            match self.call_raw(method, params.clone()) {
+                Err(ClientError::Fault(fault))
+                    if attempt < attempts && !auth_fault(&fault) && retryable(&fault) =>
+                {
+                    debug!(
+                        "{method} failed with fault {}: {}, trying again",
+                        fault.code(),
+                        fault.string()
+                    );
+                    attempt += 1;
+                    self.pause_before_retry()?;
+                }
+                res => break res?,
+            }
+        };
+        R::try_from_value(&res).map_err(|err| ClientError::Type {
+            method: method.to_owned(),
+            err,
+        })
+    }
+
+    fn pause_before_retry(&mut self) -> Result<(), ClientError> {
+        let deadline = Instant::now() + RETRY_DELAY;
+        while Instant::now() < deadline && !self.shutdown_requested() {
+            self.await_messages_until(Some(deadline))?;
+        }
+        Ok(())
+    }
+}
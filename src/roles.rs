@@ -0,0 +1,44 @@
+use serde::Deserialize;
+
+use crate::{Client, ClientError};
+
+/// what a player may do with chat commands, each role can do everything the
+/// ones before it can
+///
+/// this is separate from the levels of the server itself, like `SuperAdmin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// anyone that is not in `roles`
+    #[default]
+    Player,
+    Mod,
+    Owner,
+}
+
+impl Client {
+    /// the role of `login`, the logins in `admins` are owners
+    pub fn role_of(&self, login: &str) -> Role {
+        let config = self.config();
+        if config.admins.iter().any(|admin| admin == login) {
+            return Role::Owner;
+        }
+        config.roles.get(login).copied().unwrap_or_default()
+    }
+
+    /// runs `command` if `login` has at least the role `required`
+    ///
+    /// the role is what safe mode would ask for, so it does not block the
+    /// command, see [`Client::as_vetted`].
+    pub(crate) fn command_for(
+        &mut self,
+        login: &str,
+        required: Role,
+        command: impl FnOnce(&mut Self) -> Result<(), ClientError>,
+    ) -> Result<(), ClientError> {
+        if self.role_of(login) < required {
+            return self.chat_send_to_login(login, "insufficient permission");
+        }
+        self.as_vetted(command)
+    }
+}
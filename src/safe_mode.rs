@@ -0,0 +1,64 @@
+use crate::{method_allowed, Client, ClientError};
+
+/// what safe mode blocks until it is armed, in the same form as
+/// `allowed_methods`
+pub const DESTRUCTIVE_METHODS: &[&str] = &[
+    "RemoveMap*",
+    "Kick*",
+    "Ban*",
+    "BlackList*",
+    "Clean*",
+    "SetServerPassword*",
+    "StopServer",
+    "QuitGame",
+];
+
+fn is_destructive(method: &str) -> bool {
+    method_allowed(DESTRUCTIVE_METHODS, method)
+}
+
+impl Client {
+    /// lets chat and control commands and the repl make destructive calls
+    /// until the controller stops, see `safe_mode`
+    pub fn arm(&mut self) {
+        say!("armed, destructive calls are allowed for this session");
+        self.armed = true;
+    }
+
+    pub fn disarm(&mut self) {
+        say!("disarmed, safe mode blocks destructive calls again");
+        self.armed = false;
+    }
+
+    pub fn is_armed(&self) -> bool {
+        self.armed || !self.config().safe_mode
+    }
+
+    /// fails if safe mode keeps whoever is giving commands from doing `what`
+    ///
+    /// like restricted mode, only what people ask for is checked, the
+    /// controller itself may still remove maps it rotated in.
+    pub(crate) fn check_safe_mode(&self, what: &str) -> Result<(), ClientError> {
+        if (self.untrusted || self.repl) && !self.vetted && !self.is_armed() {
+            say!("safe mode blocked {what}");
+            return Err(ClientError::SafeModeBlocked(what.to_owned()));
+        }
+        Ok(())
+    }
+
+    /// runs `f` for a player whose role allows it, safe mode does not block
+    /// what it does, restricted mode still checks its calls
+    pub(crate) fn as_vetted<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let outer = std::mem::replace(&mut self.vetted, true);
+        let res = f(self);
+        self.vetted = outer;
+        res
+    }
+
+    pub(crate) fn check_destructive(&self, method: &str) -> Result<(), ClientError> {
+        if is_destructive(method) {
+            return self.check_safe_mode(method);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::{Duration, Instant};
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{Client, ClientError, MapCandidate};
+
+impl Client {
+    /// adds `count` maps from the search to the rotation at once
+    ///
+    /// tmx requests are spread out by `bulk_jitter_ms`, so it does not look
+    /// like we are scraping. stops early on a shutdown, returns how many maps
+    /// were added.
+    pub fn seed_maps(&mut self, count: usize) -> Result<usize, ClientError> {
+        let mut added = 0;
+        for n in 0..count {
+            if n > 0 && self.pause_for_tmx()? {
+                break;
+            }
+            let candidate = match self.random_map() {
+                Ok(candidate) => candidate,
+                Err(err) => {
+                    self.report_error("picking a map", err);
+                    break;
+                }
+            };
+            if self.pause_for_tmx()? {
+                break;
+            }
+            if self.seed_candidate(&candidate)? {
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    /// like [`Client::seed_maps`], but with the given maps, in random order
+    pub fn seed_map_ids(&mut self, ids: &[u64]) -> Result<usize, ClientError> {
+        let mut ids = ids.to_vec();
+        ids.shuffle(&mut rand::thread_rng());
+        let mut added = 0;
+        for (n, id) in ids.into_iter().enumerate() {
+            if n > 0 && self.pause_for_tmx()? {
+                break;
+            }
+            if self.seed_candidate(&MapCandidate::from_id(id))? {
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    /// a map that fails to download is skipped
+    fn seed_candidate(&mut self, candidate: &MapCandidate) -> Result<bool, ClientError> {
+        say!("downloading map {}", candidate.id);
+        match self.download_map_to(candidate.id, self.config().insert_mode) {
+            Err(ClientError::Download(err)) => {
+                self.report_error("downloading a map", err);
+                Ok(false)
+            }
+            res => res,
+        }
+    }
+
+    /// keeps handling messages for a random while, returns whether a shutdown
+    /// was requested in the meantime
+    fn pause_for_tmx(&mut self) -> Result<bool, ClientError> {
+        let (min, max) = self.config().bulk_jitter_ms;
+        let ms = rand::thread_rng().gen_range(min.min(max)..=max.max(min));
+        debug!("waiting {ms}ms before the next tmx request");
+        let deadline = Instant::now() + Duration::from_millis(ms);
+        while Instant::now() < deadline && !self.shutdown_requested() {
+            self.await_messages_until(Some(deadline))?;
+        }
+        Ok(self.shutdown_requested())
+    }
+
+    /// fills the rotation up to `min_maps`, for `seed_on_start`
+    ///
+    /// a rotation that has enough maps is left alone. offline there is no
+    /// tmx to seed from, and during the startup grace nothing is downloaded,
+    /// so both skip it. returns how many maps were added.
+    pub fn seed_to_min_maps(&mut self) -> Result<usize, ClientError> {
+        let min_maps = self.config().min_maps;
+        let len = self.get_map_list()?.len();
+        if len >= min_maps {
+            debug!("the rotation has {len} maps, no need to seed it");
+            return Ok(0);
+        }
+        if self.config().offline {
+            say!("the rotation has only {len} maps, but we are offline and cannot seed it");
+            return Ok(0);
+        }
+        if self.grace_left().is_some() {
+            say!("the rotation has only {len} maps, not seeding it during the startup grace");
+            return Ok(0);
+        }
+        let count = min_maps - len;
+        say!("the rotation has only {len} maps, adding {count} to reach {min_maps}");
+        let added = self.seed_maps(count)?;
+        say!("seeded {added} of {count} maps");
+        Ok(added)
+    }
+
+    /// `/seed <count>` adds that many maps right away
+    pub(crate) fn seed_command(&mut self, login: &str, args: &[&str]) -> Result<(), ClientError> {
+        let [count] = args else {
+            return self.chat_send_to_login(login, "usage: /seed <count>");
+        };
+        let Ok(count) = count.parse() else {
+            return self.chat_send_to_login(login, "usage: /seed <count>");
+        };
+        self.chat_send_to_login(login, &format!("adding {count} maps"))?;
+        let added = self.seed_maps(count)?;
+        self.chat_send_to_login(login, &format!("added {added} of {count} maps"))
+    }
+}
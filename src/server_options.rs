@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use dxr::{TryFromValue, Value};
+
+use crate::{strip_tm_formatting, Client, ClientError};
+
+/// the fields `SetServerOptions` takes, the others `GetServerOptions` returns
+/// are only there to be read
+const SETTABLE: &[&str] = &[
+    "Name",
+    "Comment",
+    "Password",
+    "PasswordForSpectator",
+    "NextCallVoteTimeOut",
+    "CallVoteRatio",
+    "NextMaxPlayers",
+    "NextMaxSpectators",
+    "KeepPlayerSlots",
+    "AllowMapDownload",
+    "AutoSaveReplays",
+    "RefereePassword",
+    "RefereeMode",
+    "AutoSaveValidationReplays",
+    "HideServer",
+    "UseChangingValidationSeed",
+    "ClientInputsMaxLatency",
+    "DisableHorns",
+    "DisableServiceAnnounces",
+];
+
+/// the server options as `GetServerOptions` returns them
+///
+/// the name and comment stay as the server has them, with their formatting
+/// codes, so writing the options back does not lose the colors. the
+/// `display_` versions are for logs and the status endpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerOptions {
+    fields: HashMap<String, Value>,
+}
+
+impl ServerOptions {
+    fn text(&self, field: &str) -> String {
+        self.fields
+            .get(field)
+            .and_then(|value| String::try_from_value(value).ok())
+            .unwrap_or_default()
+    }
+
+    /// the name with its formatting codes
+    pub fn name(&self) -> String {
+        self.text("Name")
+    }
+
+    pub fn display_name(&self) -> String {
+        strip_tm_formatting(&self.name())
+    }
+
+    /// the comment with its formatting codes
+    pub fn comment(&self) -> String {
+        self.text("Comment")
+    }
+
+    pub fn display_comment(&self) -> String {
+        strip_tm_formatting(&self.comment())
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.fields
+            .insert("Name".to_owned(), Value::string(name.to_owned()));
+    }
+
+    pub fn set_comment(&mut self, comment: &str) {
+        self.fields
+            .insert("Comment".to_owned(), Value::string(comment.to_owned()));
+    }
+
+    /// any other field, as the server sent it
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        self.fields.get(field)
+    }
+
+    /// the fields `SetServerOptions` accepts
+    fn settable(&self) -> HashMap<String, Value> {
+        self.fields
+            .iter()
+            .filter(|(name, _)| SETTABLE.contains(&name.as_str()))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+}
+
+impl Client {
+    /// with its formatting codes, like the name in the server options
+    pub fn get_server_name(&mut self) -> Result<String, ClientError> {
+        self.call("GetServerName", ())
+    }
+
+    pub fn get_server_options(&mut self) -> Result<ServerOptions, ClientError> {
+        let fields = self.call("GetServerOptions", ())?;
+        Ok(ServerOptions { fields })
+    }
+
+    /// writes back options read with [`Client::get_server_options`], with
+    /// whatever was changed in between
+    pub fn set_server_options(&mut self, options: &ServerOptions) -> Result<(), ClientError> {
+        let suc: bool = self.call("SetServerOptions", options.settable())?;
+        if !suc {
+            return Err(ClientError::Rejected("SetServerOptions"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use dxr::Value;
+
+    use super::ServerOptions;
+
+    #[test]
+    fn only_settable_fields_are_written_back_with_their_formatting() {
+        let mut fields = HashMap::new();
+        fields.insert(
+            "Name".to_owned(),
+            Value::string("$f00My $oServer".to_owned()),
+        );
+        fields.insert("CurrentMaxPlayers".to_owned(), Value::i4(32));
+        let mut options = ServerOptions { fields };
+        assert_eq!(options.display_name(), "My Server");
+
+        options.set_comment("$0f0welcome");
+        let settable = options.settable();
+        assert_eq!(settable.len(), 2);
+        assert_eq!(
+            settable["Name"],
+            Value::string("$f00My $oServer".to_owned())
+        );
+        assert_eq!(options.display_comment(), "welcome");
+    }
+}
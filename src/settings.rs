@@ -0,0 +1,67 @@
+use dxr::TryFromValue;
+
+use crate::{Client, ClientError, CurrentNext};
+
+/// names of boolean settings, for [`Client::set_bool_setting`] and
+/// [`Client::get_bool_setting`]
+pub mod bool_setting {
+    pub const SET_USE_CHANGING_VALIDATION_SEED: &str = "SetUseChangingValidationSeed";
+    pub const GET_USE_CHANGING_VALIDATION_SEED: &str = "GetUseChangingValidationSeed";
+    pub const SET_DISABLE_RESPAWN: &str = "SetDisableRespawn";
+    pub const GET_DISABLE_RESPAWN: &str = "GetDisableRespawn";
+    pub const DISABLE_HORNS: &str = "DisableHorns";
+    pub const GET_DISABLE_HORNS: &str = "GetDisableHorns";
+    pub const DISABLE_SERVICE_ANNOUNCES: &str = "DisableServiceAnnounces";
+    pub const ARE_SERVICE_ANNOUNCES_DISABLED: &str = "AreServiceAnnouncesDisabled";
+    pub const KEEP_PLAYER_SLOTS: &str = "KeepPlayerSlots";
+    pub const IS_KEEPING_PLAYER_SLOTS: &str = "IsKeepingPlayerSlots";
+    pub const SET_WARM_UP: &str = "SetWarmUp";
+    pub const GET_WARM_UP: &str = "GetWarmUp";
+    pub const AUTO_SAVE_REPLAYS: &str = "AutoSaveReplays";
+    pub const IS_AUTO_SAVE_REPLAYS_ENABLED: &str = "IsAutoSaveReplaysEnabled";
+}
+
+impl Client {
+    /// calls a setter like `SetDisableRespawn` that answers whether it worked
+    pub fn set_bool_setting(
+        &mut self,
+        method: &'static str,
+        value: bool,
+    ) -> Result<(), ClientError> {
+        self.set_setting(method, value)
+    }
+
+    /// calls a getter like `GetDisableRespawn`
+    ///
+    /// settings that apply right away come as a plain bool, those are the
+    /// current and the next value both.
+    pub fn get_bool_setting(
+        &mut self,
+        method: &'static str,
+    ) -> Result<CurrentNext<bool>, ClientError> {
+        let value = self.call_raw(method, Vec::new())?;
+        if let Ok(setting) = CurrentNext::try_from_value(&value) {
+            return Ok(setting);
+        }
+        let setting = bool::try_from_value(&value).map_err(|err| ClientError::Type {
+            method: method.to_owned(),
+            err,
+        })?;
+        Ok(CurrentNext {
+            CurrentValue: setting,
+            NextValue: setting,
+        })
+    }
+
+    pub(crate) fn set_setting(
+        &mut self,
+        method: &'static str,
+        value: impl dxr::TryToValue,
+    ) -> Result<(), ClientError> {
+        let suc: bool = self.call(method, (value,))?;
+        if !suc {
+            return Err(ClientError::Rejected(method));
+        }
+        Ok(())
+    }
+}
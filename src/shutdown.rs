@@ -0,0 +1,51 @@
+use std::sync::mpsc::Sender;
+
+use crate::{client::Event, Client, ConnectionState};
+
+/// asks a client to stop from another thread, like a signal handler
+///
+/// the client notices even while it waits for the server, since this goes
+/// through the same channel as everything the server sends.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    events: Sender<Event>,
+}
+
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        // the client is gone already if this fails
+        let _ = self.events.send(Event::Shutdown);
+    }
+}
+
+impl Client {
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            events: self.events_tx(),
+        }
+    }
+
+    /// inserts the staged maps, saves the black list, stops the callbacks and
+    /// disconnects
+    ///
+    /// errors are only printed, we are leaving either way.
+    pub fn close(&mut self) {
+        self.closing = true;
+        if self.is_connected() {
+            // otherwise the maps we downloaded for the next round are lost
+            if let Err(err) = self.insert_staged() {
+                say!("while inserting staged maps: {err}");
+            }
+            if let Some(file) = self.config().black_list_file.clone() {
+                if let Err(err) = self.save_black_list(&file) {
+                    say!("while saving the black list: {err}");
+                }
+            }
+            if let Err(err) = self.call::<bool>("EnableCallbacks", false) {
+                say!("while disabling callbacks: {err}");
+            }
+        }
+        self.disconnect();
+        self.state = ConnectionState::Disconnected;
+    }
+}
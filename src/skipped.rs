@@ -0,0 +1,71 @@
+use crate::{tmx_id_of, Client, ClientError, MapInfo};
+
+/// the map we inserted to be played next, checked on the next `BeginMap`
+#[derive(Debug, Clone)]
+pub(crate) struct ExpectedMap {
+    uid: String,
+    file_name: String,
+    map_type: String,
+}
+
+impl ExpectedMap {
+    pub(crate) fn new(info: &MapInfo) -> Self {
+        ExpectedMap {
+            uid: info.UId.clone(),
+            file_name: info.FileName.clone(),
+            map_type: info.MapType.clone(),
+        }
+    }
+}
+
+impl Client {
+    /// blocklists the inserted map if the server skipped it
+    ///
+    /// the server accepts maps the mode cannot play, and only skips them once
+    /// it gets to them. we know it did when the map after the inserted one
+    /// started instead, any other map means someone chose it. the map added
+    /// on this `BeginMap` takes the place of the skipped one.
+    pub(crate) fn check_expected_map(&mut self) -> Result<(), ClientError> {
+        let Some(expected) = self.expected_next.take() else {
+            return Ok(());
+        };
+        let Some(current) = &self.current_map else {
+            return Ok(());
+        };
+        if current.UId == expected.uid {
+            return Ok(());
+        }
+        let current = current.UId.clone();
+        let maps = self.get_map_list()?;
+        let Some(index) = maps.iter().position(|m| m.UId == expected.uid) else {
+            // removed before it had its turn
+            return Ok(());
+        };
+        if maps[(index + 1) % maps.len()].UId != current {
+            debug!(
+                "{} was not played, another map was chosen",
+                expected.file_name
+            );
+            return Ok(());
+        }
+
+        let reason = match self.get_mode_script_info() {
+            Ok(info) if !info.map_types().any(|t| expected.map_type.ends_with(t)) => {
+                format!(
+                    ", {} is not one of {}",
+                    expected.map_type, info.CompatibleMapTypes
+                )
+            }
+            Err(err) if err.is_connection() => return Err(err),
+            _ => String::new(),
+        };
+        say!("the server skipped {}{reason}", expected.file_name);
+        match tmx_id_of(&expected.file_name) {
+            Some(id) => self.blocklist_map(id, &expected.file_name),
+            None => {
+                say!("{} is not from tmx, it stays", expected.file_name);
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::{Client, ClientError};
+
+/// what `ForceSpectator` allows a player to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpectatorMode {
+    /// the player picks again, this undoes the others
+    UserSelectable,
+    Spectator,
+    Player,
+    /// a spectator for now, but they can switch back themselves
+    SpectatorSelectable,
+}
+
+impl SpectatorMode {
+    pub fn to_i32(self) -> i32 {
+        match self {
+            SpectatorMode::UserSelectable => 0,
+            SpectatorMode::Spectator => 1,
+            SpectatorMode::Player => 2,
+            SpectatorMode::SpectatorSelectable => 3,
+        }
+    }
+}
+
+/// how a spectator watches the player it is forced onto
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpectatorCamera {
+    /// whatever it was using before
+    Unchanged,
+    Replay,
+    Follow,
+    Free,
+}
+
+impl SpectatorCamera {
+    pub fn to_i32(self) -> i32 {
+        match self {
+            SpectatorCamera::Unchanged => -1,
+            SpectatorCamera::Replay => 0,
+            SpectatorCamera::Follow => 1,
+            SpectatorCamera::Free => 2,
+        }
+    }
+}
+
+/// the server faults like this for logins that are not connected
+fn player_not_found(err: ClientError, login: &str) -> ClientError {
+    match err {
+        ClientError::Fault(fault) if fault.string().to_lowercase().contains("unknown") => {
+            ClientError::PlayerNotFound(login.to_owned())
+        }
+        err => err,
+    }
+}
+
+impl Client {
+    pub fn force_spectator(&mut self, login: &str, mode: SpectatorMode) -> Result<(), ClientError> {
+        let suc: bool = self
+            .call("ForceSpectator", (login, mode.to_i32()))
+            .map_err(|err| player_not_found(err, login))?;
+        if !suc {
+            return Err(ClientError::Rejected("ForceSpectator"));
+        }
+        Ok(())
+    }
+
+    /// makes `spectator` watch `target`, an empty target lets them watch
+    /// whoever they like again
+    pub fn force_spectator_target(
+        &mut self,
+        spectator: &str,
+        target: &str,
+        camera: SpectatorCamera,
+    ) -> Result<(), ClientError> {
+        let suc: bool = self
+            .call("ForceSpectatorTarget", (spectator, target, camera.to_i32()))
+            .map_err(|err| player_not_found(err, spectator))?;
+        if !suc {
+            return Err(ClientError::Rejected("ForceSpectatorTarget"));
+        }
+        Ok(())
+    }
+
+    /// `/force <login> <spectator|player|free>`
+    pub(crate) fn force_command(&mut self, login: &str, args: &[&str]) -> Result<(), ClientError> {
+        let (player, mode) = match args {
+            [player, "spectator"] => (*player, SpectatorMode::Spectator),
+            [player, "player"] => (*player, SpectatorMode::Player),
+            [player, "free"] => (*player, SpectatorMode::UserSelectable),
+            _ => {
+                return self
+                    .chat_send_to_login(login, "usage: /force <login> <spectator|player|free>")
+            }
+        };
+        let reply = match self.force_spectator(player, mode) {
+            Ok(()) if mode == SpectatorMode::UserSelectable => format!("{player} can choose again"),
+            Ok(()) => format!("{player} is now a {}", args[1]),
+            Err(err) if !err.is_connection() => err.to_string(),
+            Err(err) => return Err(err),
+        };
+        self.chat_send_to_login(login, &reply)
+    }
+
+    /// `/cast <spectator> [target]`, without a target the spectator is free
+    /// to watch anyone again
+    pub(crate) fn cast_command(&mut self, login: &str, args: &[&str]) -> Result<(), ClientError> {
+        let (spectator, target) = match args {
+            [spectator] => (*spectator, ""),
+            [spectator, target] => (*spectator, *target),
+            _ => return self.chat_send_to_login(login, "usage: /cast <spectator> [target]"),
+        };
+        let reply = match self.force_spectator_target(spectator, target, SpectatorCamera::Unchanged)
+        {
+            Ok(()) if target.is_empty() => format!("{spectator} can watch anyone again"),
+            Ok(()) => format!("{spectator} is now watching {target}"),
+            Err(err) if !err.is_connection() => err.to_string(),
+            Err(err) => return Err(err),
+        };
+        self.chat_send_to_login(login, &reply)
+    }
+}
@@ -0,0 +1,23 @@
+use dxr::Value;
+
+use crate::{Client, ClientError};
+
+impl Client {
+    /// inserts every map that was downloaded but held back by `deferred_insert`
+    pub(crate) fn insert_staged(&mut self) -> Result<(), ClientError> {
+        while let Some(candidate) = self.staged.pop_front() {
+            say!("inserting staged map {}", candidate.id);
+            let mode = self.config().insert_mode;
+            if self.add_map(&self.map_path(candidate.id), mode)? {
+                self.apply_time_budget(&candidate)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// players and spectators currently on the server
+    pub fn player_count(&mut self) -> Result<usize, ClientError> {
+        let players: Vec<Value> = self.call_paged("GetPlayerList")?;
+        Ok(players.len())
+    }
+}
@@ -0,0 +1,55 @@
+use crate::{output::has_log_prefix, set_log_prefix, strip_tm_formatting, Client, ClientError};
+
+/// `None` if we could not find out, the startup line says "unknown" then
+fn known<T>(what: &str, res: Result<T, ClientError>) -> Result<Option<T>, ClientError> {
+    match res {
+        Ok(val) => Ok(Some(val)),
+        Err(err) if !err.is_connection() => {
+            debug!("no {what} for the startup line: {err}");
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+impl Client {
+    /// the name of the server as it was on the last connect, without its
+    /// formatting codes
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
+    }
+
+    /// says which server we are connected to, in one line
+    ///
+    /// without an `id` in the config, the name of the server also replaces
+    /// the address in front of the log lines of this server.
+    pub(crate) fn log_server_info(&mut self) -> Result<(), ClientError> {
+        let name = self.get_server_name();
+        let name = known("server name", name)?.map(|name| strip_tm_formatting(&name));
+        if let Some(name) = name.as_ref().filter(|name| !name.is_empty()) {
+            self.server_name = Some(name.clone());
+            if self.config().id.is_none() && has_log_prefix() {
+                set_log_prefix(name.as_str());
+            }
+        }
+        let version = self.get_version();
+        let version = known("version", version)?;
+        let maps_dir = self.call::<String>("GetMapsDirectory", ());
+        let maps_dir = known("maps directory", maps_dir)?;
+        let maps = self.get_map_list();
+        let maps = known("map count", maps)?;
+
+        let unknown = || "unknown".to_owned();
+        say!(
+            "connected to {:?} title={} version={} build={} api={} maps_dir={:?} maps={}",
+            name.unwrap_or_else(unknown),
+            version.as_ref().map_or_else(unknown, |v| v.TitleId.clone()),
+            version.as_ref().map_or_else(unknown, |v| v.Version.clone()),
+            version.as_ref().map_or_else(unknown, |v| v.Build.clone()),
+            self.api_version().unwrap_or("unknown"),
+            maps_dir.unwrap_or_else(unknown),
+            maps.map_or_else(unknown, |maps| maps.len().to_string()),
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,496 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    export::match_settings_xml, Blocklist, Catalog, Client, ClientError, Config, History,
+    HistoryEntry,
+};
+
+/// the version of the files we write, bump it with every change to what is
+/// in them and teach [`migrate`] how to upgrade the older ones
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// what the files look like from version 1 on
+#[derive(Serialize, Deserialize)]
+struct Versioned<T> {
+    schema_version: u32,
+    data: T,
+}
+
+/// the version of a file, 0 for the files from before they had one
+pub fn schema_version(value: &Value) -> u32 {
+    match value.get("schema_version").and_then(Value::as_u64) {
+        Some(version) => u32::try_from(version).unwrap_or(u32::MAX),
+        None => 0,
+    }
+}
+
+/// which of the state files is migrated, they upgrade differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateFile {
+    Blocklist,
+    History,
+    TagFactors,
+    Catalog,
+}
+
+/// upgrades the contents of a file from `old_version` to [`SCHEMA_VERSION`],
+/// one version at a time
+pub fn migrate(file: StateFile, old_version: u32, value: Value) -> Value {
+    (old_version..SCHEMA_VERSION).fold(value, |value, version| match (version, file) {
+        // the history was a list of ids before it knew when they were picked
+        (0, StateFile::History) => {
+            json!({ "schema_version": 1, "data": history_with_pick_times(value) })
+        }
+        // just the data, which stays as it was
+        (0, _) => json!({ "schema_version": 1, "data": value }),
+        _ => unreachable!("no migration from version {version}"),
+    })
+}
+
+/// turns the ids of an old history into entries without a pick time
+fn history_with_pick_times(value: Value) -> Value {
+    match value {
+        Value::Array(entries) => entries
+            .into_iter()
+            .map(|entry| match entry {
+                Value::Number(id) => json!({ "id": id, "picked_at": 0 }),
+                entry => entry,
+            })
+            .collect(),
+        value => value,
+    }
+}
+
+/// moves a file we can not read out of the way, so it is not overwritten by
+/// the next save and can be looked at, or read by a newer controller
+fn back_up(path: &Path, version: u32, why: &str) -> io::Result<()> {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".v{version}.bak"));
+    let backup = PathBuf::from(backup);
+    fs::rename(path, &backup)?;
+    say!(
+        "{} {why}, starting fresh and keeping it as {}",
+        path.display(),
+        backup.display()
+    );
+    Ok(())
+}
+
+/// reads a file in any version up to ours, `None` if there is none
+///
+/// files from a newer version, or that do not make sense, are backed up and
+/// treated as missing.
+pub(crate) fn read_versioned<T: DeserializeOwned>(
+    path: &Path,
+    file: StateFile,
+) -> io::Result<Option<T>> {
+    let Some(text) = not_found_as_none(fs::read_to_string(path))? else {
+        return Ok(None);
+    };
+    let value: Value = match serde_json::from_str(&text) {
+        Ok(value) => value,
+        Err(err) => {
+            back_up(path, 0, &format!("is not json ({err})"))?;
+            return Ok(None);
+        }
+    };
+    let version = schema_version(&value);
+    if version > SCHEMA_VERSION {
+        back_up(
+            path,
+            version,
+            &format!("is from a newer version ({version})"),
+        )?;
+        return Ok(None);
+    }
+    match serde_json::from_value::<Versioned<T>>(migrate(file, version, value)) {
+        Ok(file) => Ok(Some(file.data)),
+        Err(err) => {
+            back_up(path, version, &format!("could not be read ({err})"))?;
+            Ok(None)
+        }
+    }
+}
+
+pub(crate) fn write_versioned<T: Serialize>(path: &Path, data: &T) -> io::Result<()> {
+    let file = Versioned {
+        schema_version: SCHEMA_VERSION,
+        data,
+    };
+    fs::write(path, serde_json::to_string(&file)?)
+}
+
+/// where the state that outlives the controller is kept
+///
+/// loads return `None` when nothing was saved yet.
+pub trait Storage {
+    fn load_blocklist(&self) -> io::Result<Option<Blocklist>>;
+    fn save_blocklist(&mut self, blocklist: &Blocklist) -> io::Result<()>;
+
+    /// the recently picked maps, oldest first
+    fn load_history(&self) -> io::Result<Option<Vec<HistoryEntry>>>;
+    fn save_history(&mut self, entries: &[HistoryEntry]) -> io::Result<()>;
+
+    /// the share of their weight disliked tags have, see [`crate::TagDecay`]
+    fn load_tag_factors(&self) -> io::Result<Option<HashMap<u32, f64>>>;
+    fn save_tag_factors(&mut self, factors: &HashMap<u32, f64>) -> io::Result<()>;
+
+    fn load_catalog(&self) -> io::Result<Option<Catalog>>;
+    fn save_catalog(&mut self, catalog: &Catalog) -> io::Result<()>;
+
+    /// the map list as match settings xml, see `match_settings_path`
+    fn load_match_settings(&self) -> io::Result<Option<String>>;
+    fn save_match_settings(&mut self, xml: &str) -> io::Result<()>;
+}
+
+/// keeps everything in json files, at the paths from the config
+///
+/// the files carry their [`SCHEMA_VERSION`], older ones are upgraded when
+/// they are loaded.
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    pub blocklist_path: PathBuf,
+    /// the history is not kept when this is `None`
+    pub history_path: Option<PathBuf>,
+    /// the tag decay is not kept when this is `None`
+    pub tag_decay_path: Option<PathBuf>,
+    pub catalog_path: PathBuf,
+    /// written as it is, without a version, so the server can read it
+    pub match_settings_path: Option<PathBuf>,
+}
+
+impl FileStorage {
+    pub fn new(config: &Config) -> Self {
+        FileStorage {
+            blocklist_path: config.blocklist_path.clone(),
+            history_path: config.history_path.clone(),
+            tag_decay_path: config.tag_decay_path.clone(),
+            catalog_path: config.catalog_path.clone(),
+            match_settings_path: config.match_settings_path.clone(),
+        }
+    }
+}
+
+/// `None` if the file does not exist
+fn not_found_as_none<T>(res: io::Result<T>) -> io::Result<Option<T>> {
+    match res {
+        Ok(val) => Ok(Some(val)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+impl Storage for FileStorage {
+    fn load_blocklist(&self) -> io::Result<Option<Blocklist>> {
+        Blocklist::load(&self.blocklist_path)
+    }
+
+    fn save_blocklist(&mut self, blocklist: &Blocklist) -> io::Result<()> {
+        blocklist.save(&self.blocklist_path)
+    }
+
+    fn load_history(&self) -> io::Result<Option<Vec<HistoryEntry>>> {
+        let Some(path) = &self.history_path else {
+            return Ok(None);
+        };
+        read_versioned(path, StateFile::History)
+    }
+
+    fn save_history(&mut self, entries: &[HistoryEntry]) -> io::Result<()> {
+        let Some(path) = &self.history_path else {
+            return Ok(());
+        };
+        write_versioned(path, &entries)
+    }
+
+    fn load_tag_factors(&self) -> io::Result<Option<HashMap<u32, f64>>> {
+        let Some(path) = &self.tag_decay_path else {
+            return Ok(None);
+        };
+        read_versioned(path, StateFile::TagFactors)
+    }
+
+    fn save_tag_factors(&mut self, factors: &HashMap<u32, f64>) -> io::Result<()> {
+        let Some(path) = &self.tag_decay_path else {
+            return Ok(());
+        };
+        write_versioned(path, factors)
+    }
+
+    fn load_catalog(&self) -> io::Result<Option<Catalog>> {
+        read_versioned(&self.catalog_path, StateFile::Catalog)
+    }
+
+    fn save_catalog(&mut self, catalog: &Catalog) -> io::Result<()> {
+        write_versioned(&self.catalog_path, catalog)
+    }
+
+    fn load_match_settings(&self) -> io::Result<Option<String>> {
+        let Some(path) = &self.match_settings_path else {
+            return Ok(None);
+        };
+        not_found_as_none(fs::read_to_string(path))
+    }
+
+    fn save_match_settings(&mut self, xml: &str) -> io::Result<()> {
+        let Some(path) = &self.match_settings_path else {
+            return Ok(());
+        };
+        fs::write(path, xml)
+    }
+}
+
+/// keeps everything in memory, clones share their contents
+///
+/// useful to run a [`Client`] without touching the disk.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    state: Arc<Mutex<MemoryState>>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    blocklist: Option<Blocklist>,
+    history: Option<Vec<HistoryEntry>>,
+    tag_factors: Option<HashMap<u32, f64>>,
+    catalog: Option<Catalog>,
+    match_settings: Option<String>,
+}
+
+impl MemoryStorage {
+    pub fn blocklist(&self) -> Option<Blocklist> {
+        self.state.lock().unwrap().blocklist.clone()
+    }
+
+    pub fn history(&self) -> Option<Vec<HistoryEntry>> {
+        self.state.lock().unwrap().history.clone()
+    }
+
+    pub fn tag_factors(&self) -> Option<HashMap<u32, f64>> {
+        self.state.lock().unwrap().tag_factors.clone()
+    }
+
+    pub fn catalog(&self) -> Option<Catalog> {
+        self.state.lock().unwrap().catalog.clone()
+    }
+
+    pub fn match_settings(&self) -> Option<String> {
+        self.state.lock().unwrap().match_settings.clone()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn load_blocklist(&self) -> io::Result<Option<Blocklist>> {
+        Ok(self.blocklist())
+    }
+
+    fn save_blocklist(&mut self, blocklist: &Blocklist) -> io::Result<()> {
+        self.state.lock().unwrap().blocklist = Some(blocklist.clone());
+        Ok(())
+    }
+
+    fn load_history(&self) -> io::Result<Option<Vec<HistoryEntry>>> {
+        Ok(self.history())
+    }
+
+    fn save_history(&mut self, entries: &[HistoryEntry]) -> io::Result<()> {
+        self.state.lock().unwrap().history = Some(entries.to_vec());
+        Ok(())
+    }
+
+    fn load_tag_factors(&self) -> io::Result<Option<HashMap<u32, f64>>> {
+        Ok(self.tag_factors())
+    }
+
+    fn save_tag_factors(&mut self, factors: &HashMap<u32, f64>) -> io::Result<()> {
+        self.state.lock().unwrap().tag_factors = Some(factors.clone());
+        Ok(())
+    }
+
+    fn load_catalog(&self) -> io::Result<Option<Catalog>> {
+        Ok(self.catalog())
+    }
+
+    fn save_catalog(&mut self, catalog: &Catalog) -> io::Result<()> {
+        self.state.lock().unwrap().catalog = Some(catalog.clone());
+        Ok(())
+    }
+
+    fn load_match_settings(&self) -> io::Result<Option<String>> {
+        Ok(self.match_settings())
+    }
+
+    fn save_match_settings(&mut self, xml: &str) -> io::Result<()> {
+        self.state.lock().unwrap().match_settings = Some(xml.to_owned());
+        Ok(())
+    }
+}
+
+impl Client {
+    /// replaces where the state is kept, and loads it from there
+    pub fn set_storage(&mut self, storage: Box<dyn Storage>) {
+        self.storage = storage;
+        self.load_state();
+    }
+
+    /// starts empty for whatever could not be loaded
+    pub(crate) fn load_state(&mut self) {
+        self.blocklist = match self.storage.load_blocklist() {
+            Ok(blocklist) => blocklist.unwrap_or_default(),
+            Err(err) => {
+                say!("could not load the blocklist: {err}");
+                Blocklist::default()
+            }
+        };
+
+        let mut history =
+            History::new(self.config().history_size).with_window(self.config().no_repeat_window);
+        match self.storage.load_history() {
+            Ok(entries) => entries
+                .into_iter()
+                .flatten()
+                .for_each(|entry| history.push_entry(entry)),
+            Err(err) => say!("could not load the history: {err}"),
+        }
+        self.history = history;
+
+        self.tag_factors = match self.storage.load_tag_factors() {
+            Ok(factors) => factors.unwrap_or_default(),
+            Err(err) => {
+                say!("could not load the tag decay: {err}");
+                HashMap::new()
+            }
+        };
+
+        self.catalog = match self.storage.load_catalog() {
+            Ok(catalog) => catalog.unwrap_or_default(),
+            Err(err) => {
+                say!("could not load the catalog: {err}");
+                Catalog::default()
+            }
+        };
+    }
+
+    pub(crate) fn save_blocklist(&mut self) {
+        if let Err(err) = self.storage.save_blocklist(&self.blocklist) {
+            say!("could not save the blocklist: {err}");
+        }
+    }
+
+    pub(crate) fn save_history(&mut self) {
+        let entries: Vec<HistoryEntry> = self.history.entries().copied().collect();
+        if let Err(err) = self.storage.save_history(&entries) {
+            say!("could not save the history: {err}");
+        }
+    }
+
+    pub(crate) fn save_tag_factors(&mut self) {
+        if let Err(err) = self.storage.save_tag_factors(&self.tag_factors) {
+            say!("could not save the tag decay: {err}");
+        }
+    }
+
+    pub(crate) fn save_catalog(&mut self) {
+        if let Err(err) = self.storage.save_catalog(&self.catalog) {
+            say!("could not save the catalog: {err}");
+        }
+    }
+
+    /// writes the map list as match settings, if `match_settings_path` is set
+    pub(crate) fn save_match_settings(&mut self) -> Result<(), ClientError> {
+        if self.config().match_settings_path.is_none() {
+            return Ok(());
+        }
+        let maps = self.get_map_list()?;
+        let xml = match_settings_xml(maps.iter().map(|map| map.FileName.as_str()));
+        if let Err(err) = self.storage.save_match_settings(&xml) {
+            say!("could not save the match settings: {err}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::temp_dir, CatalogEntry, MapOrigin};
+
+    fn file_storage(dir: &Path) -> FileStorage {
+        let config = Config {
+            blocklist_path: dir.join("blocklist.json"),
+            history_path: Some(dir.join("history.json")),
+            catalog_path: dir.join("catalog.json"),
+            match_settings_path: Some(dir.join("tracklist.txt")),
+            ..Config::default()
+        };
+        FileStorage::new(&config)
+    }
+
+    #[test]
+    fn files_from_before_versions_are_upgraded() {
+        assert_eq!(schema_version(&json!([1, 2])), 0);
+        let upgraded = migrate(StateFile::Blocklist, 0, json!([1, 2]));
+        assert_eq!(upgraded, json!({ "schema_version": 1, "data": [1, 2] }));
+        assert_eq!(schema_version(&upgraded), SCHEMA_VERSION);
+        // histories from before and after they knew the pick times
+        let upgraded = migrate(
+            StateFile::History,
+            0,
+            json!([1, { "id": 2, "picked_at": 5 }]),
+        );
+        let entries = json!([{ "id": 1, "picked_at": 0 }, { "id": 2, "picked_at": 5 }]);
+        assert_eq!(upgraded, json!({ "schema_version": 1, "data": entries }));
+
+        let dir = temp_dir("migrate");
+        // the history was a list of ids back then
+        fs::write(dir.join("history.json"), "[1, 2]").unwrap();
+        let entries = file_storage(&dir).load_history().unwrap().unwrap();
+        let ids: Vec<u64> = entries.iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, [1, 2]);
+        assert!(entries.iter().all(|entry| entry.picked_at == 0));
+    }
+
+    #[test]
+    fn unreadable_files_are_backed_up() {
+        let dir = temp_dir("backup");
+        let newer = json!({ "schema_version": SCHEMA_VERSION + 1, "data": [] });
+        fs::write(dir.join("history.json"), newer.to_string()).unwrap();
+        fs::write(dir.join("catalog.json"), "not json").unwrap();
+        let storage = file_storage(&dir);
+        assert_eq!(storage.load_history().unwrap(), None);
+        assert_eq!(storage.load_catalog().unwrap(), None);
+
+        let backup = format!("history.json.v{}.bak", SCHEMA_VERSION + 1);
+        assert!(dir.join(backup).exists());
+        assert!(dir.join("catalog.json.v0.bak").exists());
+        assert!(!dir.join("history.json").exists());
+    }
+
+    #[test]
+    fn the_catalog_and_match_settings_survive_a_restart() {
+        let dir = temp_dir("storage");
+        let mut storage = file_storage(&dir);
+        assert_eq!(storage.load_catalog().unwrap(), None);
+        assert_eq!(storage.load_match_settings().unwrap(), None);
+
+        let mut catalog = Catalog::default();
+        catalog.insert("1.Map.Gbx", CatalogEntry::new(MapOrigin::Tmx { id: 1 }));
+        storage.save_catalog(&catalog).unwrap();
+        storage.save_match_settings("<playlist/>").unwrap();
+
+        let storage = file_storage(&dir);
+        assert_eq!(storage.load_catalog().unwrap(), Some(catalog));
+        assert_eq!(
+            storage.load_match_settings().unwrap().as_deref(),
+            Some("<playlist/>")
+        );
+    }
+}
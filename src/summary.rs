@@ -0,0 +1,57 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::{Client, ClientError};
+
+/// a chat message every now and then about what happened this session
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionSummary {
+    #[serde(rename = "interval_secs", with = "crate::config::secs")]
+    pub interval: Duration,
+    /// `{maps}` is replaced with the maps played since the controller
+    /// started, `{players}` with the players online right now
+    pub template: String,
+    /// nobody would read it anyway
+    pub skip_when_empty: bool,
+}
+
+impl Default for SessionSummary {
+    fn default() -> Self {
+        SessionSummary {
+            interval: Duration::from_secs(30 * 60),
+            template: "{maps} maps played this session, {players} players online".to_owned(),
+            skip_when_empty: true,
+        }
+    }
+}
+
+impl Client {
+    /// how long the message loop may wait before the summary is due
+    pub(crate) fn summary_timeout(&self) -> Option<Duration> {
+        let interval = self.config().session_summary.as_ref()?.interval;
+        // it waits for calls of its own, so only the outermost loop sends it
+        if !self.is_connected() || !self.calls.is_empty() {
+            return None;
+        }
+        Some(interval.saturating_sub(self.last_summary.elapsed()))
+    }
+
+    pub(crate) fn send_session_summary(&mut self) -> Result<(), ClientError> {
+        self.last_summary = Instant::now();
+        let Some(summary) = self.config().session_summary.clone() else {
+            return Ok(());
+        };
+        let players = self.player_count()?;
+        if players == 0 && summary.skip_when_empty {
+            return Ok(());
+        }
+        let msg = summary
+            .template
+            .replace("{maps}", &self.stats.maps_played.to_string())
+            .replace("{players}", &players.to_string());
+        self.call::<bool>("ChatSendServerMessage", msg.as_str())?;
+        Ok(())
+    }
+}
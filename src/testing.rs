@@ -0,0 +1,240 @@
+//! a scripted server and helpers for the tests of the other modules
+
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use dxr::{Fault, FaultResponse, MethodCall, MethodResponse, TryToValue, Value};
+
+use crate::{Client, Config, MapInfo, MemoryStorage};
+
+/// what the server answers a call with
+#[derive(Clone)]
+pub(crate) enum Reply {
+    Value(Value),
+    Fault(i32, &'static str),
+    /// sends the callback with this xml first, see [`callback_xml`], then
+    /// answers with the reply
+    Callback(String, Box<Reply>),
+}
+
+impl Reply {
+    pub fn of(value: impl TryToValue) -> Reply {
+        Reply::Value(value.try_to_value().unwrap())
+    }
+}
+
+/// every call so far, with its params
+type Calls = Mutex<Vec<(String, Vec<Value>)>>;
+
+type Handler = dyn Fn(&str, &[Value]) -> Option<Reply> + Send + Sync;
+
+/// answers calls with what the handler says, `true` when it says `None`
+///
+/// every connection is accepted, so reconnects work. the calls are recorded
+/// in the order they arrived, see [`MockServer::params_of`].
+pub(crate) struct MockServer {
+    addr: SocketAddr,
+    calls: Arc<Calls>,
+    conns: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl MockServer {
+    pub fn start(
+        handler: impl Fn(&str, &[Value]) -> Option<Reply> + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+        let conns = Arc::new(Mutex::new(Vec::new()));
+        let (thread_calls, thread_conns) = (calls.clone(), conns.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { return };
+                if let Ok(clone) = stream.try_clone() {
+                    thread_conns.lock().unwrap().push(clone);
+                }
+                let (calls, handler) = (thread_calls.clone(), handler.clone());
+                thread::spawn(move || serve(stream, &calls, &*handler));
+            }
+        });
+        MockServer { addr, calls, conns }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// the params of every call to `method` so far
+    pub fn params_of(&self, method: &str) -> Vec<Vec<Value>> {
+        let calls = self.calls.lock().unwrap();
+        calls
+            .iter()
+            .filter(|(name, _)| name == method)
+            .map(|(_, params)| params.clone())
+            .collect()
+    }
+
+    /// closes every connection so far, like a server that restarts
+    pub fn drop_connections(&self) {
+        for stream in self.conns.lock().unwrap().drain(..) {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+fn serve(mut stream: TcpStream, calls: &Calls, handler: &Handler) {
+    let hello = b"GBXRemote 2";
+    let _ = stream.write_all(&(hello.len() as u32).to_le_bytes());
+    let _ = stream.write_all(hello);
+    while let Ok((handle, body)) = read_frame(&mut stream) {
+        let call: MethodCall = dxr::deserialize_xml(&body).unwrap();
+        let (method, params) = (call.name().to_owned(), call.params());
+        calls.lock().unwrap().push((method.clone(), params.clone()));
+        let reply = handler(&method, &params).unwrap_or(Reply::Value(Value::boolean(true)));
+        if respond(&mut stream, handle, reply).is_err() {
+            return;
+        }
+    }
+}
+
+fn respond(stream: &mut TcpStream, handle: u32, reply: Reply) -> io::Result<()> {
+    let msg = match reply {
+        Reply::Value(value) => dxr::serialize_xml(&MethodResponse::new(value)).unwrap(),
+        Reply::Fault(code, string) => {
+            let fault = FaultResponse::from(Fault::new(code, string.to_owned()));
+            dxr::serialize_xml(&fault).unwrap()
+        }
+        Reply::Callback(xml, reply) => {
+            // callbacks have handles without the top bit
+            write_frame(stream, 1, &xml)?;
+            return respond(stream, handle, *reply);
+        }
+    };
+    write_frame(stream, handle, &msg)
+}
+
+fn read_frame(stream: &mut TcpStream) -> io::Result<(u32, String)> {
+    let mut header = [0; 8];
+    stream.read_exact(&mut header)?;
+    let len = u32::from_le_bytes(header[..4].try_into().unwrap());
+    let handle = u32::from_le_bytes(header[4..].try_into().unwrap());
+    let mut body = vec![0; len as usize];
+    stream.read_exact(&mut body)?;
+    Ok((handle, String::from_utf8(body).unwrap()))
+}
+
+fn write_frame(stream: &mut TcpStream, handle: u32, msg: &str) -> io::Result<()> {
+    let mut frame = (msg.len() as u32).to_le_bytes().to_vec();
+    frame.extend(handle.to_le_bytes());
+    frame.extend(msg.as_bytes());
+    stream.write_all(&frame)
+}
+
+/// collects what is written to it, clones share their contents
+#[derive(Clone, Default)]
+pub(crate) struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn lines(&self) -> Vec<String> {
+        let bytes = self.0.lock().unwrap();
+        String::from_utf8_lossy(&bytes)
+            .lines()
+            .map(str::to_owned)
+            .collect()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// a config for `server` that keeps nothing on disk
+pub(crate) fn test_config(server: &MockServer) -> Config {
+    Config {
+        address: server.addr().to_string(),
+        ..Config::default()
+    }
+}
+
+/// a client logged in to `server`, with its state in memory
+pub(crate) fn test_client(config: Config) -> Client {
+    let mut client = Client::new(config);
+    client.set_storage(Box::new(MemoryStorage::default()));
+    client.log_in().unwrap();
+    client
+}
+
+/// what `GetMapList` and the like return for the map at `file_name`
+pub(crate) fn map_info(file_name: &str, uid: &str) -> MapInfo {
+    MapInfo {
+        Name: file_name.to_owned(),
+        UId: uid.to_owned(),
+        FileName: file_name.to_owned(),
+        Environnement: "Stadium".to_owned(),
+        Author: "author".to_owned(),
+        AuthorNickname: "author".to_owned(),
+        GoldTime: 30000,
+        CopperPrice: 100,
+        MapType: "TrackMania\\TM_Race".to_owned(),
+        MapStyle: String::new(),
+    }
+}
+
+/// the smallest map file [`crate::GbxMapHeader::parse`] reads
+pub(crate) fn gbx_map(uid: &str, name: &str) -> Vec<u8> {
+    let xml = format!(
+        "<header type=\"map\"><ident uid=\"{uid}\" name=\"{name}\" author=\"author\"/>\
+         <desc envir=\"Stadium\" maptype=\"TrackMania\\TM_Race\"/></header>"
+    );
+    let mut chunk = (xml.len() as u32).to_le_bytes().to_vec();
+    chunk.extend(xml.as_bytes());
+    let mut file = b"GBX".to_vec();
+    file.extend(6u16.to_le_bytes());
+    file.extend(b"BUCR");
+    // the class of maps, and the size of the user data, which is not checked
+    file.extend(0x0304_3000u32.to_le_bytes());
+    file.extend(0u32.to_le_bytes());
+    file.extend(1u32.to_le_bytes());
+    file.extend(0x0304_3005u32.to_le_bytes());
+    file.extend((chunk.len() as u32).to_le_bytes());
+    file.extend(chunk);
+    file
+}
+
+/// an empty directory of its own for each test
+pub(crate) fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("controller-test-{name}-{}", rand::random::<u32>()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// the xml of a callback, for [`Client::handle_callback`]
+pub(crate) fn callback_xml(method: &str, params: Vec<Value>) -> String {
+    dxr::serialize_xml(&MethodCall::new(method.to_owned(), params)).unwrap()
+}
+
+/// `login` saying `text` in chat
+pub(crate) fn chat_xml(login: &str, text: &str) -> String {
+    callback_xml(
+        "ManiaPlanet.PlayerChat",
+        vec![
+            Value::i4(1),
+            Value::string(login.to_owned()),
+            Value::string(text.to_owned()),
+            Value::boolean(true),
+        ],
+    )
+}
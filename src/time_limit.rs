@@ -0,0 +1,76 @@
+use std::{collections::HashMap, time::Duration};
+
+use dxr::{TryFromValue, Value};
+use serde::Deserialize;
+
+use crate::{Client, ClientError, CurrentNext};
+
+/// derives the time limit of a map from its author time
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeBudget {
+    /// the limit is the author time multiplied by this
+    pub multiplier: f64,
+    /// bounds for the limit, in case tmx reports a nonsense author time
+    #[serde(rename = "min_secs", with = "crate::config::secs")]
+    pub min: Duration,
+    #[serde(rename = "max_secs", with = "crate::config::secs")]
+    pub max: Duration,
+}
+
+impl Default for TimeBudget {
+    fn default() -> Self {
+        TimeBudget {
+            multiplier: 5.0,
+            min: Duration::from_secs(60),
+            max: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+impl TimeBudget {
+    pub fn limit_for(&self, author_time: Duration) -> Duration {
+        author_time
+            .mul_f64(self.multiplier)
+            .clamp(self.min, self.max)
+    }
+}
+
+impl Client {
+    pub fn get_time_attack_limit(&mut self) -> Result<CurrentNext<i32>, ClientError> {
+        self.call("GetTimeAttackLimit", ())
+    }
+
+    /// makes sure the map that just started is played with the configured time limit
+    ///
+    /// script modes get their `S_TimeLimit` setting changed, which applies
+    /// right away. legacy modes only take a new limit from the next map on.
+    pub(crate) fn reassert_time_limit(&mut self, limit: Duration) -> Result<(), ClientError> {
+        let secs = limit.as_secs().min(i32::MAX as u64) as i32;
+
+        match self.get_mode_script_settings() {
+            Ok(settings) => {
+                let Some(current) = settings.get("S_TimeLimit") else {
+                    say!("this mode has no time limit setting");
+                    return Ok(());
+                };
+                if i32::try_from_value(current).ok() == Some(secs) {
+                    return Ok(());
+                }
+                say!("setting S_TimeLimit to {secs}s");
+                let settings = HashMap::from([("S_TimeLimit".to_owned(), Value::i4(secs))]);
+                self.set_mode_script_settings(settings)
+            }
+            // not a script mode
+            Err(ClientError::Fault(_)) => {
+                let ms = secs.saturating_mul(1000);
+                if self.get_time_attack_limit()?.NextValue == ms {
+                    return Ok(());
+                }
+                say!("setting time attack limit to {secs}s");
+                self.set_time_attack_limit(limit)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
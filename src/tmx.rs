@@ -0,0 +1,570 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::{eyre, ContextCompat};
+use rand::{distributions::WeightedIndex, prelude::Distribution, rngs::StdRng, Rng, SeedableRng};
+use serde::{
+    de::{self, DeserializeOwned, MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use url::{form_urlencoded, Url};
+
+use crate::History;
+
+/// the filters that are sent to the trackmania.exchange search
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MapSearch {
+    /// tmx tag ids, a map needs at least one of them
+    pub tags: Vec<u32>,
+    pub map_type: String,
+    /// only maps uploaded by this tmx user
+    pub author: Option<String>,
+    /// a tmx length id, like 2 for "30 secs"
+    pub length: Option<u32>,
+    /// how `length` compares, as tmx numbers it: 0 exactly, 1 at most, 2 at least
+    pub length_op: Option<u32>,
+    /// favors maps by their tags, a map weighs the sum of its tags' weights
+    ///
+    /// tags without a weight count as 1 and maps with a tag of weight 0 are
+    /// never picked. when this is empty, tmx picks the map.
+    pub tag_weights: HashMap<u32, f64>,
+    /// only maps from this tmx map pack, the other filters do not apply to
+    /// them but `tag_weights` does
+    pub pack_id: Option<u64>,
+}
+
+impl Default for MapSearch {
+    fn default() -> Self {
+        MapSearch {
+            tags: vec![23, 37, 40],
+            map_type: "TM_Race".to_owned(),
+            author: None,
+            length: None,
+            length_op: None,
+            tag_weights: HashMap::new(),
+            pack_id: None,
+        }
+    }
+}
+
+impl MapSearch {
+    pub fn to_query(&self) -> String {
+        let tags: Vec<String> = self.tags.iter().map(|t| t.to_string()).collect();
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        query.append_pair("etags", &tags.join(","));
+        query.append_pair("mtype", &self.map_type);
+        if let Some(author) = &self.author {
+            query.append_pair("author", author);
+        }
+        if let Some(length) = self.length {
+            query.append_pair("length", &length.to_string());
+        }
+        if let Some(op) = self.length_op {
+            query.append_pair("lengthop", &op.to_string());
+        }
+        query.finish()
+    }
+
+    /// the filters of a search on trackmania.exchange, as copied from the browser
+    ///
+    /// params we have no filter for, like the page, are ignored.
+    pub fn from_tmx_url(url: &str) -> Result<MapSearch, TmxUrlError> {
+        let url = Url::parse(url).map_err(TmxUrlError::Url)?;
+        match url.host_str() {
+            Some("trackmania.exchange" | "www.trackmania.exchange") => {}
+            host => return Err(TmxUrlError::WrongHost(host.unwrap_or_default().to_owned())),
+        }
+        // the search is /mapsearch2 on the current site, /mapsearch before
+        if !url.path().starts_with("/mapsearch") {
+            return Err(TmxUrlError::NotASearch(url.path().to_owned()));
+        }
+        MapSearch::from_query(url.query().unwrap_or_default())
+    }
+
+    /// the inverse of [`MapSearch::to_query`]
+    pub fn from_query(query: &str) -> Result<MapSearch, TmxUrlError> {
+        let mut search = MapSearch {
+            tags: Vec::new(),
+            ..MapSearch::default()
+        };
+        for (name, value) in form_urlencoded::parse(query.as_bytes()) {
+            let invalid = || TmxUrlError::InvalidParam {
+                name: name.to_string(),
+                value: value.to_string(),
+            };
+            match &*name {
+                "etags" => {
+                    search.tags = value
+                        .split(',')
+                        .filter(|t| !t.is_empty())
+                        .map(|t| t.trim().parse().map_err(|_| invalid()))
+                        .collect::<Result<_, _>>()?;
+                }
+                "mtype" => search.map_type = value.into_owned(),
+                "author" => search.author = Some(value.into_owned()),
+                "length" => search.length = Some(value.parse().map_err(|_| invalid())?),
+                "lengthop" => search.length_op = Some(value.parse().map_err(|_| invalid())?),
+                _ => {}
+            }
+        }
+        Ok(search)
+    }
+
+    /// how likely a map with these tags is to be picked, see `tag_weights`
+    pub fn weight(&self, tags: &[u32]) -> f64 {
+        let weights = tags
+            .iter()
+            .map(|t| self.tag_weights.get(t).copied().unwrap_or(1.0));
+        if tags.is_empty() {
+            1.0
+        } else if weights.clone().any(|w| w <= 0.0) {
+            0.0
+        } else {
+            weights.sum()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TmxUrlError {
+    Url(url::ParseError),
+    /// the url is not on trackmania.exchange
+    WrongHost(String),
+    /// the path of a page on trackmania.exchange that is not the map search
+    NotASearch(String),
+    InvalidParam {
+        name: String,
+        value: String,
+    },
+}
+
+impl fmt::Display for TmxUrlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TmxUrlError::Url(err) => write!(f, "invalid url: {err}"),
+            TmxUrlError::WrongHost(host) => write!(f, "{host:?} is not trackmania.exchange"),
+            TmxUrlError::NotASearch(path) => write!(f, "{path:?} is not the map search"),
+            TmxUrlError::InvalidParam { name, value } => write!(f, "invalid {name}: {value:?}"),
+        }
+    }
+}
+
+impl std::error::Error for TmxUrlError {}
+
+/// lets the config give the search as a table or as a tmx search url
+pub(crate) fn deserialize_search<'de, D: Deserializer<'de>>(de: D) -> Result<MapSearch, D::Error> {
+    struct SearchVisitor;
+
+    impl<'de> Visitor<'de> for SearchVisitor {
+        type Value = MapSearch;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a table or a trackmania.exchange search url")
+        }
+
+        fn visit_str<E: de::Error>(self, url: &str) -> Result<MapSearch, E> {
+            MapSearch::from_tmx_url(url).map_err(E::custom)
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<MapSearch, A::Error> {
+            MapSearch::deserialize(de::value::MapAccessDeserializer::new(map))
+        }
+    }
+
+    de.deserialize_any(SearchVisitor)
+}
+
+/// a map that could be played next
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapCandidate {
+    pub id: u64,
+    pub name: Option<String>,
+    /// the tmx user that uploaded it
+    pub author: Option<String>,
+    pub author_time: Option<Duration>,
+    /// as reported by tmx, e.g. "45 secs"
+    pub length: Option<String>,
+    /// as reported by tmx, e.g. "Intermediate"
+    pub difficulty: Option<String>,
+    /// tmx tag ids
+    pub tags: Vec<u32>,
+    /// as reported by tmx, e.g. "Stadium"
+    pub environment: Option<String>,
+    /// the uid the server knows the map by
+    pub uid: Option<String>,
+    /// the ubisoft login of whoever made the map
+    pub author_login: Option<String>,
+    pub award_count: Option<u32>,
+    /// the average of the votes on tmx
+    pub rating: Option<f64>,
+}
+
+impl MapCandidate {
+    /// a candidate we know nothing about except its id
+    pub fn from_id(id: u64) -> Self {
+        MapCandidate {
+            id,
+            name: None,
+            author: None,
+            author_time: None,
+            length: None,
+            difficulty: None,
+            tags: Vec::new(),
+            environment: None,
+            uid: None,
+            author_login: None,
+            award_count: None,
+            rating: None,
+        }
+    }
+}
+
+/// picks one of the candidates with a probability proportional to its weight
+/// under `search`, skipping the ones in `history`
+///
+/// `None` if every candidate weighs nothing.
+pub fn pick_weighted<'a>(
+    candidates: &'a [MapCandidate],
+    search: &MapSearch,
+    history: &History,
+    rng: &mut impl Rng,
+) -> Option<&'a MapCandidate> {
+    let weights = candidates.iter().map(|c| {
+        if history.contains(c.id) {
+            0.0
+        } else {
+            search.weight(&c.tags)
+        }
+    });
+    let index = WeightedIndex::new(weights).ok()?;
+    Some(&candidates[index.sample(rng)])
+}
+
+/// decides whether a candidate may be added, see [`crate::Client::set_map_filter`]
+pub type MapFilter = Box<dyn Fn(&MapCandidate) -> bool>;
+
+/// picks the maps that get added to the rotation
+///
+/// the rotation logic on [`crate::Client`] takes care of skipping recently
+/// played maps, so a source is allowed to return those.
+pub trait MapSource {
+    fn next_candidate(
+        &mut self,
+        search: &MapSearch,
+        history: &History,
+    ) -> color_eyre::Result<MapCandidate>;
+}
+
+/// how many pages of search results we pick from when weighting by tags
+const WEIGHTED_PAGES: u32 = 20;
+
+/// how long the maps of a pack are used before asking tmx again
+const PACK_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// asks trackmania.exchange for a random map
+///
+/// with `tag_weights`, it asks for a page of results instead and picks from
+/// those with [`pick_weighted`]. with a `pack_id`, it picks from the maps of
+/// the pack the same way.
+pub struct TmxMapSource {
+    exchange: reqwest::blocking::Client,
+    rng: StdRng,
+    /// the maps of each pack, and when we got them
+    packs: HashMap<u64, (Instant, Vec<MapCandidate>)>,
+}
+
+impl TmxMapSource {
+    pub fn new(exchange: reqwest::blocking::Client) -> Self {
+        TmxMapSource {
+            exchange,
+            rng: StdRng::from_entropy(),
+            packs: HashMap::new(),
+        }
+    }
+
+    /// picks the same pages and maps every time, given the same search results
+    pub fn with_seed(exchange: reqwest::blocking::Client, seed: u64) -> Self {
+        TmxMapSource {
+            exchange,
+            rng: StdRng::seed_from_u64(seed),
+            packs: HashMap::new(),
+        }
+    }
+
+    fn search(&self, query: &str) -> color_eyre::Result<Vec<TmxMapMetadata>> {
+        let url = format!("http://trackmania.exchange/mapsearch2/search?api=on&{query}");
+        let res = self.exchange.get(url).send()?;
+
+        let mut val: serde_json::Value = serde_json::from_str(&res.text()?)?;
+        match val.get_mut("results").map(serde_json::Value::take) {
+            Some(results @ serde_json::Value::Array(_)) => Ok(serde_json::from_value(results)?),
+            _ => Err(eyre!("no results")),
+        }
+    }
+
+    /// asks for the maps of pack `id`, unless we got them not long ago
+    fn refresh_pack(&mut self, id: u64) -> color_eyre::Result<()> {
+        let fresh = self
+            .packs
+            .get(&id)
+            .is_some_and(|(fetched, _)| fetched.elapsed() < PACK_TTL);
+        if !fresh {
+            let url = format!("https://trackmania.exchange/api/mappack/get_mappack_tracks/{id}");
+            let res = self.exchange.get(url).send()?.error_for_status()?;
+            let maps: Vec<TmxMapMetadata> = serde_json::from_str(&res.text()?)?;
+            let maps = maps
+                .into_iter()
+                .map(TmxMapMetadata::into_candidate)
+                .collect::<color_eyre::Result<_>>()?;
+            self.packs.insert(id, (Instant::now(), maps));
+        }
+        Ok(())
+    }
+}
+
+/// `None` instead of an error if the field has an unexpected type
+fn lenient<'de, D: Deserializer<'de>, T: DeserializeOwned>(de: D) -> Result<Option<T>, D::Error> {
+    let value = serde_json::Value::deserialize(de)?;
+    Ok(T::deserialize(value).ok())
+}
+
+/// a map as the tmx search and map info return it
+///
+/// everything but the id is nice to have, so fields that are missing, null
+/// or of the wrong type are `None` instead of failing the whole map.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default)]
+pub struct TmxMapMetadata {
+    #[serde(rename = "TrackID", alias = "MapId", deserialize_with = "lenient")]
+    pub track_id: Option<u64>,
+    #[serde(rename = "TrackUID", alias = "MapUid", deserialize_with = "lenient")]
+    pub uid: Option<String>,
+    #[serde(rename = "Name", deserialize_with = "lenient")]
+    pub name: Option<String>,
+    /// the tmx user that uploaded it
+    #[serde(rename = "Username", deserialize_with = "lenient")]
+    pub username: Option<String>,
+    #[serde(rename = "AuthorLogin", deserialize_with = "lenient")]
+    pub author_login: Option<String>,
+    /// in milliseconds, 0 if tmx does not know it
+    #[serde(rename = "AuthorTime", deserialize_with = "lenient")]
+    pub author_time: Option<u64>,
+    /// tag ids, like "23,37"
+    #[serde(rename = "Tags", deserialize_with = "lenient")]
+    pub tags: Option<String>,
+    #[serde(rename = "LengthName", deserialize_with = "lenient")]
+    pub length: Option<String>,
+    #[serde(rename = "DifficultyName", deserialize_with = "lenient")]
+    pub difficulty: Option<String>,
+    #[serde(rename = "EnvironmentName", deserialize_with = "lenient")]
+    pub environment: Option<String>,
+    #[serde(rename = "AwardCount", deserialize_with = "lenient")]
+    pub award_count: Option<u32>,
+    #[serde(rename = "RatingVoteAverage", deserialize_with = "lenient")]
+    pub rating: Option<f64>,
+}
+
+impl TmxMapMetadata {
+    pub fn tag_ids(&self) -> Vec<u32> {
+        self.tags
+            .iter()
+            .flat_map(|tags| tags.split(','))
+            .filter_map(|t| t.trim().parse().ok())
+            .collect()
+    }
+
+    pub fn into_candidate(self) -> color_eyre::Result<MapCandidate> {
+        let id = self.track_id.context("no track id")?;
+        Ok(MapCandidate {
+            id,
+            tags: self.tag_ids(),
+            name: self.name,
+            author: self.username,
+            author_time: self
+                .author_time
+                .filter(|&ms| ms > 0)
+                .map(Duration::from_millis),
+            length: self.length,
+            difficulty: self.difficulty,
+            environment: self.environment,
+            uid: self.uid,
+            author_login: self.author_login,
+            award_count: self.award_count,
+            rating: self.rating,
+        })
+    }
+}
+
+impl MapSource for TmxMapSource {
+    fn next_candidate(
+        &mut self,
+        search: &MapSearch,
+        history: &History,
+    ) -> color_eyre::Result<MapCandidate> {
+        if let Some(pack) = search.pack_id {
+            match self.refresh_pack(pack).map(|()| &self.packs[&pack].1) {
+                Ok(maps) if maps.is_empty() => {
+                    say!("map pack {pack} has no maps, searching all of tmx")
+                }
+                Ok(maps) => {
+                    return pick_weighted(maps, search, history, &mut self.rng)
+                        .cloned()
+                        .with_context(|| format!("every map of pack {pack} was played recently"));
+                }
+                Err(err) => say!("could not get map pack {pack}: {err}, searching all of tmx"),
+            }
+        }
+        if search.tag_weights.is_empty() {
+            let results = self.search(&format!("random=1&{}", search.to_query()))?;
+            return results
+                .into_iter()
+                .next()
+                .context("no results")?
+                .into_candidate();
+        }
+
+        let mut page = self.rng.gen_range(1..=WEIGHTED_PAGES);
+        let query = |page| format!("page={page}&limit=100&{}", search.to_query());
+        let mut results = self.search(&query(page))?;
+        if results.is_empty() && page > 1 {
+            // fewer maps match than we thought
+            page = 1;
+            results = self.search(&query(page))?;
+        }
+        let candidates = results
+            .into_iter()
+            .map(TmxMapMetadata::into_candidate)
+            .collect::<color_eyre::Result<Vec<_>>>()?;
+        pick_weighted(&candidates, search, history, &mut self.rng)
+            .cloned()
+            .with_context(|| format!("no map on page {page} has any weight"))
+    }
+}
+
+/// returns a scripted sequence of map ids, for testing the rotation logic
+pub struct StubMapSource {
+    ids: VecDeque<u64>,
+}
+
+impl StubMapSource {
+    pub fn new(ids: impl IntoIterator<Item = u64>) -> Self {
+        StubMapSource {
+            ids: ids.into_iter().collect(),
+        }
+    }
+}
+
+impl MapSource for StubMapSource {
+    fn next_candidate(
+        &mut self,
+        _search: &MapSearch,
+        _history: &History,
+    ) -> color_eyre::Result<MapCandidate> {
+        let id = self
+            .ids
+            .pop_front()
+            .ok_or_else(|| eyre!("stub map source is exhausted"))?;
+        Ok(MapCandidate::from_id(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(id: u64, tags: Vec<u32>) -> MapCandidate {
+        MapCandidate {
+            tags,
+            ..MapCandidate::from_id(id)
+        }
+    }
+
+    #[test]
+    fn the_same_seed_picks_the_same_maps() {
+        let candidates: Vec<MapCandidate> = (1..=20).map(|id| candidate(id, vec![1])).collect();
+        let (search, history) = (MapSearch::default(), History::new(10));
+        let picks = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..10)
+                .map(|_| {
+                    pick_weighted(&candidates, &search, &history, &mut rng)
+                        .unwrap()
+                        .id
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(picks(7), picks(7));
+        assert_ne!(picks(7), picks(8));
+    }
+
+    #[test]
+    fn weightless_and_played_maps_are_never_picked() {
+        let candidates = [
+            candidate(1, vec![1]),
+            candidate(2, vec![2]),
+            candidate(3, vec![3]),
+        ];
+        let mut search = MapSearch::default();
+        search.tag_weights.insert(2, 0.0);
+        let mut history = History::new(10);
+        history.push(3);
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..50 {
+            let pick = pick_weighted(&candidates, &search, &history, &mut rng).unwrap();
+            assert_eq!(pick.id, 1);
+        }
+        history.push(1);
+        assert!(pick_weighted(&candidates, &search, &history, &mut rng).is_none());
+    }
+
+    #[test]
+    fn searches_survive_the_query() {
+        let search = MapSearch {
+            tags: vec![3, 41],
+            map_type: "TM_Stunt".to_owned(),
+            author: Some("some one".to_owned()),
+            length: Some(2),
+            length_op: Some(1),
+            ..MapSearch::default()
+        };
+        assert_eq!(MapSearch::from_query(&search.to_query()).unwrap(), search);
+        assert_eq!(
+            MapSearch::from_query(&MapSearch::default().to_query()).unwrap(),
+            MapSearch::default()
+        );
+    }
+
+    #[test]
+    fn search_urls_from_the_browser_are_parsed() {
+        let search = MapSearch::from_tmx_url(
+            "https://trackmania.exchange/mapsearch2?etags=23%2C37&mtype=TM_Race&length=2&page=3",
+        )
+        .unwrap();
+        assert_eq!(search.tags, [23, 37]);
+        assert_eq!(search.length, Some(2));
+        assert_eq!(search.author, None);
+
+        let err = |url| MapSearch::from_tmx_url(url).unwrap_err();
+        assert!(matches!(err("not a url"), TmxUrlError::Url(_)));
+        assert!(matches!(
+            err("https://example.com/mapsearch2?etags=23"),
+            TmxUrlError::WrongHost(host) if host == "example.com"
+        ));
+        assert!(matches!(
+            err("https://trackmania.exchange/maps/123"),
+            TmxUrlError::NotASearch(_)
+        ));
+        assert!(matches!(
+            err("https://trackmania.exchange/mapsearch2?length=long"),
+            TmxUrlError::InvalidParam { name, .. } if name == "length"
+        ));
+        assert!(matches!(
+            err("https://trackmania.exchange/mapsearch2?etags=23,x"),
+            TmxUrlError::InvalidParam { name, .. } if name == "etags"
+        ));
+    }
+}
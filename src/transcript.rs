@@ -0,0 +1,369 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt, fs,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::Path,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// how long the replay waits for each call the controller should make
+const CALL_TIMEOUT: Duration = Duration::from_secs(5);
+/// how long the controller has to be silent before an unordered replay goes
+/// on with the next callback
+const QUIET: Duration = Duration::from_millis(300);
+
+/// one message of a protocol trace
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    /// whether the controller sent it, so it is a call
+    pub outgoing: bool,
+    pub handle: u32,
+    pub body: String,
+}
+
+impl TraceFrame {
+    /// the method of a call or callback, `None` for responses
+    pub fn method(&self) -> Option<&str> {
+        method_name(&self.body)
+    }
+}
+
+/// the messages of a session, as `protocol_trace` records them
+///
+/// a trace of a real server can be played back with [`ReplayServer`], which
+/// checks that the controller makes the same calls again. calls that depend
+/// on timing, like the keep-alive pings, are best left out of the recording
+/// or removed with [`Transcript::without_calls`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Transcript {
+    pub frames: Vec<TraceFrame>,
+}
+
+impl Transcript {
+    /// log prefixes are skipped, lines that do not start a message continue
+    /// the body of the one before
+    pub fn parse(trace: &str) -> Result<Transcript, TranscriptError> {
+        let mut frames: Vec<TraceFrame> = Vec::new();
+        for (n, line) in trace.lines().enumerate() {
+            match parse_line(line) {
+                Some(frame) => frames.push(frame?),
+                None => match frames.last_mut() {
+                    Some(frame) => {
+                        frame.body.push('\n');
+                        frame.body.push_str(line);
+                    }
+                    None if line.trim().is_empty() => {}
+                    None => return Err(TranscriptError::InvalidLine(n + 1)),
+                },
+            }
+        }
+        Ok(Transcript { frames })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Transcript, TranscriptError> {
+        Transcript::parse(&fs::read_to_string(path).map_err(TranscriptError::Io)?)
+    }
+
+    /// the methods the controller called, in order
+    pub fn calls(&self) -> Vec<&str> {
+        self.frames
+            .iter()
+            .filter(|frame| frame.outgoing)
+            .filter_map(TraceFrame::method)
+            .collect()
+    }
+
+    /// drops the calls to `method` together with their responses
+    pub fn without_calls(mut self, method: &str) -> Transcript {
+        let dropped: Vec<u32> = self
+            .frames
+            .iter()
+            .filter(|frame| frame.outgoing && frame.method() == Some(method))
+            .map(|frame| frame.handle)
+            .collect();
+        // responses have no method, callbacks that share a handle stay
+        self.frames.retain(|frame| {
+            !dropped.contains(&frame.handle) || frame.method().is_some_and(|m| m != method)
+        });
+        self
+    }
+}
+
+/// `None` if the line does not start a message
+fn parse_line(line: &str) -> Option<Result<TraceFrame, TranscriptError>> {
+    // the log file puts a timestamp like "2024-01-31 12:00:00 " first
+    let rest = line.get(20..).filter(|_| {
+        let bytes = line.as_bytes();
+        bytes[4] == b'-' && bytes[10] == b' ' && bytes[13] == b':' && bytes[19] == b' '
+    })?;
+    let rest = match rest.strip_prefix('[') {
+        Some(prefixed) => prefixed.split_once("] ")?.1,
+        None => rest,
+    };
+    let (outgoing, rest) = match rest.get(..3)? {
+        "-> " => (true, &rest[3..]),
+        "<- " => (false, &rest[3..]),
+        _ => return None,
+    };
+    let (handle, body) = rest.split_once(' ').unwrap_or((rest, ""));
+    let handle = handle
+        .strip_prefix("0x")
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok());
+    Some(match handle {
+        Some(handle) => Ok(TraceFrame {
+            outgoing,
+            handle,
+            body: body.to_owned(),
+        }),
+        None => Err(TranscriptError::InvalidHandle(line.to_owned())),
+    })
+}
+
+fn method_name(body: &str) -> Option<&str> {
+    let start = body.find("<methodName>")? + "<methodName>".len();
+    let len = body[start..].find("</methodName>")?;
+    Some(body[start..start + len].trim())
+}
+
+#[derive(Debug)]
+pub enum TranscriptError {
+    Io(io::Error),
+    /// the line number of text before the first message
+    InvalidLine(usize),
+    InvalidHandle(String),
+}
+
+impl fmt::Display for TranscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranscriptError::Io(err) => write!(f, "could not read transcript: {err}"),
+            TranscriptError::InvalidLine(n) => write!(f, "line {n} is not part of a message"),
+            TranscriptError::InvalidHandle(line) => write!(f, "invalid handle in {line:?}"),
+        }
+    }
+}
+
+impl std::error::Error for TranscriptError {}
+
+/// what is sent for calls the transcript has no response to, see
+/// [`ReplayServer::start_unordered`]
+const UNKNOWN_CALL: &str = "<?xml version=\"1.0\"?><methodResponse><fault><value><struct>\
+    <member><name>faultCode</name><value><i4>-1000</i4></value></member>\
+    <member><name>faultString</name><value><string>not in the transcript</string></value></member>\
+    </struct></value></fault></methodResponse>";
+
+/// plays the server side of a [`Transcript`] to one controller
+///
+/// it sends the recorded responses and callbacks in their recorded order,
+/// and waits for each recorded call before it goes on. responses are sent
+/// with the handle of the call that was made this time.
+pub struct ReplayServer {
+    addr: SocketAddr,
+    thread: JoinHandle<Result<Transcript, ReplayError>>,
+}
+
+impl ReplayServer {
+    /// listens on a free port of localhost, see [`ReplayServer::addr`]
+    pub fn start(transcript: Transcript) -> io::Result<ReplayServer> {
+        ReplayServer::spawn(transcript, replay)
+    }
+
+    /// like [`ReplayServer::start`], but the calls may come in any order
+    ///
+    /// each call is answered with the next recorded response to its method,
+    /// the last one once they run out, and a fault if there is none. a
+    /// callback is sent once the controller made no calls for a moment. this
+    /// checks what the controller makes of the callbacks, not its calls, see
+    /// [`ReplayServer::finish`] for those.
+    pub fn start_unordered(transcript: Transcript) -> io::Result<ReplayServer> {
+        ReplayServer::spawn(transcript, replay_unordered)
+    }
+
+    fn spawn(
+        transcript: Transcript,
+        replay: fn(&mut Replay, &Transcript) -> Result<(), ReplayError>,
+    ) -> io::Result<ReplayServer> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let thread = thread::spawn(move || {
+            let (stream, _) = listener.accept()?;
+            let mut state = Replay::new(stream)?;
+            replay(&mut state, &transcript)?;
+            Ok(state.received)
+        });
+        Ok(ReplayServer { addr, thread })
+    }
+
+    /// what the `address` of the controller should be
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// waits until every message was played, or the replay went wrong
+    ///
+    /// returns the calls the controller made, with their handles of this time.
+    pub fn finish(self) -> Result<Transcript, ReplayError> {
+        self.thread.join().unwrap_or(Err(ReplayError::Panicked))
+    }
+}
+
+/// the connection to the controller, and the calls it made on it so far
+struct Replay {
+    stream: TcpStream,
+    received: Transcript,
+}
+
+impl Replay {
+    fn new(mut stream: TcpStream) -> io::Result<Replay> {
+        stream.set_read_timeout(Some(CALL_TIMEOUT))?;
+        let hello = b"GBXRemote 2";
+        stream.write_all(&(hello.len() as u32).to_le_bytes())?;
+        stream.write_all(hello)?;
+        Ok(Replay {
+            stream,
+            received: Transcript::default(),
+        })
+    }
+
+    /// the next call, `expected` is what is missing if none comes in time
+    fn read_call(&mut self, expected: &str) -> Result<&TraceFrame, ReplayError> {
+        let (handle, body) = read_frame(&mut self.stream).map_err(|err| match err.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+                ReplayError::Missing(expected.to_owned())
+            }
+            _ => ReplayError::Io(err),
+        })?;
+        self.received.frames.push(TraceFrame {
+            outgoing: true,
+            handle,
+            body,
+        });
+        Ok(&self.received.frames[self.received.frames.len() - 1])
+    }
+
+    fn send(&mut self, handle: u32, body: &str) -> io::Result<()> {
+        self.stream.write_all(&(body.len() as u32).to_le_bytes())?;
+        self.stream.write_all(&handle.to_le_bytes())?;
+        self.stream.write_all(body.as_bytes())
+    }
+}
+
+fn replay(replay: &mut Replay, transcript: &Transcript) -> Result<(), ReplayError> {
+    // recorded handles of calls to the ones used this time
+    let mut handles = HashMap::new();
+    for frame in &transcript.frames {
+        if frame.outgoing {
+            let expected = frame.method().unwrap_or_default();
+            let call = replay.read_call(expected)?;
+            let got = call.method().unwrap_or_default();
+            if got != expected {
+                return Err(ReplayError::Unexpected {
+                    expected: expected.to_owned(),
+                    got: got.to_owned(),
+                });
+            }
+            handles.insert(frame.handle, call.handle);
+        } else {
+            let handle = handles.get(&frame.handle).copied().unwrap_or(frame.handle);
+            replay.send(handle, &frame.body)?;
+        }
+    }
+    Ok(())
+}
+
+fn replay_unordered(replay: &mut Replay, transcript: &Transcript) -> Result<(), ReplayError> {
+    // the recorded responses to each method, in the order they were sent
+    let mut methods = HashMap::new();
+    let mut responses: HashMap<&str, VecDeque<&str>> = HashMap::new();
+    for frame in &transcript.frames {
+        match frame.method() {
+            Some(method) if frame.outgoing => {
+                methods.insert(frame.handle, method);
+            }
+            Some(_) => {}
+            None => {
+                if let Some(method) = methods.get(&frame.handle) {
+                    responses.entry(method).or_default().push_back(&frame.body);
+                }
+            }
+        }
+    }
+
+    replay.stream.set_read_timeout(Some(QUIET))?;
+    for frame in &transcript.frames {
+        if !frame.outgoing && frame.method().is_some() {
+            answer_until_quiet(replay, &mut responses)?;
+            replay.send(frame.handle, &frame.body)?;
+        }
+    }
+    answer_until_quiet(replay, &mut responses)
+}
+
+/// answers calls until the controller stops making them for [`QUIET`]
+fn answer_until_quiet(
+    replay: &mut Replay,
+    responses: &mut HashMap<&str, VecDeque<&str>>,
+) -> Result<(), ReplayError> {
+    loop {
+        let call = match replay.read_call("") {
+            // nothing is missing, the read timeout is just QUIET
+            Err(ReplayError::Missing(_)) => return Ok(()),
+            res => res?,
+        };
+        let (handle, method) = (call.handle, call.method().unwrap_or_default().to_owned());
+        let response = match responses.get_mut(method.as_str()) {
+            Some(queue) if queue.len() > 1 => queue.pop_front().unwrap_or(UNKNOWN_CALL),
+            Some(queue) => queue.front().copied().unwrap_or(UNKNOWN_CALL),
+            None => UNKNOWN_CALL,
+        };
+        replay.send(handle, response)?;
+    }
+}
+
+fn read_frame(stream: &mut TcpStream) -> io::Result<(u32, String)> {
+    let mut header = [0; 8];
+    stream.read_exact(&mut header)?;
+    let len = u32::from_le_bytes(header[..4].try_into().unwrap());
+    let handle = u32::from_le_bytes(header[4..].try_into().unwrap());
+    let mut body = vec![0; len as usize];
+    stream.read_exact(&mut body)?;
+    let body =
+        String::from_utf8(body).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok((handle, body))
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    /// the controller did not make this call in time
+    Missing(String),
+    /// the controller called `got` where the transcript has `expected`
+    Unexpected {
+        expected: String,
+        got: String,
+    },
+    Panicked,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "replay connection failed: {err}"),
+            ReplayError::Missing(method) => write!(f, "{method} was never called"),
+            ReplayError::Unexpected { expected, got } => {
+                write!(f, "expected a call to {expected}, got {got}")
+            }
+            ReplayError::Panicked => write!(f, "the replay thread panicked"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(err: io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
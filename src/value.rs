@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use base64::Engine;
+use chrono::{NaiveDateTime, SecondsFormat};
+use dxr::{TryFromValue, Value};
+
+use crate::ServerTimezone;
+
+/// converts any xml-rpc value into the equivalent json
+///
+/// base64 values become base64 strings and dates become rfc 3339 strings in
+/// utc, since json has no types for them. see [`ServerTimezone`] for how the
+/// dates are read.
+pub fn value_to_json(value: &Value, zone: ServerTimezone) -> serde_json::Value {
+    // dxr does not let us look at the type of a value, so we try each type in turn
+    if let Ok(int) = i32::try_from_value(value) {
+        int.into()
+    } else if let Ok(boolean) = bool::try_from_value(value) {
+        boolean.into()
+    } else if let Ok(string) = String::try_from_value(value) {
+        string.into()
+    } else if let Ok(double) = f64::try_from_value(value) {
+        double.into()
+    } else if let Ok(date) = NaiveDateTime::try_from_value(value) {
+        match zone.to_utc(date) {
+            Some(date) => date.to_rfc3339_opts(SecondsFormat::Secs, true).into(),
+            // keep what the server sent rather than inventing an offset
+            None => date.format("%Y-%m-%dT%H:%M:%S").to_string().into(),
+        }
+    } else if let Some(bytes) = value_as_bytes(value) {
+        base64::engine::general_purpose::STANDARD
+            .encode(bytes)
+            .into()
+    } else if let Ok(array) = Vec::<Value>::try_from_value(value) {
+        array.iter().map(|v| value_to_json(v, zone)).collect()
+    } else if let Ok(members) = HashMap::<String, Value>::try_from_value(value) {
+        members
+            .iter()
+            .map(|(name, value)| (name.clone(), value_to_json(value, zone)))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    } else {
+        serde_json::Value::Null
+    }
+}
+
+/// the decoded contents of a base64 value, `None` for any other type
+pub fn value_as_bytes(value: &Value) -> Option<Vec<u8>> {
+    Vec::<u8>::try_from_value(value).ok()
+}
+
+/// what getters for settings return, the value for the next map can differ
+/// from the current one
+#[allow(non_snake_case)]
+#[derive(TryFromValue, Debug, Clone, PartialEq)]
+pub struct CurrentNext<T> {
+    pub CurrentValue: T,
+    pub NextValue: T,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, NaiveDate};
+    use serde_json::json;
+
+    use super::*;
+
+    fn parse(xml: &str) -> Value {
+        dxr::deserialize_xml(xml).unwrap()
+    }
+
+    #[test]
+    fn scalars() {
+        let utc = ServerTimezone::Utc;
+        assert_eq!(value_to_json(&Value::i4(-3), utc), json!(-3));
+        assert_eq!(value_to_json(&Value::boolean(true), utc), json!(true));
+        assert_eq!(
+            value_to_json(&Value::string("a<b".to_owned()), utc),
+            json!("a<b")
+        );
+        assert_eq!(value_to_json(&Value::double(1.5), utc), json!(1.5));
+    }
+
+    #[test]
+    fn nested_structs_and_arrays() {
+        let value = parse(
+            "<value><struct>\
+                <member><name>Login</name><value><string>alice</string></value></member>\
+                <member><name>Times</name><value><array><data>\
+                    <value><i4>1</i4></value>\
+                    <value><array><data><value><boolean>0</boolean></value></data></array></value>\
+                </data></array></value></member>\
+                <member><name>Team</name><value><struct>\
+                    <member><name>Id</name><value><i4>2</i4></value></member>\
+                </struct></value></member>\
+            </struct></value>",
+        );
+        assert_eq!(
+            value_to_json(&value, ServerTimezone::Utc),
+            json!({ "Login": "alice", "Times": [1, [false]], "Team": { "Id": 2 } })
+        );
+    }
+
+    #[test]
+    fn empty_array_and_struct() {
+        let utc = ServerTimezone::Utc;
+        assert_eq!(
+            value_to_json(&parse("<value><array><data></data></array></value>"), utc),
+            json!([])
+        );
+        assert_eq!(
+            value_to_json(&parse("<value><struct></struct></value>"), utc),
+            json!({})
+        );
+    }
+
+    #[test]
+    fn base64_is_a_string() {
+        let value = Value::base64(b"GBX\0\x01".to_vec());
+        assert_eq!(
+            value_to_json(&value, ServerTimezone::Utc),
+            json!("R0JYAAE=")
+        );
+        assert_eq!(value_as_bytes(&value), Some(b"GBX\0\x01".to_vec()));
+        assert_eq!(value_as_bytes(&Value::i4(1)), None);
+    }
+
+    #[test]
+    fn dates_are_rfc_3339_in_utc() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 31)
+            .unwrap()
+            .and_hms_opt(12, 30, 5)
+            .unwrap();
+        let value = Value::datetime(date);
+        assert_eq!(
+            value_to_json(&value, ServerTimezone::Utc),
+            json!("2024-01-31T12:30:05Z")
+        );
+        let plus_two = ServerTimezone::Offset(FixedOffset::east_opt(2 * 3600).unwrap());
+        assert_eq!(
+            value_to_json(&value, plus_two),
+            json!("2024-01-31T10:30:05Z")
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+use crate::{Client, ClientError, CurrentNext, MapCandidate};
+
+/// lets players pick the next map from a few candidates during the podium
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MapVoteSettings {
+    pub candidates: usize,
+    /// keep this below the podium time of the mode, or the vote only decides
+    /// the map after the next one
+    #[serde(rename = "window_secs", with = "crate::config::secs")]
+    pub window: Duration,
+}
+
+impl Default for MapVoteSettings {
+    fn default() -> Self {
+        MapVoteSettings {
+            candidates: 3,
+            window: Duration::from_secs(10),
+        }
+    }
+}
+
+/// a vote that is still open
+#[derive(Debug, Clone)]
+pub(crate) struct MapVote {
+    candidates: Vec<MapCandidate>,
+    /// login to the index of the candidate they voted for
+    votes: HashMap<String, usize>,
+    ends: Instant,
+}
+
+impl MapVote {
+    /// the candidate with the most votes, the first one listed wins a tie
+    fn winner(&self) -> Option<(&MapCandidate, usize)> {
+        let mut counts = vec![0; self.candidates.len()];
+        for &choice in self.votes.values() {
+            counts[choice] += 1;
+        }
+        let (index, &count) = counts
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, &count)| count)?;
+        (count > 0).then(|| (&self.candidates[index], count))
+    }
+}
+
+impl Client {
+    /// picks the candidates and announces them, once per map
+    pub(crate) fn start_map_vote(&mut self) -> Result<(), ClientError> {
+        let Some(settings) = self.config().map_vote.clone() else {
+            return Ok(());
+        };
+        if self.vote_held {
+            return Ok(());
+        }
+        self.vote_held = true;
+        if !self.enough_voters() {
+            // nobody would be told, so nobody needs to know
+            if !self.players.is_empty() {
+                self.call::<bool>("ChatSendServerMessage", "not enough players to vote")?;
+            }
+            return Ok(());
+        }
+
+        // only the winner counts as picked, see `end_map_vote`
+        let mut candidates: Vec<MapCandidate> = Vec::new();
+        for _ in 0..settings.candidates {
+            let picked: Vec<u64> = candidates.iter().map(|candidate| candidate.id).collect();
+            match self.peek_random_map(&picked) {
+                Ok(candidate) => candidates.push(candidate),
+                Err(err) => self.report_error("picking a map to vote on", err),
+            }
+        }
+        if candidates.len() < 2 {
+            return Ok(());
+        }
+
+        let msg = format!(
+            "vote for the next map with /1 to /{} within {}s:",
+            candidates.len(),
+            settings.window.as_secs()
+        );
+        self.call::<bool>("ChatSendServerMessage", msg.as_str())?;
+        for (n, candidate) in candidates.iter().enumerate() {
+            let msg = format!(
+                "/{}: {} ({})",
+                n + 1,
+                candidate.name.as_deref().unwrap_or("unnamed map"),
+                candidate
+                    .difficulty
+                    .as_deref()
+                    .unwrap_or("unknown difficulty")
+            );
+            self.call::<bool>("ChatSendServerMessage", msg.as_str())?;
+        }
+        self.vote = Some(MapVote {
+            candidates,
+            votes: HashMap::new(),
+            ends: Instant::now() + settings.window,
+        });
+        Ok(())
+    }
+
+    /// whether there are enough players for any vote to count, spectators
+    /// do not count
+    pub(crate) fn enough_voters(&self) -> bool {
+        self.active_player_count() >= self.config().min_vote_players
+    }
+
+    /// for the votes the server runs itself, like `/callvote` in the game
+    pub fn set_call_vote_timeout(&mut self, timeout: Duration) -> Result<(), ClientError> {
+        let ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+        let suc: bool = self.call("SetCallVoteTimeOut", ms)?;
+        if !suc {
+            return Err(ClientError::Rejected("SetCallVoteTimeOut"));
+        }
+        Ok(())
+    }
+
+    /// in ms, the next value applies from the next map on
+    pub fn get_call_vote_timeout(&mut self) -> Result<CurrentNext<i32>, ClientError> {
+        self.call("GetCallVoteTimeOut", ())
+    }
+
+    /// how long the message loop may wait before the vote has to be counted
+    pub(crate) fn vote_timeout(&self) -> Option<Duration> {
+        let vote = self.vote.as_ref()?;
+        // like the keep-alive, only the outermost loop counts the vote
+        if !self.calls.is_empty() {
+            return None;
+        }
+        Some(vote.ends.saturating_duration_since(Instant::now()))
+    }
+
+    /// `/1`, `/2` and so on, players can change their mind until the vote ends
+    ///
+    /// returns whether `command` was a vote.
+    pub(crate) fn vote_command(&mut self, login: &str, command: &str) -> Result<bool, ClientError> {
+        let Some(vote) = &mut self.vote else {
+            return Ok(false);
+        };
+        let Ok(choice) = command.parse::<usize>() else {
+            return Ok(false);
+        };
+        if choice == 0 || choice > vote.candidates.len() {
+            return Ok(false);
+        }
+        if !self.enough_voters() {
+            self.chat_send_to_login(login, "not enough players to vote")?;
+            return Ok(true);
+        }
+        let Some(vote) = &mut self.vote else {
+            return Ok(false);
+        };
+        vote.votes.insert(login.to_owned(), choice - 1);
+        self.chat_send_to_login(login, &format!("you voted for /{choice}"))?;
+        Ok(true)
+    }
+
+    /// inserts the winner as the next map, without votes the map that was
+    /// picked at random stays next
+    pub(crate) fn end_map_vote(&mut self) -> Result<(), ClientError> {
+        let Some(vote) = self.vote.take() else {
+            return Ok(());
+        };
+        let Some((winner, count)) = vote.winner() else {
+            self.call::<bool>(
+                "ChatSendServerMessage",
+                "nobody voted, the next map is random",
+            )?;
+            return Ok(());
+        };
+        let msg = format!(
+            "{} won with {count} votes",
+            winner.name.as_deref().unwrap_or("unnamed map")
+        );
+        self.call::<bool>("ChatSendServerMessage", msg.as_str())?;
+
+        let rel_path = match self.fetch_map(winner.id) {
+            Ok(rel_path) => rel_path,
+            Err(ClientError::Download(err)) => {
+                self.report_error("downloading the winning map", err);
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
+        self.insert_map(&rel_path)?;
+        let suc: bool = self.call("ChooseNextMap", rel_path.as_str())?;
+        if !suc {
+            return Err(ClientError::Rejected("ChooseNextMap"));
+        }
+        self.record_pick(winner);
+        self.apply_time_budget(winner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::MapVote;
+    use crate::testing::{test_client, test_config, MockServer};
+    use crate::{MapCandidate, MapVoteSettings, StubMapSource};
+
+    fn vote(votes: &[(&str, usize)]) -> MapVote {
+        MapVote {
+            candidates: (1..=3).map(MapCandidate::from_id).collect(),
+            votes: votes
+                .iter()
+                .map(|&(login, choice)| (login.to_owned(), choice))
+                .collect(),
+            ends: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn the_first_listed_candidate_wins_a_tie() {
+        assert!(vote(&[]).winner().is_none());
+        let tie = vote(&[("a", 2), ("b", 1), ("c", 2), ("d", 1)]);
+        let (winner, count) = tie.winner().unwrap();
+        assert_eq!((winner.id, count), (2, 2));
+        let clear = vote(&[("a", 2), ("b", 2), ("c", 0)]);
+        let (winner, count) = clear.winner().unwrap();
+        assert_eq!((winner.id, count), (3, 2));
+    }
+
+    #[test]
+    fn candidates_do_not_count_as_played() {
+        let server = MockServer::start(|_, _| None);
+        let mut config = test_config(&server);
+        config.map_vote = Some(MapVoteSettings::default());
+        config.max_retries_per_map = 1;
+        let mut client = test_client(config);
+        // a candidate that comes up twice is only offered once
+        client.source = Box::new(StubMapSource::new([1, 1, 2, 3]));
+
+        client.start_map_vote().unwrap();
+        let ids: Vec<u64> = client
+            .vote
+            .as_ref()
+            .unwrap()
+            .candidates
+            .iter()
+            .map(|c| c.id)
+            .collect();
+        assert_eq!(ids, [1, 2, 3]);
+        assert_eq!(client.history().len(), 0);
+        assert_eq!(client.retries_left, 1);
+        // the announcement and one line per candidate
+        assert_eq!(server.params_of("ChatSendServerMessage").len(), 4);
+    }
+}
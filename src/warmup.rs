@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::{bool_setting, Client, ClientError, WaitError};
+
+/// what the mode script says about its warm-up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct WarmUpStatus {
+    /// whether the mode has a warm-up at all
+    pub available: bool,
+    pub active: bool,
+}
+
+impl Client {
+    /// turns the warm-up of legacy modes on or off, script modes fault
+    pub fn set_warm_up(&mut self, warm_up: bool) -> Result<(), ClientError> {
+        self.set_bool_setting(bool_setting::SET_WARM_UP, warm_up)
+    }
+
+    /// whether a legacy mode is warming up, script modes fault
+    pub fn get_warm_up(&mut self) -> Result<bool, ClientError> {
+        Ok(self
+            .get_bool_setting(bool_setting::GET_WARM_UP)?
+            .CurrentValue)
+    }
+
+    /// asks the mode script about its warm-up and waits for the answer
+    pub fn get_warm_up_status(&mut self, timeout: Duration) -> Result<WarmUpStatus, WaitError> {
+        let response_id = format!("warmup-{}", rand::random::<u32>());
+        self.trigger_mode_script_event_array("Trackmania.WarmUp.GetStatus", &[&response_id])?;
+        let json = self.wait_for_script_callback("Trackmania.WarmUp.Status", timeout)?;
+        let status: WarmUpStatus = serde_json::from_value(json).map_err(|err| {
+            ClientError::Decode(format!("invalid Trackmania.WarmUp.Status: {err}"))
+        })?;
+        self.in_warmup = status.active;
+        Ok(status)
+    }
+
+    /// whether the mode script said the warm-up started, and not yet that it
+    /// ended
+    ///
+    /// legacy modes do not tell us, so this stays false for them.
+    pub fn in_warmup(&self) -> bool {
+        self.in_warmup
+    }
+
+    /// ends the warm-up right away
+    pub fn skip_warm_up(&mut self) -> Result<(), ClientError> {
+        match self.trigger_mode_script_event_array("Trackmania.WarmUp.ForceStop", &[]) {
+            // not a script mode, or one that has no warm-up to stop
+            Err(ClientError::Fault(_) | ClientError::Rejected(_)) => self.set_warm_up(false),
+            res => res,
+        }
+    }
+
+    pub(crate) fn handle_warm_up_callback(&mut self, name: &str) {
+        match name {
+            "Trackmania.WarmUp.Start" => self.in_warmup = true,
+            "Trackmania.WarmUp.End" => self.in_warmup = false,
+            _ => {}
+        }
+    }
+
+    /// `/skipwarmup` ends the warm-up for everyone
+    pub(crate) fn skipwarmup_command(&mut self, login: &str) -> Result<(), ClientError> {
+        let reply = match self.skip_warm_up() {
+            Ok(()) => "warm-up skipped".to_owned(),
+            Err(err) if err.is_connection() => return Err(err),
+            Err(err) => format!("could not skip the warm-up: {err}"),
+        };
+        self.chat_send_to_login(login, &reply)
+    }
+}
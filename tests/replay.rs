@@ -0,0 +1,143 @@
+//! plays the sessions in `tests/transcripts` back to the controller
+//!
+//! they are synthetic: this controller recorded them with `protocol_trace`
+//! against a scripted server, not a real dedicated server. so the order of
+//! the calls in them is only what the controller happened to do, and the
+//! replays are unordered. what is checked is the callbacks they carry and
+//! what the controller made of them. `{maps_dir}` stands for where the
+//! server keeps its maps.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use dxr::{MethodCall, TryFromParams, TryFromValue, Value};
+use trackmania_controller::{
+    Client, Config, MapInfo, MemoryStorage, ReplayServer, StubMapSource, Transcript,
+};
+
+fn load(name: &str, maps_dir: &Path) -> Transcript {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/transcripts")
+        .join(name);
+    let trace = fs::read_to_string(path)
+        .unwrap()
+        .replace("{maps_dir}", &format!("{}/", maps_dir.display()));
+    Transcript::parse(&trace).unwrap()
+}
+
+/// the calls or callbacks in `transcript`, parsed
+fn calls(transcript: &Transcript, outgoing: bool) -> Vec<(String, Vec<Value>)> {
+    transcript
+        .frames
+        .iter()
+        .filter(|frame| frame.outgoing == outgoing && frame.method().is_some())
+        .map(|frame| {
+            let call: MethodCall = dxr::deserialize_xml(&frame.body).unwrap();
+            (call.name().to_owned(), call.params())
+        })
+        .collect()
+}
+
+/// an empty directory of its own for each test
+fn maps_dir(name: &str) -> PathBuf {
+    let name = format!("controller-replay-{name}-{}", std::process::id());
+    let dir = std::env::temp_dir().join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// the smallest map file with a header, a GBX v6 with only the xml chunk
+fn gbx_map(uid: &str, name: &str) -> Vec<u8> {
+    let xml = format!(
+        "<header type=\"map\"><ident uid=\"{uid}\" name=\"{name}\" author=\"someone\"/>\
+         <desc envir=\"Stadium\" maptype=\"TrackMania\\TM_Race\"/></header>"
+    );
+    let mut chunk = (xml.len() as u32).to_le_bytes().to_vec();
+    chunk.extend(xml.as_bytes());
+    let mut file = b"GBX".to_vec();
+    file.extend(6u16.to_le_bytes());
+    file.extend(b"BUCR");
+    file.extend(0x0304_3000u32.to_le_bytes());
+    file.extend(0u32.to_le_bytes());
+    file.extend(1u32.to_le_bytes());
+    file.extend(0x0304_3005u32.to_le_bytes());
+    file.extend((chunk.len() as u32).to_le_bytes());
+    file.extend(chunk);
+    file
+}
+
+/// connects and handles messages until the replay hangs up
+fn run(
+    server: &ReplayServer,
+    storage: &MemoryStorage,
+    ids: impl IntoIterator<Item = u64>,
+) -> Client {
+    let config = Config {
+        address: server.addr().to_string(),
+        ..Config::default()
+    };
+    let source = Box::new(StubMapSource::new(ids));
+    let mut client = Client::with_source(config, reqwest::blocking::Client::new(), source);
+    client.set_storage(Box::new(storage.clone()));
+    client.connect().unwrap();
+    let err = client.run_messages().unwrap_err();
+    assert!(err.is_connection(), "{err}");
+    client
+}
+
+#[test]
+fn begin_map_inserts_the_next_map() {
+    let dir = maps_dir("begin-map");
+    let transcript = load("synthetic_begin_map.log", &dir);
+    let callbacks = calls(&transcript, false);
+    assert_eq!(callbacks.len(), 1);
+    assert_eq!(callbacks[0].0, "ManiaPlanet.BeginMap");
+    let map = MapInfo::try_from_value(&callbacks[0].1[0]).unwrap();
+    assert_eq!(map.FileName, "Campaign/A01.Map.Gbx");
+
+    // already downloaded, so nothing comes from tmx
+    fs::write(dir.join("1.Map.Gbx"), gbx_map("u2", "Downloaded")).unwrap();
+    let server = ReplayServer::start_unordered(transcript).unwrap();
+    let storage = MemoryStorage::default();
+    let client = run(&server, &storage, [1]);
+    let made = calls(&server.finish().unwrap(), true);
+
+    assert!(client.history().contains(1));
+    let catalog = storage.catalog().unwrap();
+    let entry = catalog.get("1.Map.Gbx").unwrap();
+    assert_eq!(entry.tmx_id(), Some(1));
+    assert_eq!(entry.uid.as_deref(), Some("u2"));
+    let inserted = made.iter().find(|(method, _)| method == "InsertMap");
+    assert_eq!(inserted.unwrap().1, [Value::string("1.Map.Gbx".to_owned())]);
+}
+
+#[test]
+fn chat_commands_answer_players_that_joined() {
+    let dir = maps_dir("player-chat");
+    let transcript = load("synthetic_player_chat.log", &dir);
+    let callbacks = calls(&transcript, false);
+    let names: Vec<&str> = callbacks.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        names,
+        ["ManiaPlanet.PlayerConnect", "ManiaPlanet.PlayerChat"]
+    );
+    let (_, login, text, _) =
+        <(i32, String, String, bool)>::try_from_params(&callbacks[1].1).unwrap();
+    assert_eq!((login.as_str(), text.as_str()), ("bob", "/nextinfo"));
+
+    let server = ReplayServer::start_unordered(transcript).unwrap();
+    run(&server, &MemoryStorage::default(), []);
+    let made = calls(&server.finish().unwrap(), true);
+
+    let replies: Vec<(String, String)> = made
+        .iter()
+        .filter(|(method, _)| method == "ChatSendServerMessageToLogin")
+        .map(|(_, params)| <(String, String)>::try_from_params(params).unwrap())
+        .collect();
+    assert_eq!(replies.len(), 1);
+    assert!(replies[0].0.starts_with("next map: Downloaded by Someone"));
+    assert_eq!(replies[0].1, "bob");
+}